@@ -98,18 +98,8 @@ fn non_empty_owned(value: String) -> Option<String> {
 fn configure_runtime_env() -> Result<()> {
     if let Ok(cef_path) = std::env::var("CEF_PATH") {
         if !cef_path.is_empty() {
-            let current_path = std::env::var("PATH").unwrap_or_default();
-            let has_cef_path = current_path
-                .split(';')
-                .any(|entry| entry.eq_ignore_ascii_case(&cef_path));
-
-            if !has_cef_path {
-                let new_path = format!("{current_path};{cef_path}");
-                // Keep CEF binaries discoverable (e.g. libcef.dll) in dev runs.
-                unsafe {
-                    std::env::set_var("PATH", new_path);
-                }
-            }
+            // Keep CEF binaries discoverable (libcef.dll / libcef.so) in dev runs.
+            shared::cef_runtime::add_library_dir(std::path::Path::new(&cef_path))?;
         }
     }
 
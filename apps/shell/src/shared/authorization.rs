@@ -0,0 +1,129 @@
+use pulse_core::protocol::{HostCommand, HostError, HostErrorCode};
+use pulse_core::{Capability, CapabilityError, CapabilityKind};
+use pulse_state::{grants, persistence};
+
+use super::persistence_worker;
+
+/// Grant holder for the trusted Pulse UI browser.
+pub const UI_HOLDER: &str = "pulse-ui";
+
+pub fn unscoped(kind: CapabilityKind) -> Capability {
+    Capability::new(kind, None).expect("unscoped capability kinds take no scope")
+}
+
+/// Capabilities the UI must hold to run `command`. Bundle paths are checked as filesystem
+/// scopes; a path that is not absolute fails instead.
+pub fn required_capabilities(command: &HostCommand) -> Result<Vec<Capability>, CapabilityError> {
+    let kind = match command {
+        HostCommand::EnsureTab { .. }
+        | HostCommand::RestoreTabs { .. }
+        | HostCommand::NavigateTab { .. }
+        | HostCommand::BrowserBack { .. }
+        | HostCommand::BrowserForward { .. }
+        | HostCommand::BrowserReload { .. }
+        | HostCommand::BrowserStop { .. } => CapabilityKind::BrowserNavigate,
+        HostCommand::ActivateTab { .. } => CapabilityKind::WorkspaceNavigate,
+        HostCommand::CloseTab { .. } | HostCommand::SetTabPinned { .. } => {
+            CapabilityKind::WorkspaceMutate
+        }
+        HostCommand::SetContentBounds { .. }
+        | HostCommand::SetContentVisible { .. }
+        | HostCommand::UiReady => CapabilityKind::UiLayout,
+        HostCommand::LoadState
+        | HostCommand::RecoveryReport
+        | HostCommand::ListSnapshots
+        | HostCommand::ListProfiles => CapabilityKind::WorkspaceRead,
+        HostCommand::SaveState { .. } | HostCommand::RestoreSnapshot { .. } => {
+            CapabilityKind::WorkspaceMutate
+        }
+        HostCommand::Workspace { request } if request.is_mutation() => {
+            CapabilityKind::WorkspaceMutate
+        }
+        HostCommand::Workspace { .. } => CapabilityKind::WorkspaceRead,
+        HostCommand::ExportBundle { path } => {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| persistence::exports_dir().to_string_lossy().to_string());
+            return Ok(vec![
+                unscoped(CapabilityKind::WorkspaceRead),
+                Capability::new(CapabilityKind::FsWrite, Some(&path))?,
+            ]);
+        }
+        HostCommand::ImportBundle { path, .. } => {
+            return Ok(vec![
+                unscoped(CapabilityKind::WorkspaceMutate),
+                Capability::new(CapabilityKind::FsRead, Some(path))?,
+            ]);
+        }
+        HostCommand::ListGrants | HostCommand::CapabilityAudit => CapabilityKind::GrantsRead,
+        HostCommand::RevokeGrant { .. } => CapabilityKind::GrantsRevoke,
+    };
+    Ok(vec![unscoped(kind)])
+}
+
+/// Evaluates every capability `command` requires against the UI's grants, stopping at the
+/// first denial. Runs on the UI thread, so checks are decided in memory and their audit
+/// entries written by the persistence thread; allowed `ui.layout` checks, which follow
+/// every resize, are not audited.
+pub fn authorize(command: &str, required: &[Capability]) -> Result<(), HostError> {
+    let outcome = check_all(command, required);
+    persistence_worker::flush_audit();
+    outcome
+}
+
+fn check_all(command: &str, required: &[Capability]) -> Result<(), HostError> {
+    for capability in required {
+        let audit_allowed = capability.kind() != CapabilityKind::UiLayout;
+        let allowed =
+            grants::check_queued(UI_HOLDER, capability, audit_allowed).unwrap_or_else(|error| {
+                eprintln!(
+                    "Pulse capability warning: checking '{capability}' for {command}: {error:#}"
+                );
+                false
+            });
+        if !allowed {
+            let message =
+                format!("'{UI_HOLDER}' holds no grant of '{capability}' required by {command}");
+            eprintln!("Pulse capability warning: denied {command}; {message}");
+            return Err(HostError {
+                code: HostErrorCode::CapabilityDenied,
+                message,
+                capability: Some(capability.clone()),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Gives the UI, once per profile, what it could do before grants were enforced: drive
+/// tabs, layout and the workspace, manage grants, and read and write the profile's
+/// exports directory. Revoked defaults stay revoked.
+pub fn seed_ui_grants() {
+    let mut defaults = [
+        CapabilityKind::BrowserNavigate,
+        CapabilityKind::WorkspaceRead,
+        CapabilityKind::WorkspaceMutate,
+        CapabilityKind::WorkspaceNavigate,
+        CapabilityKind::UiLayout,
+        CapabilityKind::GrantsRead,
+        CapabilityKind::GrantsRevoke,
+    ]
+    .map(unscoped)
+    .to_vec();
+    let exports = persistence::exports_dir().to_string_lossy().to_string();
+    for kind in [CapabilityKind::FsRead, CapabilityKind::FsWrite] {
+        match Capability::new(kind, Some(&exports)) {
+            Ok(capability) => defaults.push(capability),
+            Err(error) => eprintln!("Pulse capability warning: {error}"),
+        }
+    }
+
+    match grants::seed_defaults(UI_HOLDER, &defaults) {
+        Ok(true) => eprintln!(
+            "Pulse granted {} default capabilities to '{UI_HOLDER}'.",
+            defaults.len()
+        ),
+        Ok(false) => {}
+        Err(error) => eprintln!("Pulse capability warning: seeding default grants: {error:#}"),
+    }
+}
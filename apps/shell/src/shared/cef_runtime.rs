@@ -0,0 +1,200 @@
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os = "windows")]
+pub const LIBRARY_FILE: &str = "libcef.dll";
+#[cfg(target_os = "linux")]
+pub const LIBRARY_FILE: &str = "libcef.so";
+#[cfg(target_os = "macos")]
+pub const LIBRARY_FILE: &str = "Chromium Embedded Framework.framework";
+
+/// Variable the OS loader searches for `LIBRARY_FILE`. macOS loads the framework from the
+/// app bundle instead.
+#[cfg(target_os = "windows")]
+const LIBRARY_PATH_VAR: Option<&str> = Some("PATH");
+#[cfg(target_os = "linux")]
+const LIBRARY_PATH_VAR: Option<&str> = Some("LD_LIBRARY_PATH");
+#[cfg(target_os = "macos")]
+const LIBRARY_PATH_VAR: Option<&str> = None;
+
+const LOCALES_DIR: &str = "locales";
+/// CEF refuses to start without these next to `LIBRARY_FILE`.
+const DATA_FILES: &[&str] = &["icudtl.dat", "v8_context_snapshot.bin", "resources.pak"];
+
+/// Where this launch found the CEF runtime. Everything sits in one directory, as in the
+/// bundles `export-cef-dir` produces and in the files synced next to the executable.
+#[cfg(not(target_os = "macos"))]
+#[derive(Debug)]
+pub struct RuntimeLayout {
+    pub runtime_dir: PathBuf,
+    pub locales_dir: PathBuf,
+}
+
+/// Appends `dir` to the loader search path (`PATH` or `LD_LIBRARY_PATH`) unless it is
+/// already listed. The loader reads the variable when a process starts, so this reaches
+/// CEF's subprocesses; the browser process itself already found the library.
+pub fn add_library_dir(dir: &Path) -> Result<()> {
+    let Some(var) = LIBRARY_PATH_VAR else {
+        return Ok(());
+    };
+
+    let current = std::env::var_os(var).unwrap_or_default();
+    // An empty entry would mean the working directory to the Linux loader.
+    let mut entries = std::env::split_paths(&current)
+        .filter(|entry| !entry.as_os_str().is_empty())
+        .collect::<Vec<_>>();
+    if entries.iter().any(|entry| same_dir(entry, dir)) {
+        return Ok(());
+    }
+
+    entries.push(dir.to_path_buf());
+    let joined: OsString = std::env::join_paths(entries)
+        .with_context(|| format!("Cannot add '{}' to {var}", dir.display()))?;
+    unsafe {
+        std::env::set_var(var, joined);
+    }
+    Ok(())
+}
+
+/// Directories that hold or may hold the runtime, in order: where the loader found the
+/// library (Linux), the executable's directory, `CEF_PATH`, then loader search path entries
+/// that contain the library.
+pub fn candidate_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    #[cfg(target_os = "linux")]
+    dirs.extend(loaded_library_dir());
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+    {
+        dirs.push(exe_dir);
+    }
+    if let Some(cef_path) = std::env::var_os("CEF_PATH").filter(|path| !path.is_empty()) {
+        dirs.push(PathBuf::from(cef_path));
+    }
+    if let Some(var) = LIBRARY_PATH_VAR {
+        let current = std::env::var_os(var).unwrap_or_default();
+        dirs.extend(
+            std::env::split_paths(&current)
+                .filter(|dir| !dir.as_os_str().is_empty() && dir.join(LIBRARY_FILE).exists()),
+        );
+    }
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !unique.iter().any(|seen| same_dir(seen, &dir)) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Finds the runtime, or returns a message naming every missing file and where it looked.
+#[cfg(not(target_os = "macos"))]
+pub fn discover() -> Result<RuntimeLayout> {
+    let candidates = candidate_dirs();
+    let Some(runtime_dir) = candidates
+        .iter()
+        .find(|dir| dir.join(LIBRARY_FILE).exists())
+        .cloned()
+    else {
+        return Err(anyhow!(
+            "CEF runtime not found: no {LIBRARY_FILE} in any of:{}{}\nSet CEF_PATH to an extracted CEF bundle{}.",
+            candidates
+                .iter()
+                .map(|dir| format!("\n  {}", dir.display()))
+                .collect::<String>(),
+            LIBRARY_PATH_VAR
+                .map(|var| format!("\n  (or any {var} entry)"))
+                .unwrap_or_default(),
+            LIBRARY_PATH_VAR
+                .map(|var| format!(" or add its directory to {var}"))
+                .unwrap_or_default()
+        ));
+    };
+
+    let locales_dir = runtime_dir.join(LOCALES_DIR);
+    let mut missing = DATA_FILES
+        .iter()
+        .map(|file| runtime_dir.join(file))
+        .filter(|path| !path.exists())
+        .collect::<Vec<_>>();
+    if !locales_dir.is_dir() {
+        missing.push(locales_dir.clone());
+    }
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "CEF runtime in '{}' is incomplete; missing:{}\nCopy the whole CEF bundle next to {LIBRARY_FILE}.",
+            runtime_dir.display(),
+            missing
+                .iter()
+                .map(|path| format!("\n  {}", path.display()))
+                .collect::<String>()
+        ));
+    }
+
+    Ok(RuntimeLayout {
+        runtime_dir,
+        locales_dir,
+    })
+}
+
+/// One `name exists=bool` line per runtime file in `dir`, for diagnostics.
+pub fn describe_dir(dir: &Path) -> Vec<String> {
+    std::iter::once(LIBRARY_FILE)
+        .chain(DATA_FILES.iter().copied())
+        .chain(std::iter::once(LOCALES_DIR))
+        .map(|file| format!("{file} exists={}", dir.join(file).exists()))
+        .collect()
+}
+
+/// CEF resolves its data files relative to the `libcef.so` that is actually mapped, which
+/// may come from an rpath or the system library dirs rather than any variable.
+#[cfg(target_os = "linux")]
+fn loaded_library_dir() -> Option<PathBuf> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines()
+        .filter_map(mapped_path)
+        .map(Path::new)
+        .find(|path| path.file_name().is_some_and(|name| name == LIBRARY_FILE))
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+}
+
+#[cfg(target_os = "linux")]
+/// The pathname of a `/proc/self/maps` line: everything after the fifth field (address,
+/// perms, offset, dev, inode), so paths with spaces stay whole.
+fn mapped_path(line: &str) -> Option<&str> {
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+    Some(rest.trim_start()).filter(|path| !path.is_empty())
+}
+
+fn same_dir(left: &Path, right: &Path) -> bool {
+    if cfg!(target_os = "windows") {
+        left.as_os_str()
+            .to_string_lossy()
+            .eq_ignore_ascii_case(&right.as_os_str().to_string_lossy())
+    } else {
+        left == right
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_paths_keep_their_spaces() {
+        assert_eq!(
+            mapped_path("7f00-7f10 r-xp 00000000 08:01 1234    /opt/My Apps/pulse/libcef.so"),
+            Some("/opt/My Apps/pulse/libcef.so")
+        );
+        assert_eq!(mapped_path("7f00-7f10 rw-p 00000000 00:00 0 "), None);
+        assert_eq!(mapped_path("7f00-7f10 rw-p 00000000 00:00 0"), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Host-generated pages for failed navigations, served from `pulse://error/`.
+//!
+//! The failure travels in the page URL's query, so the real URL can be recovered for the
+//! omnibox. Any page can navigate to such a URL, so the host only serves and trusts the ones
+//! it loaded itself; see `ShellState::record_error_page`.
+
+use url::Url;
+
+/// Scheme of pages the host serves itself.
+pub const INTERNAL_SCHEME: &str = "pulse";
+const ERROR_PAGE_HOST: &str = "error";
+/// `net::ERR_ABORTED`: the user stopped the load or another navigation replaced it.
+const ERR_ABORTED: i32 = -3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadFailure {
+    pub error_code: i32,
+    pub error_text: String,
+    pub failed_url: String,
+}
+
+pub fn is_internal_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.scheme() == INTERNAL_SCHEME)
+}
+
+/// Whether `failure` replaces the tab's page and is reported to the UI. Aborted loads and
+/// failures of internal pages are not.
+pub fn is_reportable(failure: &LoadFailure) -> bool {
+    failure.error_code != ERR_ABORTED && !is_internal_url(&failure.failed_url)
+}
+
+pub fn error_page_url(failure: &LoadFailure) -> String {
+    let mut url = Url::parse(&format!("{INTERNAL_SCHEME}://{ERROR_PAGE_HOST}/"))
+        .expect("error page base URL is valid");
+    url.query_pairs_mut()
+        .append_pair("code", &failure.error_code.to_string())
+        .append_pair("text", &failure.error_text)
+        .append_pair("url", &failure.failed_url);
+    url.to_string()
+}
+
+/// The failure an error page URL describes; `None` for any other URL.
+pub fn parse_error_page_url(url: &str) -> Option<LoadFailure> {
+    let url = Url::parse(url).ok()?;
+    if url.scheme() != INTERNAL_SCHEME || url.host_str() != Some(ERROR_PAGE_HOST) {
+        return None;
+    }
+
+    let mut failure = LoadFailure {
+        error_code: 0,
+        error_text: String::new(),
+        failed_url: String::new(),
+    };
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "code" => failure.error_code = value.parse().ok()?,
+            "text" => failure.error_text = value.into_owned(),
+            "url" => failure.failed_url = value.into_owned(),
+            _ => {}
+        }
+    }
+    Some(failure)
+}
+
+pub fn render_error_page(failure: &LoadFailure) -> String {
+    let parsed = Url::parse(&failure.failed_url).ok();
+    let site = parsed
+        .as_ref()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| failure.failed_url.clone());
+    // Only offer a retry for URLs a tab could have navigated to in the first place.
+    let retry = parsed
+        .filter(|url| matches!(url.scheme(), "http" | "https" | "file"))
+        .map(|url| {
+            format!(
+                r#"<p><a class="retry" href="{}">Try again</a></p>"#,
+                escape_html(url.as_str())
+            )
+        })
+        .unwrap_or_default();
+    let hint = hint(failure.error_code)
+        .map(|hint| format!("<p class=\"hint\">{hint}</p>"))
+        .unwrap_or_default();
+    let error_text = if failure.error_text.is_empty() {
+        String::from("ERR_FAILED")
+    } else {
+        failure.error_text.clone()
+    };
+
+    format!(
+        r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Can't reach {site}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 15vh auto; max-width: 36rem; padding: 0 1.5rem; color: #1f2328; background: #fff; }}
+@media (prefers-color-scheme: dark) {{ body {{ color: #e6edf3; background: #0d1117; }} }}
+h1 {{ font-size: 1.4rem; }}
+.url {{ word-break: break-all; opacity: 0.8; }}
+.code {{ font-family: ui-monospace, monospace; opacity: 0.7; }}
+.retry {{ display: inline-block; padding: 0.4rem 1rem; border-radius: 0.4rem; background: #2563eb; color: #fff; text-decoration: none; }}
+</style>
+</head>
+<body>
+<h1>Can't reach {site}</h1>
+<p class="url">{url}</p>
+{hint}
+<p class="code">{error_text} ({error_code})</p>
+{retry}
+</body>
+</html>
+"#,
+        site = escape_html(&site),
+        url = escape_html(&failure.failed_url),
+        error_text = escape_html(&error_text),
+        error_code = failure.error_code,
+    )
+}
+
+/// Advice for the error codes a user can act on.
+fn hint(error_code: i32) -> Option<&'static str> {
+    match error_code {
+        // INTERNET_DISCONNECTED, NETWORK_CHANGED, NAME_NOT_RESOLVED, NAME_RESOLUTION_FAILED
+        -106 | -21 | -105 | -137 => {
+            Some("You may be offline. Check your network connection, then try again.")
+        }
+        // CONNECTION_REFUSED, CONNECTION_TIMED_OUT, TIMED_OUT, ADDRESS_UNREACHABLE
+        -102 | -118 | -7 | -109 => Some(
+            "The site did not answer. It may be down, or your network or a firewall may be blocking it.",
+        ),
+        // Certificate errors.
+        -299..=-200 => Some(
+            "The site's certificate could not be trusted, so Pulse did not load it. Check the address and your system clock.",
+        ),
+        _ => None,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
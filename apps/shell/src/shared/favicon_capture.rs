@@ -0,0 +1,143 @@
+//! Downloads tab favicons through the tab's own browser and caches them in the profile.
+
+use cef::*;
+use pulse_core::protocol::HostEvent;
+use pulse_state::favicons;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+use super::error_page::INTERNAL_SCHEME;
+use super::simple_handler::emit_host_event;
+use super::state::ShellState;
+
+pub const FAVICON_HOST: &str = "favicon";
+/// Largest edge, in pixels, of a downloaded icon; bigger ones are scaled down.
+const MAX_ICON_SIZE: u32 = 64;
+
+/// `pulse://favicon/<sha256>`: one cached icon, which never changes.
+pub fn cached_icon_url(hash: &str) -> String {
+    format!("{INTERNAL_SCHEME}://{FAVICON_HOST}/{hash}")
+}
+
+/// The PNG for `pulse://favicon/<sha256>`, or for `pulse://favicon/?page=<url>`: the icon
+/// last cached for that page or its site.
+pub fn cached_icon(url: &str) -> Option<Vec<u8>> {
+    let url = Url::parse(url).ok()?;
+    if url.scheme() != INTERNAL_SCHEME || url.host_str() != Some(FAVICON_HOST) {
+        return None;
+    }
+    let hash = match url.path().trim_start_matches('/') {
+        "" => {
+            let (_, page_url) = url.query_pairs().find(|(key, _)| key == "page")?;
+            favicons::lookup(&page_url)
+                .inspect_err(|err| eprintln!("Pulse favicon warning: {err:#}"))
+                .ok()??
+        }
+        hash => hash.to_string(),
+    };
+    favicons::read(&hash)
+        .inspect_err(|err| eprintln!("Pulse favicon warning: {err:#}"))
+        .ok()?
+}
+
+/// Records `icon_url` as the favicon of `tab_id` and, when there is one, downloads it with
+/// the tab's cookies and network stack. The UI hears about it once the icon is cached.
+pub fn capture(
+    shell_state: &Arc<Mutex<ShellState>>,
+    browser: &Browser,
+    tab_id: String,
+    icon_url: Option<String>,
+) {
+    {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        state.set_tab_favicon_url(&tab_id, icon_url.clone());
+    }
+    let Some(icon_url) = icon_url else {
+        emit_host_event(
+            shell_state,
+            HostEvent::FaviconChanged {
+                tab_id,
+                icon_url: None,
+            },
+        );
+        return;
+    };
+    let Some(page_url) = browser
+        .main_frame()
+        .map(|frame| CefString::from(&frame.url()).to_string())
+    else {
+        return;
+    };
+    let Some(host) = browser.host() else {
+        return;
+    };
+
+    let mut callback =
+        FaviconDownloadCallback::new(shell_state.clone(), tab_id, page_url, icon_url.clone());
+    host.download_image(
+        Some(&CefString::from(icon_url.as_str())),
+        1,
+        MAX_ICON_SIZE,
+        0,
+        Some(&mut callback),
+    );
+}
+
+wrap_download_image_callback! {
+    struct FaviconDownloadCallback {
+        shell_state: Arc<Mutex<ShellState>>,
+        tab_id: String,
+        page_url: String,
+        icon_url: String,
+    }
+
+    impl DownloadImageCallback {
+        fn on_download_image_finished(
+            &self,
+            _image_url: Option<&CefString>,
+            http_status_code: i32,
+            image: Option<&mut Image>,
+        ) {
+            let Some(png) = image.and_then(|image| image_png_bytes(image)) else {
+                eprintln!(
+                    "Pulse favicon warning: could not download '{}' (HTTP {http_status_code})",
+                    self.icon_url
+                );
+                return;
+            };
+            let hash = match favicons::store(&self.page_url, &png) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    eprintln!("Pulse favicon warning: {err:#}");
+                    return;
+                }
+            };
+
+            // The page may have switched icons, or the tab closed, while this downloaded.
+            let current = {
+                let state = self.shell_state.lock().expect("Failed to lock shell state");
+                state.tab_favicon_url(&self.tab_id)
+            };
+            if current.as_deref() != Some(self.icon_url.as_str()) {
+                return;
+            }
+            emit_host_event(
+                &self.shell_state,
+                HostEvent::FaviconChanged {
+                    tab_id: self.tab_id.clone(),
+                    icon_url: Some(cached_icon_url(&hash)),
+                },
+            );
+        }
+    }
+}
+
+fn image_png_bytes(image: &Image) -> Option<Vec<u8>> {
+    let mut width = 0;
+    let mut height = 0;
+    let png = image.as_png(1.0, 1, Some(&mut width), Some(&mut height))?;
+    let mut bytes = vec![0_u8; png.size()];
+    let copied = png.data(bytes.as_mut_ptr(), bytes.len(), 0);
+    bytes.truncate(copied);
+    (!bytes.is_empty()).then_some(bytes)
+}
@@ -0,0 +1,102 @@
+//! Reads content tabs' back/forward lists from CEF so the UI can persist them.
+
+use cef::*;
+use pulse_core::protocol::HostEvent;
+use pulse_core::{HistoryEntry, TabHistory};
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+
+use super::simple_handler::emit_host_event;
+use super::state::ShellState;
+
+/// Visits the tab's navigation entries and, if its list changed, sends the UI the whole list
+/// and the tab's back/forward state.
+pub fn capture(shell_state: &Arc<Mutex<ShellState>>, browser: &Browser, tab_id: String) {
+    let Some(host) = browser.host() else {
+        return;
+    };
+    let mut visitor = HistoryVisitor::new(
+        shell_state.clone(),
+        browser.identifier(),
+        tab_id,
+        Arc::new(Mutex::new(TabHistory::default())),
+    );
+    host.navigation_entries(Some(&mut visitor), 0);
+}
+
+wrap_navigation_entry_visitor! {
+    struct HistoryVisitor {
+        shell_state: Arc<Mutex<ShellState>>,
+        browser_id: i32,
+        tab_id: String,
+        collected: Arc<Mutex<TabHistory>>,
+    }
+
+    impl NavigationEntryVisitor {
+        fn visit(
+            &self,
+            entry: Option<&mut NavigationEntry>,
+            current: c_int,
+            index: c_int,
+            total: c_int,
+        ) -> c_int {
+            let mut collected = self.collected.lock().expect("Failed to lock history");
+            if let Some(entry) = entry {
+                let url = CefString::from(&entry.url()).to_string();
+                if !url.is_empty() {
+                    if current != 0 {
+                        collected.index = collected.entries.len();
+                    }
+                    let title = CefString::from(&entry.title()).to_string();
+                    collected.entries.push(HistoryEntry {
+                        url,
+                        title: (!title.is_empty()).then_some(title),
+                    });
+                }
+            }
+            if index + 1 < total {
+                return 1;
+            }
+
+            let mut live = std::mem::take(&mut *collected);
+            drop(collected);
+            {
+                // Error pages are recorded as the URL that failed, like the omnibox shows.
+                let state = self.shell_state.lock().expect("Failed to lock shell state");
+                for entry in &mut live.entries {
+                    entry.url = state.displayed_url(self.browser_id, std::mem::take(&mut entry.url));
+                }
+            }
+            record(&self.shell_state, &self.tab_id, live);
+            0
+        }
+    }
+}
+
+/// Takes the shell state lock only to store the list, and emits after releasing it.
+fn record(shell_state: &Arc<Mutex<ShellState>>, tab_id: &str, live: TabHistory) {
+    let changed = {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        state
+            .set_live_history(tab_id, live)
+            .map(|history| (history, state.tab_nav_state(tab_id).unwrap_or_default()))
+    };
+    let Some((history, nav)) = changed else {
+        return;
+    };
+    emit_host_event(
+        shell_state,
+        HostEvent::TabHistoryChanged {
+            tab_id: tab_id.to_string(),
+            history,
+        },
+    );
+    // Whether back and forward reach restored entries depends on the list.
+    emit_host_event(
+        shell_state,
+        HostEvent::LoadingStateChanged {
+            tab_id: tab_id.to_string(),
+            nav,
+        },
+    );
+}
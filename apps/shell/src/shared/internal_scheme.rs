@@ -0,0 +1,139 @@
+//! The `pulse://` scheme: pages and resources the host serves itself.
+
+use cef::*;
+use std::cell::Cell;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+
+use super::error_page::{self, INTERNAL_SCHEME};
+use super::favicon_capture;
+use super::state::ShellState;
+
+/// Registers `pulse` as a standard, secure scheme. Runs in every process, before CEF starts.
+pub fn register_custom_scheme(registrar: &mut SchemeRegistrar) {
+    let options = sys::cef_scheme_options_t::CEF_SCHEME_OPTION_STANDARD as c_int
+        | sys::cef_scheme_options_t::CEF_SCHEME_OPTION_SECURE as c_int;
+    registrar.add_custom_scheme(Some(&CefString::from(INTERNAL_SCHEME)), options);
+}
+
+/// Serves `pulse://` requests in the browser process. Call once the CEF context exists.
+pub fn register_handlers(shell_state: Arc<Mutex<ShellState>>) {
+    let mut factory = InternalSchemeHandlerFactory::new(shell_state);
+    let registered = register_scheme_handler_factory(
+        Some(&CefString::from(INTERNAL_SCHEME)),
+        None,
+        Some(&mut factory),
+    );
+    if registered == 0 {
+        eprintln!("Pulse warning: could not register the {INTERNAL_SCHEME}:// scheme handler");
+    }
+}
+
+/// The response for one `pulse://` URL. Favicons are only served to the UI browser, so
+/// pages cannot probe which sites the profile has visited, and error pages only when the host
+/// loaded them, so pages cannot forge one for another site.
+fn resolve(url: &str, from_ui: bool, host_error_page: bool) -> (i32, &'static str, Vec<u8>) {
+    if host_error_page && let Some(failure) = error_page::parse_error_page_url(url) {
+        return (
+            200,
+            "text/html",
+            error_page::render_error_page(&failure).into_bytes(),
+        );
+    }
+    if from_ui && let Some(png) = favicon_capture::cached_icon(url) {
+        return (200, "image/png", png);
+    }
+    (404, "text/plain", b"Not found".to_vec())
+}
+
+wrap_scheme_handler_factory! {
+    struct InternalSchemeHandlerFactory {
+        shell_state: Arc<Mutex<ShellState>>,
+    }
+
+    impl SchemeHandlerFactory {
+        fn create(
+            &self,
+            browser: Option<&mut Browser>,
+            frame: Option<&mut Frame>,
+            _scheme_name: Option<&CefString>,
+            request: Option<&mut Request>,
+        ) -> Option<ResourceHandler> {
+            let url = CefString::from(&request?.url()).to_string();
+            let (from_ui, host_error_page) = browser.map_or((false, false), |browser| {
+                let state = self.shell_state.lock().expect("Failed to lock shell state");
+                (
+                    state.ui_browser_id == Some(browser.identifier()),
+                    frame.is_some_and(|frame| frame.is_main() != 0)
+                        && state.is_host_error_page(browser.identifier(), &url),
+                )
+            });
+            let (status, mime_type, body) = resolve(&url, from_ui, host_error_page);
+            Some(StaticResourceHandler::new(status, mime_type, body, Cell::new(0)))
+        }
+    }
+}
+
+wrap_resource_handler! {
+    /// Answers a request with a body that is fully known up front.
+    struct StaticResourceHandler {
+        status: i32,
+        mime_type: &'static str,
+        body: Vec<u8>,
+        offset: Cell<usize>,
+    }
+
+    impl ResourceHandler {
+        fn open(
+            &self,
+            _request: Option<&mut Request>,
+            handle_request: Option<&mut c_int>,
+            _callback: Option<&mut Callback>,
+        ) -> c_int {
+            if let Some(handle_request) = handle_request {
+                *handle_request = 1;
+            }
+            1
+        }
+
+        fn response_headers(
+            &self,
+            response: Option<&mut Response>,
+            response_length: Option<&mut i64>,
+            _redirect_url: Option<&mut CefString>,
+        ) {
+            if let Some(response) = response {
+                response.set_status(self.status);
+                response.set_mime_type(Some(&CefString::from(self.mime_type)));
+                response.set_charset(Some(&CefString::from("utf-8")));
+            }
+            if let Some(response_length) = response_length {
+                *response_length = self.body.len() as i64;
+            }
+        }
+
+        fn read(
+            &self,
+            data_out: *mut u8,
+            bytes_to_read: c_int,
+            bytes_read: Option<&mut c_int>,
+            _callback: Option<&mut ResourceReadCallback>,
+        ) -> c_int {
+            let offset = self.offset.get();
+            let remaining = &self.body[offset.min(self.body.len())..];
+            let count = remaining.len().min(usize::try_from(bytes_to_read).unwrap_or(0));
+            if count > 0 && !data_out.is_null() {
+                // SAFETY: CEF hands us a buffer of at least `bytes_to_read` bytes.
+                unsafe { std::ptr::copy_nonoverlapping(remaining.as_ptr(), data_out, count) };
+            }
+            self.offset.set(offset + count);
+            if let Some(bytes_read) = bytes_read {
+                *bytes_read = count as c_int;
+            }
+            // Returning 0 with no bytes read ends the response.
+            c_int::from(count > 0)
+        }
+
+        fn cancel(&self) {}
+    }
+}
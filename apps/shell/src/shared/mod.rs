@@ -1,9 +1,23 @@
 use cef::*;
-use std::path::{Path, PathBuf};
+use pulse_core::tab_lifecycle::DiscardPolicy;
+use pulse_state::paths::{self, ProfilePaths, PulseDirs};
+use pulse_state::profiles;
+use single_instance::InstanceRole;
+use state::ShellState;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-pub mod persistence;
+pub mod authorization;
+pub mod cef_runtime;
+pub mod error_page;
+pub mod favicon_capture;
+pub mod history_capture;
+pub mod internal_scheme;
+pub mod persistence_worker;
+pub mod popup_policy;
 pub mod simple_app;
 pub mod simple_handler;
+pub mod single_instance;
 pub mod state;
 
 #[cfg(target_os = "macos")]
@@ -31,11 +45,17 @@ pub fn load_cef() -> Library {
     library
 }
 
+/// Creates or rotates the state encryption key, re-seals the saved workspace, and exits.
+const ROTATE_STATE_KEY_SWITCH: &str = "rotate-state-key";
+/// Named profile to run instead of the selected one (`pulse-state profiles`).
+const PROFILE_FLAG: &str = "--profile";
+
 pub fn run_main(main_args: &MainArgs, cmd_line: &CommandLine, sandbox_info: *mut u8) {
     let switch = CefString::from("type");
     let is_browser_process = cmd_line.has_switch(Some(&switch)) != 1;
 
-    let mut app = simple_app::SimpleApp::new();
+    let shell_state = Arc::new(Mutex::new(ShellState::default()));
+    let mut app = simple_app::SimpleApp::new(shell_state.clone());
     let ret = execute_process(Some(main_args), Some(&mut app), sandbox_info);
     if is_browser_process {
         assert_eq!(ret, -1, "Cannot execute browser process");
@@ -44,19 +64,132 @@ pub fn run_main(main_args: &MainArgs, cmd_line: &CommandLine, sandbox_info: *mut
         return;
     }
 
+    let dirs = PulseDirs::resolve();
+    // The legacy root first: it lands in the current layout directly.
+    for migrate in [paths::migrate_legacy_root, paths::migrate_default_profile] {
+        match migrate(&dirs) {
+            Ok(Some(migration)) => {
+                eprintln!(
+                    "Pulse moved {} entries from '{}' to its profile directories.",
+                    migration.moved.len(),
+                    migration.from.display()
+                );
+                for skipped in &migration.skipped {
+                    eprintln!(
+                        "Pulse migration warning: left '{}' in place; its destination already exists",
+                        skipped.display()
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                // Starting anyway would create fresh state and strand the old data for good.
+                eprintln!("Pulse migration error: {err:#}");
+                return;
+            }
+        }
+    }
+
+    let requested_profile = crate::resolve_flag_from_args(PROFILE_FLAG);
+    let (profile, profile_paths) = match profiles::open(&dirs, requested_profile.as_deref()) {
+        Ok(opened) => opened,
+        Err(err) => {
+            eprintln!("Pulse profile error: {err:#}");
+            return;
+        }
+    };
+    let rotate_state_key =
+        cmd_line.has_switch(Some(&CefString::from(ROTATE_STATE_KEY_SWITCH))) == 1;
+    let launch_urls = single_instance::launch_urls(std::env::args());
+    // Held until `run_main` returns so a second launch cannot share the profile root.
+    let instance = match single_instance::acquire(&profile_paths.root) {
+        Ok(InstanceRole::Primary(instance)) => instance,
+        Ok(InstanceRole::Secondary(_)) if rotate_state_key => {
+            eprintln!(
+                "Pulse is running profile '{}'; close it before rotating the state key.",
+                profile.name
+            );
+            return;
+        }
+        Ok(InstanceRole::Secondary(running)) => {
+            match running.forward(&launch_urls) {
+                Ok(()) => eprintln!(
+                    "Pulse is already running profile '{}'; forwarded {} launch url(s) to it.",
+                    profile.name,
+                    launch_urls.len()
+                ),
+                Err(err) => eprintln!("Pulse single-instance error: {err:#}"),
+            }
+            return;
+        }
+        Err(err) => {
+            eprintln!("Pulse single-instance error: {err:#}");
+            return;
+        }
+    };
+    if rotate_state_key {
+        match pulse_state::persistence::rotate_state_key() {
+            Ok(key_id) => eprintln!("Pulse state key rotated; active key is '{key_id}'."),
+            Err(err) => eprintln!("Pulse state key rotation failed: {err:#}"),
+        }
+        return;
+    }
+    authorization::seed_ui_grants();
+    // Host settings live in the workspace store; changes apply from the next launch.
+    let discard_policy = match pulse_state::persistence::load_workspace_store() {
+        Ok(Some(store)) => {
+            let (policy, ignored) = DiscardPolicy::from_settings(store.settings());
+            for key in ignored {
+                let value = store.settings().get(&key).cloned().unwrap_or_default();
+                eprintln!("Pulse tab lifecycle warning: ignoring setting {key}='{value}'");
+            }
+            policy
+        }
+        Ok(None) => DiscardPolicy::default(),
+        Err(err) => {
+            eprintln!("Pulse tab lifecycle warning: using the default discard policy: {err:#}");
+            DiscardPolicy::default()
+        }
+    };
+    {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        state.queue_launch_urls(launch_urls);
+        state.discard_policy = discard_policy;
+    }
+
+    // Check the runtime layout up front; CEF's own failure for a missing file is a bare 0.
+    #[cfg(not(target_os = "macos"))]
+    let runtime = match cef_runtime::discover() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Pulse startup error: {err:#}");
+            print_cef_diagnostics(&dirs, &profile_paths);
+            return;
+        }
+    };
+
     let mut settings = Settings {
         no_sandbox: 1,
         ..Default::default()
     };
+    #[cfg(not(target_os = "macos"))]
+    {
+        let resources_dir = runtime.runtime_dir.to_string_lossy().to_string();
+        let locales_dir = runtime.locales_dir.to_string_lossy().to_string();
+        settings.resources_dir_path = CefString::from(resources_dir.as_str());
+        settings.locales_dir_path = CefString::from(locales_dir.as_str());
+    }
 
-    let app_data_root = pulse_app_data_root();
-    let cache_path = app_data_root.join("cache").join("default");
-    let log_file = app_data_root.join("debug.log");
+    // Each profile gets its own CEF root, so cookies and site data never cross profiles.
+    let cache_path = profile_paths.cache_root.join("cache").join("default");
     let _ = std::fs::create_dir_all(&cache_path);
+    if let Some(log_dir) = profile_paths.log_file.parent() {
+        let _ = std::fs::create_dir_all(log_dir);
+    }
 
-    let root_cache_path = app_data_root.to_string_lossy().to_string();
+    let root_cache_path = profile_paths.cache_root.to_string_lossy().to_string();
     let cache_path = cache_path.to_string_lossy().to_string();
-    let log_file = log_file.to_string_lossy().to_string();
+    let log_file = profile_paths.log_file.to_string_lossy().to_string();
     settings.root_cache_path = CefString::from(root_cache_path.as_str());
     settings.cache_path = CefString::from(cache_path.as_str());
     settings.log_file = CefString::from(log_file.as_str());
@@ -78,21 +211,29 @@ pub fn run_main(main_args: &MainArgs, cmd_line: &CommandLine, sandbox_info: *mut
         eprintln!(
             "CEF initialize returned {initialized}. Verify matching CEF binaries and runtime layout."
         );
-        print_cef_diagnostics();
+        print_cef_diagnostics(&dirs, &profile_paths);
         return;
     }
 
+    let listener_state = shell_state.clone();
+    if let Err(err) = instance.listen(move |urls| {
+        simple_app::open_forwarded_launch(&listener_state, urls);
+    }) {
+        eprintln!("Pulse single-instance warning: {err:#}");
+    }
+
     run_message_loop();
+    persistence_worker::flush();
     shutdown();
+    drop(instance);
 }
 
-fn print_cef_diagnostics() {
+fn print_cef_diagnostics(dirs: &PulseDirs, profile_paths: &ProfilePaths) {
     let exe_dir = std::env::current_exe()
         .ok()
         .and_then(|path| path.parent().map(Path::to_path_buf));
     let cef_path = std::env::var("CEF_PATH").ok();
-    let root_cache = pulse_app_data_root();
-    let log_path = root_cache.join("debug.log");
+    let log_path = &profile_paths.log_file;
 
     eprintln!("Diagnostics:");
     eprintln!("  CEF_PATH={}", cef_path.as_deref().unwrap_or("<unset>"));
@@ -103,42 +244,27 @@ fn print_cef_diagnostics() {
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| "<unknown>".into())
     );
-    eprintln!("  root_cache_path={}", root_cache.display());
+    eprintln!("  data_dir={}", dirs.data.display());
+    eprintln!("  cache_dir={}", dirs.cache.display());
+    eprintln!("  state_dir={}", dirs.state.display());
+    eprintln!("  profile_root={}", profile_paths.root.display());
+    eprintln!("  root_cache_path={}", profile_paths.cache_root.display());
     eprintln!("  log_file={}", log_path.display());
 
-    if let Some(dir) = exe_dir {
-        eprintln!(
-            "  exe_dir libcef.dll exists={}",
-            dir.join("libcef.dll").exists()
-        );
-        eprintln!(
-            "  exe_dir resources.pak exists={}",
-            dir.join("resources.pak").exists()
-        );
-        eprintln!("  exe_dir locales exists={}", dir.join("locales").exists());
+    for dir in cef_runtime::candidate_dirs() {
+        eprintln!("  runtime candidate {}:", dir.display());
+        for line in cef_runtime::describe_dir(&dir) {
+            eprintln!("    {line}");
+        }
     }
-
-    if let Some(path) = cef_path {
-        let base = PathBuf::from(path);
+    if let Some(path) = &cef_path {
         eprintln!(
             "  CEF_PATH archive.json exists={}",
-            base.join("archive.json").exists()
-        );
-        eprintln!(
-            "  CEF_PATH libcef.dll exists={}",
-            base.join("libcef.dll").exists()
-        );
-        eprintln!(
-            "  CEF_PATH resources.pak exists={}",
-            base.join("resources.pak").exists()
-        );
-        eprintln!(
-            "  CEF_PATH locales exists={}",
-            base.join("locales").exists()
+            Path::new(path).join("archive.json").exists()
         );
     }
 
-    if let Ok(text) = std::fs::read_to_string(&log_path) {
+    if let Ok(text) = std::fs::read_to_string(log_path) {
         let tail = text.lines().rev().take(20).collect::<Vec<_>>();
         if !tail.is_empty() {
             eprintln!("  debug.log tail:");
@@ -148,12 +274,3 @@ fn print_cef_diagnostics() {
         }
     }
 }
-
-pub fn pulse_app_data_root() -> PathBuf {
-    let cache_root_name =
-        std::env::var("PULSE_CACHE_ROOT").unwrap_or_else(|_| String::from("Pulse"));
-    std::env::var("LOCALAPPDATA")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| std::env::temp_dir())
-        .join(cache_root_name)
-}
@@ -0,0 +1,346 @@
+use std::sync::OnceLock;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pulse_core::protocol::{HostCommand, HostError, HostErrorCode};
+use pulse_state::{grants, persistence, profiles};
+use serde_json::{Map, Value, json};
+
+/// Quiet period after the last `saveState` before the coalesced write hits disk.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(750);
+/// Upper bound on how long a continuously updated session can stay unsaved.
+const SAVE_MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Persistence operations the UI bridge forwards to the browser process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistenceOp {
+    LoadState,
+    SaveState,
+    Workspace,
+    RecoveryReport,
+    ListSnapshots,
+    RestoreSnapshot,
+    ExportBundle,
+    ImportBundle,
+    ListProfiles,
+    ListGrants,
+    RevokeGrant,
+    CapabilityAudit,
+}
+
+impl PersistenceOp {
+    pub const ALL: [Self; 12] = [
+        Self::LoadState,
+        Self::SaveState,
+        Self::Workspace,
+        Self::RecoveryReport,
+        Self::ListSnapshots,
+        Self::RestoreSnapshot,
+        Self::ExportBundle,
+        Self::ImportBundle,
+        Self::ListProfiles,
+        Self::ListGrants,
+        Self::RevokeGrant,
+        Self::CapabilityAudit,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LoadState => "load-state",
+            Self::SaveState => "save-state",
+            Self::Workspace => "workspace",
+            Self::RecoveryReport => "recovery-report",
+            Self::ListSnapshots => "list-snapshots",
+            Self::RestoreSnapshot => "restore-snapshot",
+            Self::ExportBundle => "export-bundle",
+            Self::ImportBundle => "import-bundle",
+            Self::ListProfiles => "list-profiles",
+            Self::ListGrants => "list-grants",
+            Self::RevokeGrant => "revoke-grant",
+            Self::CapabilityAudit => "capability-audit",
+        }
+    }
+
+    /// Property name on `window.__pulseHost`.
+    pub fn bridge_name(self) -> &'static str {
+        match self {
+            Self::LoadState => "loadState",
+            Self::SaveState => "saveState",
+            Self::Workspace => "workspace",
+            Self::RecoveryReport => "recoveryReport",
+            Self::ListSnapshots => "listSnapshots",
+            Self::RestoreSnapshot => "restoreSnapshot",
+            Self::ExportBundle => "exportBundle",
+            Self::ImportBundle => "importBundle",
+            Self::ListProfiles => "listProfiles",
+            Self::ListGrants => "listGrants",
+            Self::RevokeGrant => "revokeGrant",
+            Self::CapabilityAudit => "capabilityAudit",
+        }
+    }
+
+    pub fn takes_payload(self) -> bool {
+        matches!(
+            self,
+            Self::SaveState
+                | Self::Workspace
+                | Self::RestoreSnapshot
+                | Self::ExportBundle
+                | Self::ImportBundle
+                | Self::RevokeGrant
+        )
+    }
+}
+
+impl PersistenceOp {
+    /// Builds the typed command for a bridge call. `payload` is the call's string argument:
+    /// JSON for object-shaped requests, the snapshot id for `restoreSnapshot`.
+    pub fn command(self, payload: Option<String>) -> Result<HostCommand, String> {
+        let payload = payload.unwrap_or_default();
+        let mut fields = Map::new();
+        match self {
+            Self::SaveState => {
+                let session = serde_json::from_str(&payload)
+                    .map_err(|error| format!("UI state payload is not valid JSON: {error}"))?;
+                fields.insert(String::from("session"), session);
+            }
+            Self::Workspace => {
+                let request = serde_json::from_str(&payload).map_err(|error| {
+                    format!("Workspace command payload is not valid JSON: {error}")
+                })?;
+                fields.insert(String::from("request"), request);
+            }
+            Self::RestoreSnapshot => {
+                fields.insert(String::from("id"), Value::String(payload));
+            }
+            Self::ExportBundle | Self::ImportBundle | Self::RevokeGrant => {
+                match serde_json::from_str(&payload) {
+                    Ok(Value::Object(object)) => fields = object,
+                    _ => {
+                        return Err(format!(
+                            "pulseHost.{} argument must be a JSON object",
+                            self.bridge_name()
+                        ));
+                    }
+                }
+            }
+            Self::LoadState
+            | Self::RecoveryReport
+            | Self::ListSnapshots
+            | Self::ListProfiles
+            | Self::ListGrants
+            | Self::CapabilityAudit => {}
+        }
+        fields.insert(String::from("type"), Value::from(self.as_str()));
+        serde_json::from_value(Value::Object(fields))
+            .map_err(|error| format!("Invalid pulseHost.{} request: {error}", self.bridge_name()))
+    }
+
+    /// The worker op for a persistence command and the payload its `pulse_state` function
+    /// takes; `None` for commands the UI thread runs itself.
+    pub fn from_command(command: &HostCommand) -> Option<(Self, String)> {
+        Some(match command {
+            HostCommand::LoadState => (Self::LoadState, String::new()),
+            HostCommand::SaveState { session } => (Self::SaveState, session.to_string()),
+            HostCommand::Workspace { request } => (Self::Workspace, json!(request).to_string()),
+            HostCommand::RecoveryReport => (Self::RecoveryReport, String::new()),
+            HostCommand::ListSnapshots => (Self::ListSnapshots, String::new()),
+            HostCommand::RestoreSnapshot { id } => (Self::RestoreSnapshot, id.clone()),
+            HostCommand::ExportBundle { path } => {
+                (Self::ExportBundle, json!({ "path": path }).to_string())
+            }
+            HostCommand::ImportBundle { path, mode } => (
+                Self::ImportBundle,
+                json!({ "path": path, "mode": mode }).to_string(),
+            ),
+            HostCommand::ListProfiles => (Self::ListProfiles, String::new()),
+            HostCommand::ListGrants => (Self::ListGrants, String::new()),
+            HostCommand::RevokeGrant { holder, capability } => (
+                Self::RevokeGrant,
+                json!({ "holder": holder, "capability": capability }).to_string(),
+            ),
+            HostCommand::CapabilityAudit => (Self::CapabilityAudit, String::new()),
+            _ => return None,
+        })
+    }
+}
+
+/// The value a bridge Promise resolves with, or the error it rejects with.
+pub type PersistenceResult = Result<Value, HostError>;
+pub type PersistenceReply = Box<dyn FnOnce(PersistenceResult) + Send>;
+
+enum Job {
+    Request {
+        op: PersistenceOp,
+        payload: String,
+        reply: PersistenceReply,
+    },
+    /// Writes the capability checks `authorization::authorize` decided in memory.
+    Audit,
+    Flush(Sender<()>),
+}
+
+struct PendingSave {
+    payload: String,
+    replies: Vec<PersistenceReply>,
+    first_queued_at: Instant,
+    last_queued_at: Instant,
+}
+
+impl PendingSave {
+    fn due_at(&self) -> Instant {
+        (self.last_queued_at + SAVE_DEBOUNCE).min(self.first_queued_at + SAVE_MAX_DELAY)
+    }
+}
+
+/// Queues `op` on the persistence thread. `reply` runs on that thread once the op is done;
+/// coalesced saves all receive the result of the single write that covered them.
+pub fn submit(op: PersistenceOp, payload: String, reply: PersistenceReply) {
+    let job = Job::Request { op, payload, reply };
+    if let Err(mpsc::SendError(Job::Request { reply, .. })) = worker().send(job) {
+        reply(Err(HostError::new(
+            HostErrorCode::Failed,
+            "Persistence worker is not running",
+        )));
+    }
+}
+
+/// Queues writing the capability checks decided since the last call.
+pub fn flush_audit() {
+    let _ = worker().send(Job::Audit);
+}
+
+/// Blocks until any debounced save and queued audit entry is on disk. Call before the process exits.
+pub fn flush() {
+    let Some(sender) = WORKER.get() else {
+        return;
+    };
+
+    let (ack_sender, ack_receiver) = mpsc::channel();
+    if sender.send(Job::Flush(ack_sender)).is_ok() {
+        let _ = ack_receiver.recv();
+    }
+}
+
+static WORKER: OnceLock<Sender<Job>> = OnceLock::new();
+
+fn worker() -> &'static Sender<Job> {
+    WORKER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("pulse-persistence"))
+            .spawn(move || run_worker(receiver))
+            .expect("Failed to spawn persistence worker thread");
+        sender
+    })
+}
+
+fn run_worker(receiver: Receiver<Job>) {
+    let mut pending_save: Option<PendingSave> = None;
+
+    loop {
+        let job = match &pending_save {
+            Some(pending) => {
+                let wait = pending.due_at().saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(wait) {
+                    Ok(job) => Some(job),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        flush_pending_save(&mut pending_save);
+                        return;
+                    }
+                }
+            }
+            None => match receiver.recv() {
+                Ok(job) => Some(job),
+                Err(_) => return,
+            },
+        };
+
+        match job {
+            None => flush_pending_save(&mut pending_save),
+            Some(Job::Request {
+                op: PersistenceOp::SaveState,
+                payload,
+                reply,
+            }) => {
+                let now = Instant::now();
+                match &mut pending_save {
+                    Some(pending) => {
+                        pending.payload = payload;
+                        pending.replies.push(reply);
+                        pending.last_queued_at = now;
+                    }
+                    None => {
+                        pending_save = Some(PendingSave {
+                            payload,
+                            replies: vec![reply],
+                            first_queued_at: now,
+                            last_queued_at: now,
+                        });
+                    }
+                }
+            }
+            Some(Job::Request { op, payload, reply }) => {
+                // Everything else observes the latest session, so land the pending save first.
+                flush_pending_save(&mut pending_save);
+                reply(run_op(op, &payload));
+            }
+            Some(Job::Audit) => write_audit(),
+            Some(Job::Flush(ack)) => {
+                flush_pending_save(&mut pending_save);
+                write_audit();
+                let _ = ack.send(());
+            }
+        }
+    }
+}
+
+fn flush_pending_save(pending_save: &mut Option<PendingSave>) {
+    let Some(pending) = pending_save.take() else {
+        return;
+    };
+
+    let result = run_op(PersistenceOp::SaveState, &pending.payload);
+    for reply in pending.replies {
+        reply(result.clone());
+    }
+}
+
+fn write_audit() {
+    if let Err(error) = grants::flush_queued() {
+        eprintln!("Pulse capability warning: failed to write the audit log: {error:#}");
+    }
+}
+
+fn run_op(op: PersistenceOp, payload: &str) -> PersistenceResult {
+    let result = match op {
+        PersistenceOp::LoadState => persistence::load_ui_state_json(),
+        PersistenceOp::SaveState => persistence::save_ui_state_json(payload).map(|_| None),
+        PersistenceOp::Workspace => persistence::apply_workspace_command_json(payload).map(Some),
+        PersistenceOp::RecoveryReport => persistence::last_recovery_report_json(),
+        PersistenceOp::ListSnapshots => persistence::list_snapshots_json().map(Some),
+        PersistenceOp::RestoreSnapshot => persistence::restore_snapshot(payload).map(Some),
+        PersistenceOp::ExportBundle => persistence::export_bundle_json(payload).map(Some),
+        PersistenceOp::ImportBundle => persistence::import_bundle_json(payload).map(Some),
+        PersistenceOp::ListProfiles => profiles::list_profiles_json().map(Some),
+        PersistenceOp::ListGrants => grants::list_grants_json().map(Some),
+        PersistenceOp::RevokeGrant => grants::revoke_grant_json(payload).map(Some),
+        PersistenceOp::CapabilityAudit => grants::capability_audit_json().map(Some),
+    };
+
+    let value = result.and_then(|value| match value {
+        Some(value) => serde_json::from_str(&value)
+            .with_context(|| format!("{} produced invalid JSON", op.as_str())),
+        None => Ok(Value::Null),
+    });
+    value.map_err(|error| {
+        eprintln!(
+            "Pulse persistence warning: {} failed: {error:#}",
+            op.as_str()
+        );
+        HostError::new(HostErrorCode::Failed, format!("{error:#}"))
+    })
+}
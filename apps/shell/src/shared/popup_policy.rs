@@ -0,0 +1,97 @@
+//! Where a page's `window.open` or `target=_blank` navigation opens.
+//!
+//! Links and plain `window.open(url)` become workspace tabs under their opener. Popups that
+//! ask for window features, or that start blank so their opener can script them, stay real
+//! popup windows: sign-in flows need `window.opener`, which a separate tab would not have.
+
+use url::Url;
+
+/// How the page asked for the new browser, from CEF's window-open disposition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopupRequest {
+    /// `target=_blank`, a middle click or `window.open` without window features.
+    Tab { background: bool },
+    /// `window.open` with window features, such as a sized sign-in window.
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopupRoute {
+    /// Cancel the popup and ask the UI for a child tab of the opener.
+    NewTab { background: bool },
+    /// Let CEF open a popup window.
+    PopupWindow,
+}
+
+/// `from_content_tab` is false for the UI browser and for popup windows, which have no tab
+/// to parent a new one.
+pub fn route(request: PopupRequest, target_url: &str, from_content_tab: bool) -> PopupRoute {
+    let PopupRequest::Tab { background } = request else {
+        return PopupRoute::PopupWindow;
+    };
+    if !from_content_tab || !opens_as_tab(target_url) {
+        return PopupRoute::PopupWindow;
+    }
+    PopupRoute::NewTab { background }
+}
+
+/// `about:blank` and script URLs only make sense in a window the opener keeps scripting.
+fn opens_as_tab(target_url: &str) -> bool {
+    Url::parse(target_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_web_and_file_links_from_tabs_become_tabs() {
+        let link = PopupRequest::Tab { background: false };
+        let background = PopupRequest::Tab { background: true };
+        let cases = [
+            (
+                link,
+                "https://example.com/",
+                true,
+                PopupRoute::NewTab { background: false },
+            ),
+            (
+                background,
+                "http://example.com/a",
+                true,
+                PopupRoute::NewTab { background: true },
+            ),
+            (
+                link,
+                "file:///tmp/page.html",
+                true,
+                PopupRoute::NewTab { background: false },
+            ),
+            (link, "about:blank", true, PopupRoute::PopupWindow),
+            (link, "javascript:void(0)", true, PopupRoute::PopupWindow),
+            (link, "data:text/html,hi", true, PopupRoute::PopupWindow),
+            (
+                link,
+                "mailto:someone@example.com",
+                true,
+                PopupRoute::PopupWindow,
+            ),
+            (link, "not a url", true, PopupRoute::PopupWindow),
+            (link, "", true, PopupRoute::PopupWindow),
+            (link, "https://example.com/", false, PopupRoute::PopupWindow),
+            (
+                PopupRequest::Window,
+                "https://example.com/login",
+                true,
+                PopupRoute::PopupWindow,
+            ),
+        ];
+        for (request, target_url, from_content_tab, expected) in cases {
+            assert_eq!(
+                route(request, target_url, from_content_tab),
+                expected,
+                "{request:?} to '{target_url}' from a tab: {from_content_tab}"
+            );
+        }
+    }
+}
@@ -1,13 +1,24 @@
 use cef::rc::Rc;
 use cef::*;
-use std::cell::RefCell;
+use pulse_core::protocol::{
+    HostCommand, HostError, HostErrorCode, HostOutcome, HostRequest, HostResponse,
+};
+use pulse_core::tab_lifecycle::{self, ScrollPosition, TabSnapshot, TabSnapshotRequest};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 
-use super::persistence;
+use super::internal_scheme;
+use super::persistence_worker::PersistenceOp;
 use super::simple_handler::*;
 use super::state::ShellState;
 
 const DEFAULT_UI_URL: &str = "http://localhost:5173";
+/// How long a bridge Promise waits for the host before rejecting with `timeout`.
+const HOST_REQUEST_TIMEOUT_MS: i64 = 30_000;
+/// Events kept for the UI until it subscribes; older ones are dropped first.
+const MAX_QUEUED_HOST_EVENTS: usize = 256;
 
 pub fn ui_url() -> String {
     std::env::var("PULSE_UI_URL")
@@ -31,10 +42,6 @@ fn rect_copy(rect: &Rect) -> Rect {
     }
 }
 
-fn ipc_command_message_name() -> CefString {
-    CefString::from(IPC_COMMAND_MESSAGE)
-}
-
 fn is_trusted_ui_url(url: &str, trusted_origin: &str) -> bool {
     let prefix = trusted_origin.trim_end_matches('/');
     url == prefix || url.starts_with(&(prefix.to_string() + "/"))
@@ -53,24 +60,56 @@ fn is_trusted_ui_context() -> bool {
     is_trusted_ui_url(&frame_url, &trusted_ui_origin_prefix())
 }
 
-fn v8_value_to_command_arg(value: &V8Value) -> Option<String> {
-    if value.is_string() != 0 {
-        return Some(CefString::from(&value.string_value()).to_string());
-    }
-    if value.is_int() != 0 {
-        return Some(value.int_value().to_string());
-    }
-    if value.is_uint() != 0 {
-        return Some(value.uint_value().to_string());
-    }
-    if value.is_double() != 0 {
-        return Some(value.double_value().to_string());
-    }
-    if value.is_bool() != 0 {
-        return Some((value.bool_value() != 0).to_string());
+/// `JSON.stringify` or `JSON.parse` of the page.
+fn json_function(context: &V8Context, name: &str) -> Result<V8Value, String> {
+    context
+        .global()
+        .and_then(|global| global.value_bykey(Some(&CefString::from("JSON"))))
+        .and_then(|json| json.value_bykey(Some(&CefString::from(name))))
+        .ok_or_else(|| format!("JSON.{name} is not available"))
+}
+
+/// Encodes `value` with the page's own `JSON.stringify`, so objects and arrays keep their
+/// shape on the way to the host.
+fn v8_value_to_json(context: &V8Context, value: &V8Value) -> Result<String, String> {
+    let encoded = json_function(context, "stringify")?
+        .execute_function(None, Some(&[Some(value.clone())]))
+        .filter(|encoded| encoded.is_string() != 0)
+        .ok_or_else(|| String::from("Value cannot be encoded as JSON"))?;
+    Ok(CefString::from(&encoded.string_value()).to_string())
+}
+
+fn json_to_v8_value(context: &V8Context, value: &Value) -> Option<V8Value> {
+    if value.is_null() {
+        return v8_value_create_null();
     }
+    let text = v8_value_create_string(Some(&CefString::from(value.to_string().as_str())));
+    json_function(context, "parse")
+        .ok()?
+        .execute_function(None, Some(&[text]))
+}
 
-    None
+/// Reads the optional `timeoutMs` argument of `send`.
+fn v8_value_to_timeout_ms(value: Option<&V8Value>) -> Result<i64, String> {
+    let Some(value) = value.filter(|value| value.is_undefined() == 0) else {
+        return Ok(HOST_REQUEST_TIMEOUT_MS);
+    };
+    let timeout_ms = if value.is_int() != 0 {
+        f64::from(value.int_value())
+    } else if value.is_uint() != 0 {
+        f64::from(value.uint_value())
+    } else if value.is_double() != 0 {
+        value.double_value()
+    } else {
+        f64::NAN
+    };
+    if timeout_ms.is_finite() && timeout_ms >= 1.0 {
+        Ok(timeout_ms as i64)
+    } else {
+        Err(String::from(
+            "pulseHost.send timeoutMs must be a positive number",
+        ))
+    }
 }
 
 wrap_v8_handler! {
@@ -94,122 +133,339 @@ wrap_v8_handler! {
                 return 0;
             }
 
-            let Some(arguments) = arguments else {
+            let Some(argument) = arguments.and_then(|arguments| arguments.first()?.as_ref())
+            else {
                 if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.send requires arguments");
+                    *exception = CefString::from("pulseHost.send requires a command object");
                 }
                 return 0;
             };
 
-            if arguments.is_empty() {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.send requires a command argument");
-                }
-                return 0;
-            }
-
-            let mut encoded_args = Vec::with_capacity(arguments.len());
-            for argument in arguments {
-                let Some(argument) = argument.as_ref() else {
-                    encoded_args.push(String::new());
-                    continue;
-                };
-
-                let Some(value) = v8_value_to_command_arg(argument) else {
+            let timeout_ms = v8_value_to_timeout_ms(
+                arguments
+                    .and_then(|arguments| arguments.get(1))
+                    .and_then(Option::as_ref),
+            );
+            let promise = current_context()
+                .and_then(|context| v8_value_to_json(&context, argument))
+                .and_then(|json| {
+                    serde_json::from_str::<HostCommand>(&json)
+                        .map_err(|error| format!("Invalid pulseHost.send command: {error}"))
+                })
+                .and_then(|command| {
+                    send_host_request(command, ResolveAs::Value, timeout_ms?)
+                });
+            let promise = match promise {
+                Ok(promise) => promise,
+                Err(error) => {
                     if let Some(exception) = exception {
-                        *exception = CefString::from("Unsupported pulseHost.send argument type");
+                        *exception = CefString::from(error.as_str());
                     }
                     return 0;
-                };
-                encoded_args.push(value);
-            }
-
-            let Some(mut message) = process_message_create(Some(&ipc_command_message_name())) else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("Failed to create CEF process message");
-                }
-                return 0;
-            };
-            let Some(argument_list) = message.argument_list() else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("Failed to access CEF process message args");
                 }
-                return 0;
             };
 
-            argument_list.set_size(encoded_args.len());
-            for (index, value) in encoded_args.iter().enumerate() {
-                argument_list.set_string(index, Some(&CefString::from(value.as_str())));
+            if let Some(retval) = retval {
+                *retval = Some(promise);
             }
+            1
+        }
+    }
+}
 
-            let Some(context) = v8_context_get_current_context() else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("No current V8 context");
-                }
-                return 0;
-            };
-            let Some(frame) = context.frame() else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("No current frame for V8 context");
+/// What a successful response resolves a bridge Promise with.
+#[derive(Clone, Copy)]
+enum ResolveAs {
+    /// The result itself (`send`).
+    Value,
+    /// The result as JSON text, `null` for no result (the persistence functions).
+    JsonText,
+}
+
+/// A bridge Promise waiting for the browser process to answer with `IPC_RESPONSE_MESSAGE`.
+struct PendingHostRequest {
+    context: V8Context,
+    promise: V8Value,
+    resolve_as: ResolveAs,
+}
+
+thread_local! {
+    static PENDING_HOST_REQUESTS: RefCell<HashMap<u64, PendingHostRequest>> =
+        RefCell::new(HashMap::new());
+    static NEXT_HOST_REQUEST_ID: Cell<u64> = const { Cell::new(1) };
+}
+
+fn current_context() -> Result<V8Context, String> {
+    v8_context_get_current_context().ok_or_else(|| String::from("No current V8 context"))
+}
+
+/// Posts `command` to the browser process and returns the Promise its response settles.
+/// Without a response within `timeout_ms` the Promise rejects with a `timeout` error.
+fn send_host_request(
+    command: HostCommand,
+    resolve_as: ResolveAs,
+    timeout_ms: i64,
+) -> Result<V8Value, String> {
+    let context = current_context()?;
+    let frame = context
+        .frame()
+        .ok_or_else(|| String::from("No current frame for V8 context"))?;
+    let promise =
+        v8_value_create_promise().ok_or_else(|| String::from("Failed to create V8 promise"))?;
+    let mut message = process_message_create(Some(&CefString::from(IPC_REQUEST_MESSAGE)))
+        .ok_or_else(|| String::from("Failed to create CEF process message"))?;
+    let argument_list = message
+        .argument_list()
+        .ok_or_else(|| String::from("Failed to access CEF process message args"))?;
+
+    let id = NEXT_HOST_REQUEST_ID.with(|next_id| {
+        let id = next_id.get();
+        next_id.set(id + 1);
+        id
+    });
+    let request = HostRequest::new(id, command).encode();
+    argument_list.set_size(1);
+    argument_list.set_string(0, Some(&CefString::from(request.as_str())));
+
+    PENDING_HOST_REQUESTS.with(|pending| {
+        pending.borrow_mut().insert(
+            id,
+            PendingHostRequest {
+                context,
+                promise: promise.clone(),
+                resolve_as,
+            },
+        );
+    });
+    frame.send_process_message(ProcessId::BROWSER, Some(&mut message));
+
+    let mut timeout = HostRequestTimeoutTask::new(id, timeout_ms);
+    post_delayed_task(ThreadId::RENDERER, Some(&mut timeout), timeout_ms);
+
+    Ok(promise)
+}
+
+wrap_task! {
+    struct HostRequestTimeoutTask {
+        id: u64,
+        timeout_ms: i64,
+    }
+
+    impl Task {
+        fn execute(&self) {
+            settle_host_request(
+                self.id,
+                Err(HostError::new(
+                    HostErrorCode::Timeout,
+                    format!("The host did not answer within {} ms", self.timeout_ms),
+                )),
+            );
+        }
+    }
+}
+
+fn on_host_response(args: &ListValue) {
+    let response =
+        list_string_arg(args, 0).and_then(|json| serde_json::from_str::<HostResponse>(&json).ok());
+    let Some(response) = response else {
+        eprintln!("Pulse bridge warning: dropped a malformed host response");
+        return;
+    };
+
+    let result = match response.outcome {
+        HostOutcome::Ok { value } => Ok(value),
+        HostOutcome::Error { error } => Err(error),
+    };
+    settle_host_request(response.id, result);
+}
+
+/// Settles request `id` once; a response arriving after its timeout finds nothing to settle.
+/// Errors reject with the JSON-encoded `HostError`.
+fn settle_host_request(id: u64, result: Result<Value, HostError>) {
+    let Some(pending) = PENDING_HOST_REQUESTS.with(|pending| pending.borrow_mut().remove(&id))
+    else {
+        return;
+    };
+
+    if pending.context.enter() == 0 {
+        return;
+    }
+
+    match result {
+        Ok(value) => {
+            let mut result = match pending.resolve_as {
+                ResolveAs::Value => json_to_v8_value(&pending.context, &value),
+                ResolveAs::JsonText if value.is_null() => v8_value_create_null(),
+                ResolveAs::JsonText => {
+                    v8_value_create_string(Some(&CefString::from(value.to_string().as_str())))
                 }
-                return 0;
             };
+            pending.promise.resolve_promise(result.as_mut());
+        }
+        Err(error) => {
+            let error = serde_json::to_string(&error).unwrap_or(error.message);
+            pending
+                .promise
+                .reject_promise(Some(&CefString::from(error.as_str())));
+        }
+    }
 
-            frame.send_process_message(ProcessId::BROWSER, Some(&mut message));
+    pending.context.exit();
+}
 
-            if let Some(retval) = retval {
-                *retval = v8_value_create_bool(1);
+/// A `__pulseHost.subscribe` callback of one context.
+struct HostEventListener {
+    id: i32,
+    context: V8Context,
+    callback: V8Value,
+}
+
+thread_local! {
+    static HOST_EVENT_LISTENERS: RefCell<Vec<HostEventListener>> = const { RefCell::new(Vec::new()) };
+    static NEXT_HOST_EVENT_LISTENER_ID: Cell<i32> = const { Cell::new(1) };
+    /// Events that arrived before the UI subscribed, as JSON text.
+    static QUEUED_HOST_EVENTS: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+}
+
+fn same_context(left: &V8Context, right: &V8Context) -> bool {
+    let mut right = right.clone();
+    left.is_same(Some(&mut right)) != 0
+}
+
+/// Calls every listener of `context` with the parsed event, in subscription order. Without a
+/// listener the event is queued for the first `subscribe`.
+fn dispatch_host_event(context: &V8Context, event_json: String) {
+    let callbacks = HOST_EVENT_LISTENERS.with(|listeners| {
+        listeners
+            .borrow()
+            .iter()
+            .filter(|listener| same_context(&listener.context, context))
+            .map(|listener| listener.callback.clone())
+            .collect::<Vec<_>>()
+    });
+    if callbacks.is_empty() {
+        QUEUED_HOST_EVENTS.with(|queued| {
+            let mut queued = queued.borrow_mut();
+            if queued.len() == MAX_QUEUED_HOST_EVENTS {
+                queued.pop_front();
+                eprintln!(
+                    "Pulse bridge warning: dropped a host event; nothing subscribed to __pulseHost"
+                );
+            }
+            queued.push_back(event_json);
+        });
+        return;
+    }
+
+    if context.enter() == 0 {
+        return;
+    }
+    let event = json_function(context, "parse").ok().and_then(|parse| {
+        let text = v8_value_create_string(Some(&CefString::from(event_json.as_str())));
+        parse.execute_function(None, Some(&[text]))
+    });
+    match event {
+        Some(event) => {
+            for callback in callbacks {
+                // A throwing listener must not keep the others from seeing the event.
+                let _ = callback.execute_function(None, Some(&[Some(event.clone())]));
+            }
+        }
+        None => eprintln!("Pulse bridge warning: dropped a malformed host event"),
+    }
+    context.exit();
+}
+
+fn on_host_event(frame: &Frame, args: &ListValue) {
+    let (Some(context), Some(event_json)) = (frame.v8_context(), list_string_arg(args, 0)) else {
+        return;
+    };
+    dispatch_host_event(&context, event_json);
+}
+
+wrap_task! {
+    struct HostEventFlushTask {
+        context: V8Context,
+    }
+
+    impl Task {
+        fn execute(&self) {
+            let queued = QUEUED_HOST_EVENTS.with(|queued| std::mem::take(&mut *queued.borrow_mut()));
+            for event_json in queued {
+                dispatch_host_event(&self.context, event_json);
             }
-            1
         }
     }
 }
 
+fn set_exception(exception: Option<&mut CefString>, message: &str) {
+    if let Some(exception) = exception {
+        *exception = CefString::from(message);
+    }
+}
+
 wrap_v8_handler! {
-    struct PulseHostLoadStateV8Handler;
+    struct PulseHostSubscribeV8Handler;
 
     impl V8Handler {
         fn execute(
             &self,
             _name: Option<&CefString>,
             _object: Option<&mut V8Value>,
-            _arguments: Option<&[Option<V8Value>]>,
+            arguments: Option<&[Option<V8Value>]>,
             retval: Option<&mut Option<V8Value>>,
             exception: Option<&mut CefString>,
         ) -> i32 {
             if !is_trusted_ui_context() {
-                if let Some(exception) = exception {
-                    *exception = CefString::from(
-                        "pulseHost.loadState is only available from the trusted Pulse UI origin",
-                    );
-                }
+                set_exception(
+                    exception,
+                    "pulseHost.subscribe is only available from the trusted Pulse UI origin",
+                );
                 return 0;
             }
-
-            let loaded_state = match persistence::load_ui_state_json() {
-                Ok(value) => value,
+            let Some(callback) = arguments
+                .and_then(|arguments| arguments.first()?.clone())
+                .filter(|callback| callback.is_function() != 0)
+            else {
+                set_exception(exception, "pulseHost.subscribe requires a listener function");
+                return 0;
+            };
+            let context = match current_context() {
+                Ok(context) => context,
                 Err(error) => {
-                    eprintln!("Pulse persistence warning: failed to load UI state: {error}");
-                    None
+                    set_exception(exception, &error);
+                    return 0;
                 }
             };
 
-            if let Some(retval) = retval {
-                *retval = if let Some(json) = loaded_state {
-                    let json = CefString::from(json.as_str());
-                    v8_value_create_string(Some(&json))
-                } else {
-                    v8_value_create_null()
-                };
+            let id = NEXT_HOST_EVENT_LISTENER_ID.with(|next_id| {
+                let id = next_id.get();
+                next_id.set(id + 1);
+                id
+            });
+            HOST_EVENT_LISTENERS.with(|listeners| {
+                listeners.borrow_mut().push(HostEventListener {
+                    id,
+                    context: context.clone(),
+                    callback,
+                });
+            });
+            // Queued events go out after `subscribe` returns, like every later event.
+            if QUEUED_HOST_EVENTS.with(|queued| !queued.borrow().is_empty()) {
+                let mut flush = HostEventFlushTask::new(context);
+                post_task(ThreadId::RENDERER, Some(&mut flush));
             }
 
+            if let Some(retval) = retval {
+                *retval = v8_value_create_int(id);
+            }
             1
         }
     }
 }
 
 wrap_v8_handler! {
-    struct PulseHostSaveStateV8Handler;
+    struct PulseHostUnsubscribeV8Handler;
 
     impl V8Handler {
         fn execute(
@@ -217,55 +473,98 @@ wrap_v8_handler! {
             _name: Option<&CefString>,
             _object: Option<&mut V8Value>,
             arguments: Option<&[Option<V8Value>]>,
-            retval: Option<&mut Option<V8Value>>,
+            _retval: Option<&mut Option<V8Value>>,
             exception: Option<&mut CefString>,
         ) -> i32 {
             if !is_trusted_ui_context() {
-                if let Some(exception) = exception {
-                    *exception = CefString::from(
-                        "pulseHost.saveState is only available from the trusted Pulse UI origin",
-                    );
-                }
+                set_exception(
+                    exception,
+                    "pulseHost.unsubscribe is only available from the trusted Pulse UI origin",
+                );
                 return 0;
             }
-
-            let Some(arguments) = arguments else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.saveState requires one JSON argument");
-                }
+            let Some(id) = arguments
+                .and_then(|arguments| arguments.first()?.as_ref())
+                .filter(|id| id.is_int() != 0)
+                .map(|id| id.int_value())
+            else {
+                set_exception(exception, "pulseHost.unsubscribe requires a subscription id");
                 return 0;
             };
-            if arguments.is_empty() {
+
+            HOST_EVENT_LISTENERS.with(|listeners| {
+                listeners.borrow_mut().retain(|listener| listener.id != id);
+            });
+            1
+        }
+    }
+}
+
+wrap_v8_handler! {
+    struct PulseHostPersistenceV8Handler {
+        op: PersistenceOp,
+    }
+
+    impl V8Handler {
+        fn execute(
+            &self,
+            _name: Option<&CefString>,
+            _object: Option<&mut V8Value>,
+            arguments: Option<&[Option<V8Value>]>,
+            retval: Option<&mut Option<V8Value>>,
+            exception: Option<&mut CefString>,
+        ) -> i32 {
+            let bridge_name = self.op.bridge_name();
+            if !is_trusted_ui_context() {
                 if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.saveState requires one JSON argument");
+                    *exception = CefString::from(
+                        format!(
+                            "pulseHost.{bridge_name} is only available from the trusted Pulse UI origin"
+                        )
+                        .as_str(),
+                    );
                 }
                 return 0;
             }
 
-            let Some(payload) = arguments[0].as_ref() else {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.saveState requires a JSON string payload");
+            let payload = if self.op.takes_payload() {
+                let Some(payload) = arguments.and_then(|arguments| arguments.first()?.as_ref())
+                else {
+                    if let Some(exception) = exception {
+                        *exception = CefString::from(
+                            format!("pulseHost.{bridge_name} requires one string argument").as_str(),
+                        );
+                    }
+                    return 0;
+                };
+                if payload.is_string() == 0 {
+                    if let Some(exception) = exception {
+                        *exception = CefString::from(
+                            format!("pulseHost.{bridge_name} argument must be a string").as_str(),
+                        );
+                    }
+                    return 0;
                 }
-                return 0;
+                Some(CefString::from(&payload.string_value()).to_string())
+            } else {
+                None
             };
-            if payload.is_string() == 0 {
-                if let Some(exception) = exception {
-                    *exception = CefString::from("pulseHost.saveState payload must be a JSON string");
-                }
-                return 0;
-            }
 
-            let payload_json = CefString::from(&payload.string_value()).to_string();
-            let saved = match persistence::save_ui_state_json(payload_json.as_str()) {
-                Ok(_) => true,
+            let promise = self.op.command(payload).and_then(|command| {
+                send_host_request(command, ResolveAs::JsonText, HOST_REQUEST_TIMEOUT_MS)
+            });
+            let promise = match promise {
+                Ok(promise) => promise,
                 Err(error) => {
-                    eprintln!("Pulse persistence warning: failed to save UI state: {error}");
-                    false
+                    if let Some(exception) = exception {
+                        *exception = CefString::from(error.as_str());
+                    }
+                    return 0;
                 }
             };
 
             if let Some(retval) = retval {
-                *retval = v8_value_create_bool(if saved { 1 } else { 0 });
+                *retval = Some(promise);
             }
 
             1
@@ -273,6 +572,80 @@ wrap_v8_handler! {
     }
 }
 
+/// Answers the host's sweep from a content page's renderer.
+fn reply_tab_snapshot(frame: &Frame, request: TabSnapshotRequest) {
+    let Some(context) = frame.v8_context() else {
+        return;
+    };
+    if context.enter() == 0 {
+        return;
+    }
+    let snapshot = context.global().map(|window| TabSnapshot {
+        scroll: ScrollPosition {
+            x: number_property(&window, "scrollX"),
+            y: number_property(&window, "scrollY"),
+        },
+        audible: is_playing_audio(&window),
+        discard: request.discard,
+    });
+    context.exit();
+
+    let Some(snapshot) = snapshot else {
+        return;
+    };
+    let Some(mut message) =
+        process_message_create(Some(&CefString::from(IPC_TAB_SNAPSHOT_MESSAGE)))
+    else {
+        return;
+    };
+    let Some(argument_list) = message.argument_list() else {
+        return;
+    };
+    let snapshot = serde_json::to_string(&snapshot).expect("tab snapshots serialize");
+    argument_list.set_size(1);
+    argument_list.set_string(0, Some(&CefString::from(snapshot.as_str())));
+    frame.send_process_message(ProcessId::BROWSER, Some(&mut message));
+}
+
+/// Whether an `<audio>` or `<video>` element of the document is playing with sound. Web
+/// Audio and media inside frames are not seen.
+fn is_playing_audio(window: &V8Value) -> bool {
+    let Some(mut document) = window.value_bykey(Some(&CefString::from("document"))) else {
+        return false;
+    };
+    let selector = v8_value_create_string(Some(&CefString::from("audio, video")));
+    let Some(media) = document
+        .value_bykey(Some(&CefString::from("querySelectorAll")))
+        .and_then(|query| query.execute_function(Some(&mut document), Some(&[selector])))
+    else {
+        return false;
+    };
+    let count = number_property(&media, "length") as i32;
+    (0..count)
+        .filter_map(|index| media.value_byindex(index))
+        .any(|element| {
+            !bool_property(&element, "paused")
+                && !bool_property(&element, "muted")
+                && number_property(&element, "volume") > 0.0
+        })
+}
+
+/// A finite number property of `object`, else 0.
+fn number_property(object: &V8Value, key: &str) -> f64 {
+    object
+        .value_bykey(Some(&CefString::from(key)))
+        .filter(|value| value.is_int() != 0 || value.is_uint() != 0 || value.is_double() != 0)
+        .map(|value| value.double_value())
+        .filter(|value| value.is_finite())
+        .unwrap_or(0.0)
+}
+
+fn bool_property(object: &V8Value, key: &str) -> bool {
+    object
+        .value_bykey(Some(&CefString::from(key)))
+        .is_some_and(|value| value.is_bool() != 0 && value.bool_value() != 0)
+}
+
 wrap_render_process_handler! {
     struct PulseRenderProcessHandler;
 
@@ -309,37 +682,51 @@ wrap_render_process_handler! {
                 return;
             };
 
-            let mut host_load_state_handler = PulseHostLoadStateV8Handler::new();
-            let Some(mut load_state_fn) = v8_value_create_function(
-                Some(&CefString::from("loadState")),
-                Some(&mut host_load_state_handler),
-            ) else {
-                return;
-            };
-
-            let mut host_save_state_handler = PulseHostSaveStateV8Handler::new();
-            let Some(mut save_state_fn) = v8_value_create_function(
-                Some(&CefString::from("saveState")),
-                Some(&mut host_save_state_handler),
-            ) else {
-                return;
-            };
-
             pulse_host_object.set_value_bykey(
                 Some(&CefString::from("send")),
                 Some(&mut send_fn),
                 V8Propertyattribute::default(),
             );
-            pulse_host_object.set_value_bykey(
-                Some(&CefString::from("loadState")),
-                Some(&mut load_state_fn),
-                V8Propertyattribute::default(),
-            );
-            pulse_host_object.set_value_bykey(
-                Some(&CefString::from("saveState")),
-                Some(&mut save_state_fn),
-                V8Propertyattribute::default(),
-            );
+            let mut subscribe_handler = PulseHostSubscribeV8Handler::new();
+            let mut unsubscribe_handler = PulseHostUnsubscribeV8Handler::new();
+            for (name, function) in [
+                (
+                    "subscribe",
+                    v8_value_create_function(
+                        Some(&CefString::from("subscribe")),
+                        Some(&mut subscribe_handler),
+                    ),
+                ),
+                (
+                    "unsubscribe",
+                    v8_value_create_function(
+                        Some(&CefString::from("unsubscribe")),
+                        Some(&mut unsubscribe_handler),
+                    ),
+                ),
+            ] {
+                let Some(mut function) = function else {
+                    return;
+                };
+                pulse_host_object.set_value_bykey(
+                    Some(&CefString::from(name)),
+                    Some(&mut function),
+                    V8Propertyattribute::default(),
+                );
+            }
+            for op in PersistenceOp::ALL {
+                let mut handler = PulseHostPersistenceV8Handler::new(op);
+                let name = CefString::from(op.bridge_name());
+                let Some(mut function) = v8_value_create_function(Some(&name), Some(&mut handler))
+                else {
+                    return;
+                };
+                pulse_host_object.set_value_bykey(
+                    Some(&name),
+                    Some(&mut function),
+                    V8Propertyattribute::default(),
+                );
+            }
             let mut pulse_host_value = pulse_host_object;
             global.set_value_bykey(
                 Some(&CefString::from("__pulseHost")),
@@ -347,6 +734,69 @@ wrap_render_process_handler! {
                 V8Propertyattribute::default(),
             );
         }
+
+        fn on_context_released(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            context: Option<&mut V8Context>,
+        ) {
+            let Some(context) = context else {
+                return;
+            };
+
+            // Promises of a torn-down context can never settle; drop them with it.
+            PENDING_HOST_REQUESTS.with(|pending| {
+                pending
+                    .borrow_mut()
+                    .retain(|_, request| request.context.is_same(Some(&mut *context)) == 0);
+            });
+            HOST_EVENT_LISTENERS.with(|listeners| {
+                listeners
+                    .borrow_mut()
+                    .retain(|listener| !same_context(&listener.context, context));
+            });
+            // Queued events describe the page that just went away; the next one reloads state.
+            QUEUED_HOST_EVENTS.with(|queued| queued.borrow_mut().clear());
+        }
+
+        fn on_process_message_received(
+            &self,
+            _browser: Option<&mut Browser>,
+            frame: Option<&mut Frame>,
+            source_process: ProcessId,
+            message: Option<&mut ProcessMessage>,
+        ) -> i32 {
+            if source_process != ProcessId::BROWSER {
+                return 0;
+            }
+            let Some(message) = message else {
+                return 0;
+            };
+            let Some(args) = message.argument_list() else {
+                return 0;
+            };
+
+            match CefString::from(&message.name()).to_string().as_str() {
+                IPC_RESPONSE_MESSAGE => on_host_response(&args),
+                IPC_EVENT_MESSAGE => {
+                    let Some(frame) = frame else {
+                        return 0;
+                    };
+                    on_host_event(frame, &args);
+                }
+                IPC_TAB_SNAPSHOT_REQUEST_MESSAGE => {
+                    let request = list_string_arg(&args, 0)
+                        .and_then(|json| serde_json::from_str::<TabSnapshotRequest>(&json).ok());
+                    let (Some(frame), Some(request)) = (frame, request) else {
+                        return 0;
+                    };
+                    reply_tab_snapshot(frame, request);
+                }
+                _ => return 0,
+            }
+            1
+        }
     }
 }
 
@@ -539,8 +989,56 @@ wrap_window_delegate! {
     }
 }
 
+wrap_task! {
+    struct ForwardedLaunchTask {
+        shell_state: Arc<Mutex<ShellState>>,
+        urls: Vec<String>,
+    }
+
+    impl Task {
+        fn execute(&self) {
+            {
+                let mut state = self
+                    .shell_state
+                    .lock()
+                    .expect("Failed to lock shell state");
+                state.queue_launch_urls(self.urls.clone());
+            }
+            deliver_launch_urls(&self.shell_state);
+        }
+    }
+}
+
+/// Hands URLs from another launch to the UI thread. Safe to call from any thread.
+pub fn open_forwarded_launch(shell_state: &Arc<Mutex<ShellState>>, urls: Vec<String>) {
+    let mut task = ForwardedLaunchTask::new(shell_state.clone(), urls);
+    post_task(ThreadId::UI, Some(&mut task));
+}
+
+wrap_task! {
+    struct TabSweepTask {
+        shell_state: Arc<Mutex<ShellState>>,
+    }
+
+    impl Task {
+        fn execute(&self) {
+            sweep_background_tabs(&self.shell_state);
+            schedule_tab_sweep(&self.shell_state);
+        }
+    }
+}
+
+/// Runs `sweep_background_tabs` on the UI thread every `tab_lifecycle::SWEEP_INTERVAL`.
+fn schedule_tab_sweep(shell_state: &Arc<Mutex<ShellState>>) {
+    let mut task = TabSweepTask::new(shell_state.clone());
+    let delay_ms = tab_lifecycle::SWEEP_INTERVAL.as_millis() as i64;
+    post_delayed_task(ThreadId::UI, Some(&mut task), delay_ms);
+}
+
 wrap_app! {
-    pub struct SimpleApp;
+    pub struct SimpleApp {
+        shell_state: Arc<Mutex<ShellState>>,
+    }
 
     impl App {
         fn on_before_command_line_processing(
@@ -572,11 +1070,17 @@ wrap_app! {
             }
         }
 
+        fn on_register_custom_schemes(&self, registrar: Option<&mut SchemeRegistrar>) {
+            if let Some(registrar) = registrar {
+                internal_scheme::register_custom_scheme(registrar);
+            }
+        }
+
         fn browser_process_handler(&self) -> Option<BrowserProcessHandler> {
             Some(SimpleBrowserProcessHandler::new(
                 RefCell::new(None),
                 RefCell::new(None),
-                Arc::new(Mutex::new(ShellState::default())),
+                self.shell_state.clone(),
             ))
         }
 
@@ -604,6 +1108,8 @@ wrap_browser_process_handler! {
                 configured_ui_url,
                 trusted_ui_origin
             );
+            internal_scheme::register_handlers(self.shell_state.clone());
+            schedule_tab_sweep(&self.shell_state);
 
             let content_handler = SimpleHandler::new(
                 BrowserRole::WebContent,
@@ -1,8 +1,21 @@
-use super::state::{ContentBounds, ShellState};
+use super::authorization;
+use super::error_page::{self, LoadFailure};
+use super::favicon_capture;
+use super::history_capture;
+use super::persistence_worker::{self, PersistenceOp};
+use super::popup_policy::{self, PopupRequest, PopupRoute};
+use super::state::{ContentBounds, HistoryStep, ShellState};
 use cef::rc::Rc;
 use cef::*;
-use serde_json::json;
+use pulse_core::protocol::{
+    HostCommand, HostError, HostErrorCode, HostEvent, HostEventMessage, HostOutcome, HostRequest,
+    HostResponse, NavState, TabLifecycle,
+};
+use pulse_core::tab_lifecycle::{self, TabSnapshot, TabSnapshotRequest};
+use serde_json::{Value, json};
+use std::os::raw::c_int;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 #[cfg(target_os = "windows")]
 mod win;
@@ -15,19 +28,17 @@ fn platform_title_change(_browser: Option<&mut Browser>, _title: Option<&CefStri
 #[cfg(not(target_os = "windows"))]
 fn platform_after_created(_browser: Option<&mut Browser>) {}
 
-pub const IPC_COMMAND_MESSAGE: &str = "pulse-host-command";
+/// Renderer -> browser: one JSON `pulse_core::protocol::HostRequest`.
+pub const IPC_REQUEST_MESSAGE: &str = "pulse-host-request";
+/// Browser -> renderer: one JSON `pulse_core::protocol::HostResponse` with the request's id.
+pub const IPC_RESPONSE_MESSAGE: &str = "pulse-host-response";
+/// Browser -> renderer: one JSON `pulse_core::protocol::HostEventMessage`.
+pub const IPC_EVENT_MESSAGE: &str = "pulse-host-event";
+/// Browser -> a background tab's renderer: one JSON `tab_lifecycle::TabSnapshotRequest`.
+pub const IPC_TAB_SNAPSHOT_REQUEST_MESSAGE: &str = "pulse-tab-snapshot-request";
+/// That renderer -> browser: one JSON `tab_lifecycle::TabSnapshot`.
+pub const IPC_TAB_SNAPSHOT_MESSAGE: &str = "pulse-tab-snapshot";
 pub const APP_RUNTIME_STYLE: RuntimeStyle = RuntimeStyle::ALLOY;
-const CMD_ENSURE_TAB: &str = "ensure-tab";
-const CMD_ACTIVATE_TAB: &str = "activate-tab";
-const CMD_NAVIGATE_TAB: &str = "navigate-tab";
-const CMD_NAVIGATE: &str = "navigate";
-const CMD_CLOSE_TAB: &str = "close-tab";
-const CMD_BROWSER_BACK: &str = "browser-back";
-const CMD_BROWSER_FORWARD: &str = "browser-forward";
-const CMD_BROWSER_RELOAD: &str = "browser-reload";
-const CMD_BROWSER_STOP: &str = "browser-stop";
-const CMD_SET_CONTENT_BOUNDS: &str = "set-content-bounds";
-const CMD_SET_CONTENT_VISIBLE: &str = "set-content-visible";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BrowserRole {
@@ -70,12 +81,7 @@ impl SimpleHandler {
                 let Some(browser) = browser else {
                     return;
                 };
-                let browser_id = browser.identifier();
-                let tab_id = {
-                    let state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.tab_id_for_browser_id(browser_id)
-                };
-                let Some(tab_id) = tab_id else {
+                let Some(tab_id) = self.tab_id_for_browser(browser) else {
                     return;
                 };
 
@@ -83,6 +89,12 @@ impl SimpleHandler {
                 let url = browser
                     .main_frame()
                     .map(|frame| CefString::from(&frame.url()).to_string());
+                {
+                    let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    state.set_tab_title(&tab_id, (!title.is_empty()).then(|| title.clone()));
+                }
+                // The current history entry carries the title too.
+                history_capture::capture(&self.shell_state, browser, tab_id.clone());
 
                 self.emit_tab_runtime_update(
                     tab_id.as_str(),
@@ -119,24 +131,244 @@ impl SimpleHandler {
         let Some(browser) = browser else {
             return;
         };
+        let Some(tab_id) = self.tab_id_for_browser(browser) else {
+            return;
+        };
 
-        let browser_id = browser.identifier();
-        let tab_id = {
+        // An error page stands in for the URL that failed, so the omnibox keeps showing that.
+        let current_url = url.and_then(|url| {
             let state = self.shell_state.lock().expect("Failed to lock shell state");
-            state.tab_id_for_browser_id(browser_id)
+            let value = state.displayed_url(browser.identifier(), url.to_string());
+            (!value.is_empty()).then_some(value)
+        });
+
+        self.emit_tab_runtime_update(tab_id.as_str(), current_url.as_deref(), None);
+    }
+
+    fn on_loading_progress_change(&mut self, browser: Option<&mut Browser>, progress: f64) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(tab_id) = self.content_tab_id(browser) else {
+            return;
+        };
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::LoadProgress {
+                tab_id,
+                progress: progress.clamp(0.0, 1.0),
+            },
+        );
+    }
+
+    fn on_favicon_urlchange(&mut self, browser: Option<&mut Browser>, icon_urls: Vec<String>) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(browser) = browser else {
+            return;
         };
-        let Some(tab_id) = tab_id else {
+        let Some(tab_id) = self.content_tab_id(Some(&mut *browser)) else {
             return;
         };
+        let icon_url = icon_urls.into_iter().find(|url| !url.is_empty());
+        favicon_capture::capture(&self.shell_state, browser, tab_id, icon_url);
+    }
 
-        let current_url = if let Some(url) = url {
-            let value = url.to_string();
-            if value.is_empty() { None } else { Some(value) }
-        } else {
-            None
+    fn on_loading_state_change(&mut self, browser: Option<&mut Browser>, nav: NavState) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(browser) = browser else {
+            return;
+        };
+        let Some(tab_id) = self.content_tab_id(Some(&mut *browser)) else {
+            return;
+        };
+        let nav = {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.set_tab_nav_state(&tab_id, nav);
+            state.tab_nav_state(&tab_id).unwrap_or(nav)
+        };
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::LoadingStateChanged {
+                tab_id: tab_id.clone(),
+                nav,
+            },
+        );
+        if !nav.is_loading {
+            history_capture::capture(&self.shell_state, browser, tab_id);
+        }
+    }
+
+    fn on_load_start(&mut self, browser: Option<&mut Browser>, frame: Option<&mut Frame>) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(url) = main_frame_url(frame) else {
+            return;
+        };
+        let Some(tab_id) = self.content_tab_id(browser) else {
+            return;
         };
+        emit_host_event(&self.shell_state, HostEvent::LoadStarted { tab_id, url });
+    }
 
-        self.emit_tab_runtime_update(tab_id.as_str(), current_url.as_deref(), None);
+    fn on_load_end(
+        &mut self,
+        browser: Option<&mut Browser>,
+        frame: Option<&mut Frame>,
+        http_status_code: i32,
+    ) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let mut frame = frame.cloned();
+        let Some(url) = main_frame_url(frame.as_mut()) else {
+            return;
+        };
+        let Some(tab_id) = self.content_tab_id(browser) else {
+            return;
+        };
+        let pending_scroll = {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.set_tab_crashed(&tab_id, false);
+            state.take_pending_scroll(&tab_id)
+        };
+        // A tab recreated after being discarded goes back to where it was scrolled.
+        if let (Some(frame), Some(scroll)) = (frame, pending_scroll) {
+            frame.execute_java_script(
+                Some(&CefString::from(scroll.restore_script().as_str())),
+                Some(&CefString::from(url.as_str())),
+                0,
+            );
+        }
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::LoadFinished {
+                tab_id,
+                url,
+                http_status_code,
+            },
+        );
+    }
+
+    fn on_load_error(
+        &mut self,
+        browser: Option<&mut Browser>,
+        frame: Option<&mut Frame>,
+        error_code: i32,
+        error_text: String,
+        failed_url: String,
+    ) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(frame) = frame.filter(|frame| frame.is_main() != 0) else {
+            return;
+        };
+        let Some(browser) = browser else {
+            return;
+        };
+        let Some(tab_id) = self.content_tab_id(Some(&mut *browser)) else {
+            return;
+        };
+        let failure = LoadFailure {
+            error_code,
+            error_text,
+            failed_url,
+        };
+        if !error_page::is_reportable(&failure) {
+            return;
+        }
+        eprintln!(
+            "Pulse tab load failed: tab_id='{tab_id}' url='{}' error={error_code} ({})",
+            failure.failed_url, failure.error_text
+        );
+
+        let error_page_url = error_page::error_page_url(&failure);
+        self.shell_state
+            .lock()
+            .expect("Failed to lock shell state")
+            .record_error_page(browser.identifier(), error_page_url.clone());
+        frame.load_url(Some(&CefString::from(error_page_url.as_str())));
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::LoadFailed {
+                tab_id,
+                url: failure.failed_url,
+                error_code,
+                error_text: failure.error_text,
+            },
+        );
+    }
+
+    fn on_render_process_terminated(
+        &mut self,
+        browser: Option<&mut Browser>,
+        error_code: i32,
+        reason: String,
+    ) {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let Some(tab_id) = self.content_tab_id(browser) else {
+            return;
+        };
+        eprintln!("Pulse tab renderer terminated: tab_id='{tab_id}' error={error_code} ({reason})");
+        let background = {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.set_tab_crashed(&tab_id, true);
+            state.active_tab_id.as_deref() != Some(tab_id.as_str())
+        };
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::TabCrashed {
+                tab_id: tab_id.clone(),
+                error_code,
+                reason,
+            },
+        );
+        // A crashed background tab will never answer a snapshot request; discarding it
+        // lets `activate-tab` recreate it.
+        if background {
+            discard_background_tab(&self.shell_state, &tab_id);
+        }
+    }
+
+    /// Returns true to cancel the popup, when it becomes a workspace tab instead.
+    fn on_before_popup(
+        &mut self,
+        browser: Option<&mut Browser>,
+        request: PopupRequest,
+        target_url: String,
+    ) -> bool {
+        debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+        let opener_tab_id = self.content_tab_id(browser);
+        match popup_policy::route(request, &target_url, opener_tab_id.is_some()) {
+            PopupRoute::NewTab { background } => {
+                let Some(opener_tab_id) = opener_tab_id else {
+                    return false;
+                };
+                eprintln!(
+                    "Pulse popup opens as a tab: opener_tab_id='{opener_tab_id}' url='{target_url}'"
+                );
+                emit_host_event(
+                    &self.shell_state,
+                    HostEvent::TabOpenRequested {
+                        opener_tab_id,
+                        url: target_url,
+                        background,
+                    },
+                );
+                true
+            }
+            PopupRoute::PopupWindow => {
+                emit_host_event(
+                    &self.shell_state,
+                    HostEvent::PopupOpened {
+                        opener_tab_id,
+                        url: Some(target_url).filter(|url| !url.is_empty()),
+                    },
+                );
+                false
+            }
+        }
     }
 
     fn on_after_created(&mut self, mut browser: Option<&mut Browser>) {
@@ -174,15 +406,21 @@ impl SimpleHandler {
             self.browser_list
                 .retain(|item| item.identifier() != closing_id);
 
-            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
-            match self.role {
-                BrowserRole::UiChrome if state.ui_browser_id == Some(closing_id) => {
-                    state.set_ui_browser_id(None);
-                }
-                BrowserRole::WebContent => {
-                    state.remove_content_tab_by_browser_id(closing_id);
+            let closed_tab_id = {
+                let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                state.forget_error_pages(closing_id);
+                match self.role {
+                    BrowserRole::UiChrome if state.ui_browser_id == Some(closing_id) => {
+                        state.set_ui_browser_id(None);
+                        None
+                    }
+                    BrowserRole::WebContent => state.remove_content_tab_by_browser_id(closing_id),
+                    _ => None,
                 }
-                _ => {}
+            };
+            // `close-tab` unregisters its tab first, so only closes the UI did not ask for remain.
+            if let Some(tab_id) = closed_tab_id {
+                emit_host_event(&self.shell_state, HostEvent::TabClosed { tab_id });
             }
         }
 
@@ -200,10 +438,6 @@ impl SimpleHandler {
     ) -> i32 {
         debug_assert_ne!(currently_on(ThreadId::UI), 0);
 
-        if self.role != BrowserRole::UiChrome {
-            return 0;
-        }
-
         if source_process != ProcessId::RENDERER {
             return 0;
         }
@@ -212,76 +446,178 @@ impl SimpleHandler {
             return 0;
         };
 
-        let message_name = CefString::from(&message.name()).to_string();
-        if message_name != IPC_COMMAND_MESSAGE {
+        let name = CefString::from(&message.name()).to_string();
+        if self.role == BrowserRole::WebContent && name == IPC_TAB_SNAPSHOT_MESSAGE {
+            let snapshot = message
+                .argument_list()
+                .and_then(|args| list_string_arg(&args, 0))
+                .and_then(|json| serde_json::from_str::<TabSnapshot>(&json).ok());
+            if let Some(snapshot) = snapshot {
+                self.on_tab_snapshot(browser, snapshot);
+            }
+            return 1;
+        }
+
+        if self.role != BrowserRole::UiChrome || name != IPC_REQUEST_MESSAGE {
             return 0;
         }
 
-        if !self.is_expected_ui_browser(browser) || !self.is_trusted_ui_frame(frame) {
+        let mut frame = frame.cloned();
+        if !self.is_expected_ui_browser(browser) || !self.is_trusted_ui_frame(frame.as_mut()) {
             return 1;
         }
-
+        let Some(frame) = frame else {
+            return 1;
+        };
         let Some(args) = message.argument_list() else {
             return 1;
         };
 
-        let Some(command) = list_string_arg(&args, 0) else {
-            return 1;
+        let request = match HostRequest::decode(&list_string_arg(&args, 0).unwrap_or_default()) {
+            Ok(request) => request,
+            Err(rejected) => {
+                eprintln!(
+                    "Pulse host warning: rejected request: {}",
+                    rejected.error.message
+                );
+                if let Some(id) = rejected.id {
+                    send_host_response(&frame, id, Err(rejected.error));
+                }
+                return 1;
+            }
         };
+        let id = request.id;
+        let command = request.command;
+
+        let authorized = authorization::required_capabilities(&command)
+            .map_err(|error| HostError::new(HostErrorCode::InvalidRequest, error.to_string()))
+            .and_then(|required| authorization::authorize(command.name(), &required));
+        if let Err(error) = authorized {
+            send_host_response(&frame, id, Err(error));
+            return 1;
+        }
 
-        match command.as_str() {
-            CMD_ENSURE_TAB => {
-                let Some(tab_id) = list_string_arg(&args, 1) else {
-                    return 1;
-                };
-                let initial_url =
-                    list_string_arg(&args, 2).unwrap_or_else(|| String::from("about:blank"));
+        if let Some((op, payload)) = PersistenceOp::from_command(&command) {
+            // Disk I/O runs on the persistence thread; browser-process frames may be used from any thread.
+            persistence_worker::submit(
+                op,
+                payload,
+                Box::new(move |result| send_host_response(&frame, id, result)),
+            );
+            return 1;
+        }
+
+        let result = self.run_command(command);
+        send_host_response(&frame, id, result);
+        1
+    }
+
+    /// Runs a tab or layout command on the UI thread. `ensure-tab` and `navigate-tab`
+    /// resolve to `{ "created": bool }`, `restore-tabs` to `{ "loaded": [tab_id] }`; the other
+    /// commands to `null`.
+    fn run_command(&mut self, command: HostCommand) -> Result<Value, HostError> {
+        match command {
+            HostCommand::EnsureTab {
+                tab_id,
+                initial_url,
+                pinned,
+            } => {
+                let initial_url = initial_url.unwrap_or_else(|| String::from("about:blank"));
                 eprintln!(
                     "Pulse host cmd ensure-tab: tab_id='{}' initial_url='{}'",
                     tab_id, initial_url
                 );
-                self.ensure_tab(&tab_id, &initial_url);
+                let created = self.ensure_tab(&tab_id, &initial_url, pinned)?;
+                if !created {
+                    let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    state.set_tab_pinned(tab_id.trim(), pinned);
+                }
+                return Ok(json!({ "created": created }));
             }
-            CMD_ACTIVATE_TAB => {
-                let Some(tab_id) = list_string_arg(&args, 1) else {
-                    return 1;
-                };
+            HostCommand::ActivateTab { tab_id } => {
                 eprintln!("Pulse host cmd activate-tab: tab_id='{}'", tab_id);
-                let tab_url = {
+                let (previous, discarded, tab_url, title, nav) = {
                     let mut state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.activate_tab(&tab_id);
+                    let previous = state.active_tab_id.clone();
+                    if !state.activate_tab(&tab_id) {
+                        return Err(unknown_tab(&tab_id));
+                    }
                     state.set_content_visible(true);
-                    state.tab_runtime_url(&tab_id)
+                    (
+                        previous,
+                        state.is_tab_discarded(&tab_id),
+                        state.tab_runtime_url(&tab_id),
+                        state.tab_title(&tab_id),
+                        state.tab_nav_state(&tab_id).unwrap_or_default(),
+                    )
                 };
+                if discarded {
+                    let url = tab_url.as_deref().unwrap_or("about:blank");
+                    eprintln!("Pulse host recreating discarded tab: tab_id='{tab_id}' url='{url}'");
+                    if self.revive_tab(&tab_id, url)? {
+                        self.sweep_if_over_limit();
+                    }
+                }
 
-                self.emit_tab_runtime_update(tab_id.as_str(), tab_url.as_deref(), None);
+                self.emit_tab_runtime_update(tab_id.as_str(), tab_url.as_deref(), title.as_deref());
+                emit_host_event(
+                    &self.shell_state,
+                    HostEvent::LoadingStateChanged {
+                        tab_id: tab_id.clone(),
+                        nav,
+                    },
+                );
+                self.emit_activation(previous, &tab_id);
             }
-            CMD_NAVIGATE_TAB => {
-                let Some(tab_id) = list_string_arg(&args, 1) else {
-                    return 1;
-                };
-                let Some(url) = list_string_arg(&args, 2) else {
-                    return 1;
-                };
+            HostCommand::NavigateTab { tab_id, url } => {
                 let trimmed = url.trim();
-                if !trimmed.is_empty() {
-                    eprintln!(
-                        "Pulse host cmd navigate-tab: tab_id='{}' url='{}'",
-                        tab_id, trimmed
-                    );
-                    self.ensure_tab(&tab_id, trimmed);
+                if trimmed.is_empty() {
+                    return Err(HostError::new(
+                        HostErrorCode::InvalidRequest,
+                        "navigate-tab needs a non-empty url",
+                    ));
+                }
+                eprintln!(
+                    "Pulse host cmd navigate-tab: tab_id='{}' url='{}'",
+                    tab_id, trimmed
+                );
+                let created = self.ensure_tab(&tab_id, trimmed, false)?;
+                let (previous, discarded) = {
                     let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    let previous = state.active_tab_id.clone();
                     state.activate_tab(&tab_id);
-                    state.navigate_tab(&tab_id, trimmed);
+                    (previous, state.is_tab_discarded(&tab_id))
+                };
+                // A discarded tab is recreated at the new URL instead of its old one.
+                let mut revived = false;
+                if discarded {
+                    {
+                        let mut state =
+                            self.shell_state.lock().expect("Failed to lock shell state");
+                        state.push_history_entry(&tab_id, trimmed);
+                    }
+                    revived = self.revive_tab(&tab_id, trimmed)?;
+                    if revived {
+                        self.sweep_if_over_limit();
+                    }
                 }
+                {
+                    let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    if revived {
+                        state.set_content_visible(true);
+                    } else {
+                        state.navigate_tab(&tab_id, trimmed);
+                    }
+                }
+                self.emit_activation(previous, &tab_id);
+                return Ok(json!({ "created": created }));
             }
-            CMD_CLOSE_TAB => {
-                let Some(tab_id) = list_string_arg(&args, 1) else {
-                    return 1;
-                };
-
+            HostCommand::CloseTab { tab_id } => {
                 let browser_to_close = {
                     let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    if !state.has_tab(&tab_id) {
+                        return Err(unknown_tab(&tab_id));
+                    }
                     state.close_tab(&tab_id)
                 };
 
@@ -291,95 +627,243 @@ impl SimpleHandler {
                     }
                 }
             }
-            CMD_BROWSER_BACK => {
-                if let Some(tab_id) = list_string_arg(&args, 1) {
-                    let state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.browser_back(&tab_id);
+            HostCommand::SetTabPinned { tab_id, pinned } => {
+                let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                if !state.set_tab_pinned(&tab_id, pinned) {
+                    return Err(unknown_tab(&tab_id));
                 }
             }
-            CMD_BROWSER_FORWARD => {
-                if let Some(tab_id) = list_string_arg(&args, 1) {
-                    let state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.browser_forward(&tab_id);
+            HostCommand::RestoreTabs {
+                tabs,
+                active_tab_id,
+                prefetch_tab_ids,
+            } => {
+                let active_tab_id = active_tab_id.map(|tab_id| tab_id.trim().to_string());
+                let prefetch_tab_ids = prefetch_tab_ids
+                    .iter()
+                    .map(|tab_id| tab_id.trim().to_string())
+                    .collect::<Vec<_>>();
+                let (previous, restored, load) = {
+                    let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                    let restored = tabs
+                        .into_iter()
+                        .filter_map(|tab| {
+                            let tab_id = tab.tab_id.trim().to_string();
+                            let url = match tab.url.trim() {
+                                "" => "about:blank",
+                                url => url,
+                            };
+                            (!tab_id.is_empty()
+                                && state.register_restored_tab(
+                                    tab_id.clone(),
+                                    url.to_string(),
+                                    tab.title,
+                                    tab.pinned,
+                                    tab.history,
+                                ))
+                            .then_some(tab_id)
+                        })
+                        .collect::<Vec<_>>();
+                    let load = tab_lifecycle::restore_load_set(
+                        &state.discard_policy,
+                        &restored,
+                        active_tab_id.as_deref(),
+                        &prefetch_tab_ids,
+                    );
+                    let previous = state.active_tab_id.clone();
+                    if let Some(active_tab_id) = active_tab_id.as_deref() {
+                        if load.iter().any(|tab_id| tab_id == active_tab_id) {
+                            state.activate_tab(active_tab_id);
+                            state.set_content_visible(true);
+                        }
+                    }
+                    (previous, restored, load)
+                };
+                eprintln!(
+                    "Pulse host cmd restore-tabs: restored {} tabs, loading {:?}",
+                    restored.len(),
+                    load
+                );
+
+                let mut loaded = Vec::with_capacity(load.len());
+                for tab_id in load {
+                    let url = {
+                        let state = self.shell_state.lock().expect("Failed to lock shell state");
+                        state.tab_runtime_url(&tab_id)
+                    };
+                    if self.revive_tab(&tab_id, url.as_deref().unwrap_or("about:blank"))? {
+                        loaded.push(tab_id);
+                    }
                 }
+                // Once for the whole set, so tabs just prefetched are not swept one by one.
+                self.sweep_if_over_limit();
+                self.emit_lifecycles(previous.into_iter().chain(restored));
+                return Ok(json!({ "loaded": loaded }));
             }
-            CMD_BROWSER_RELOAD => {
-                if let Some(tab_id) = list_string_arg(&args, 1) {
-                    let state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.browser_reload(&tab_id);
+            HostCommand::BrowserBack { tab_id } => self.step_history(&tab_id, -1)?,
+            HostCommand::BrowserForward { tab_id } => self.step_history(&tab_id, 1)?,
+            HostCommand::BrowserReload { tab_id } => {
+                let state = self.shell_state.lock().expect("Failed to lock shell state");
+                if !state.browser_reload(&tab_id) {
+                    return Err(unknown_tab(&tab_id));
                 }
             }
-            CMD_BROWSER_STOP => {
-                if let Some(tab_id) = list_string_arg(&args, 1) {
-                    let state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.browser_stop(&tab_id);
+            HostCommand::BrowserStop { tab_id } => {
+                let state = self.shell_state.lock().expect("Failed to lock shell state");
+                if !state.browser_stop(&tab_id) {
+                    return Err(unknown_tab(&tab_id));
                 }
             }
-            CMD_NAVIGATE => {
-                // Backward compatibility with the previous single-content command shape.
-                if let Some(url) = list_string_arg(&args, 1) {
-                    let trimmed = url.trim();
-                    if !trimmed.is_empty() {
-                        let mut state =
-                            self.shell_state.lock().expect("Failed to lock shell state");
-                        state.navigate_active_tab(trimmed);
-                    }
-                }
+            HostCommand::SetContentBounds {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                eprintln!(
+                    "Pulse host cmd set-content-bounds: x={} y={} width={} height={}",
+                    x, y, width, height
+                );
+                let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                state.set_content_bounds(ContentBounds {
+                    x,
+                    y,
+                    width: width.max(1),
+                    height: height.max(1),
+                });
             }
-            CMD_SET_CONTENT_BOUNDS => {
-                let x = list_i32_arg(&args, 1);
-                let y = list_i32_arg(&args, 2);
-                let width = list_i32_arg(&args, 3);
-                let height = list_i32_arg(&args, 4);
-
-                if let (Some(x), Some(y), Some(width), Some(height)) = (x, y, width, height) {
-                    eprintln!(
-                        "Pulse host cmd set-content-bounds: x={} y={} width={} height={}",
-                        x, y, width, height
-                    );
-                    let mut state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.set_content_bounds(ContentBounds {
-                        x,
-                        y,
-                        width: width.max(1),
-                        height: height.max(1),
-                    });
-                }
+            HostCommand::SetContentVisible { visible } => {
+                let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+                state.set_content_visible(visible);
             }
-            CMD_SET_CONTENT_VISIBLE => {
-                if let Some(visible) = list_bool_arg(&args, 1) {
+            HostCommand::UiReady => {
+                {
                     let mut state = self.shell_state.lock().expect("Failed to lock shell state");
-                    state.set_content_visible(visible);
+                    state.set_ui_ready();
                 }
+                deliver_launch_urls(&self.shell_state);
+            }
+            other => {
+                return Err(HostError::new(
+                    HostErrorCode::Failed,
+                    format!("{} is not a UI-thread command", other.name()),
+                ));
             }
-            _ => {}
         }
-
-        1
+        Ok(Value::Null)
     }
 
-    fn ensure_tab(&mut self, tab_id: &str, initial_url: &str) {
+    /// Creates the tab's browser unless the tab exists, even discarded. Returns whether it
+    /// was created.
+    fn ensure_tab(
+        &mut self,
+        tab_id: &str,
+        initial_url: &str,
+        pinned: bool,
+    ) -> Result<bool, HostError> {
         let tab_id = tab_id.trim();
         if tab_id.is_empty() {
-            return;
+            return Err(HostError::new(
+                HostErrorCode::InvalidRequest,
+                "tab_id must not be empty",
+            ));
         }
 
         {
             let state = self.shell_state.lock().expect("Failed to lock shell state");
             if state.has_tab(tab_id) {
-                return;
+                return Ok(false);
             }
         }
 
-        let Some(content_client) = self.content_client.clone() else {
-            return;
-        };
-
         let normalized_url = if initial_url.trim().is_empty() {
             "about:blank"
         } else {
             initial_url.trim()
         };
+        let browser_view = self.create_content_browser_view(tab_id, normalized_url)?;
+
+        {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.register_content_tab(
+                tab_id.to_string(),
+                browser_view,
+                normalized_url.to_string(),
+                pinned,
+            );
+        }
+        self.sweep_if_over_limit();
+        Ok(true)
+    }
+
+    /// Gives a discarded tab a new browser at `url`. Returns false, and closes the new
+    /// browser, when the tab is unknown or already has one. Callers sweep afterwards.
+    fn revive_tab(&mut self, tab_id: &str, url: &str) -> Result<bool, HostError> {
+        let browser_view = self.create_content_browser_view(tab_id, url)?;
+        let attached = {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.attach_browser(tab_id, browser_view.clone())
+        };
+        if !attached {
+            eprintln!(
+                "Pulse host warning: not recreating tab_id='{tab_id}', which is unknown or live"
+            );
+            // A view never added to the window may have no browser yet; it goes away with
+            // its last reference.
+            if let Some(browser_host) = browser_view.browser().and_then(|browser| browser.host()) {
+                browser_host.close_browser(1);
+            }
+        }
+        Ok(attached)
+    }
+
+    /// Sweeps background tabs when more tabs have a browser than the policy allows.
+    fn sweep_if_over_limit(&self) {
+        let over_limit = {
+            let state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.live_tab_count() > state.discard_policy.max_live_tabs
+        };
+        if over_limit {
+            sweep_background_tabs(&self.shell_state);
+        }
+    }
+
+    /// Goes back or forward, recreating the tab when the entry is older than its browser.
+    fn step_history(&mut self, tab_id: &str, offset: isize) -> Result<(), HostError> {
+        let step = {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.step_history(tab_id, offset)
+        };
+        let Some(step) = step else {
+            return Err(unknown_tab(tab_id));
+        };
+        let HistoryStep::Recreate { browser, url } = step else {
+            return Ok(());
+        };
+
+        eprintln!(
+            "Pulse host recreating tab at a restored history entry: tab_id='{tab_id}' url='{url}'"
+        );
+        if let Some(browser_host) = browser.and_then(|browser| browser.host()) {
+            browser_host.close_browser(1);
+        }
+        if self.revive_tab(tab_id, &url)? {
+            self.sweep_if_over_limit();
+        }
+        Ok(())
+    }
+
+    fn create_content_browser_view(
+        &self,
+        tab_id: &str,
+        normalized_url: &str,
+    ) -> Result<BrowserView, HostError> {
+        let Some(content_client) = self.content_client.clone() else {
+            return Err(HostError::new(
+                HostErrorCode::Failed,
+                "This browser cannot host content tabs",
+            ));
+        };
 
         let browser_settings = BrowserSettings::default();
         let url = CefString::from(normalized_url);
@@ -393,46 +877,81 @@ impl SimpleHandler {
             None,
             Some(&mut browser_view_delegate),
         ) else {
-            eprintln!(
+            let message = format!(
                 "Failed to create content BrowserView for tab_id='{tab_id}' url='{normalized_url}'"
             );
-            return;
+            eprintln!("{message}");
+            return Err(HostError::new(HostErrorCode::Failed, message));
         };
+        Ok(browser_view)
+    }
 
-        let mut state = self.shell_state.lock().expect("Failed to lock shell state");
-        state.register_content_tab(tab_id.to_string(), browser_view);
+    /// Tells the UI that `tab_id` became active and `previous`, if another tab, did not.
+    fn emit_activation(&self, previous: Option<String>, tab_id: &str) {
+        self.emit_lifecycles(
+            [
+                previous.filter(|previous| previous != tab_id),
+                Some(tab_id.to_string()),
+            ]
+            .into_iter()
+            .flatten(),
+        );
     }
 
-    fn emit_tab_runtime_update(&self, tab_id: &str, url: Option<&str>, title: Option<&str>) {
-        let ui_main_frame = {
+    /// Sends each tab's current lifecycle; unknown tabs are skipped.
+    fn emit_lifecycles(&self, tab_ids: impl IntoIterator<Item = String>) {
+        let changed = {
             let state = self.shell_state.lock().expect("Failed to lock shell state");
-            state
-                .ui_browser_view
-                .as_ref()
-                .and_then(|view| view.browser())
-                .and_then(|browser| browser.main_frame())
+            tab_ids
+                .into_iter()
+                .filter_map(|id| Some((state.tab_lifecycle(&id)?, id)))
+                .collect::<Vec<_>>()
         };
+        for (lifecycle, tab_id) in changed {
+            emit_host_event(
+                &self.shell_state,
+                HostEvent::TabLifecycleChanged { tab_id, lifecycle },
+            );
+        }
+    }
 
-        let Some(ui_main_frame) = ui_main_frame else {
+    /// A background tab's reply to `sweep_background_tabs`. The tab is discarded if the sweep
+    /// picked it and it is still in the background and silent.
+    fn on_tab_snapshot(&mut self, browser: Option<&mut Browser>, snapshot: TabSnapshot) {
+        let Some(tab_id) = self.content_tab_id(browser) else {
             return;
         };
+        {
+            let mut state = self.shell_state.lock().expect("Failed to lock shell state");
+            state.set_tab_snapshot(&tab_id, snapshot);
+        }
+        if snapshot.discard && !snapshot.audible {
+            discard_background_tab(&self.shell_state, &tab_id);
+        }
+    }
 
-        let detail = json!({
-            "tabId": tab_id,
-            "url": url,
-            "title": title,
-        });
-
-        let script = format!(
-            "(function(){{window.dispatchEvent(new CustomEvent('pulse:tab-runtime-updated',{{detail:{detail}}}));}})();",
-            detail = detail
+    fn emit_tab_runtime_update(&self, tab_id: &str, url: Option<&str>, title: Option<&str>) {
+        emit_host_event(
+            &self.shell_state,
+            HostEvent::TabUpdated {
+                tab_id: tab_id.to_string(),
+                url: url.map(String::from),
+                title: title.map(String::from),
+            },
         );
+    }
 
-        ui_main_frame.execute_java_script(
-            Some(&CefString::from(script.as_str())),
-            Some(&CefString::from("pulse://host-events")),
-            0,
-        );
+    fn tab_id_for_browser(&self, browser: &Browser) -> Option<String> {
+        let state = self.shell_state.lock().expect("Failed to lock shell state");
+        state.tab_id_for_browser_id(browser.identifier())
+    }
+
+    /// The logical tab of a content browser; `None` for the UI browser and unknown browsers.
+    fn content_tab_id(&self, browser: Option<&mut Browser>) -> Option<String> {
+        if self.role != BrowserRole::WebContent {
+            return None;
+        }
+        self.tab_id_for_browser(browser?)
     }
 
     fn is_expected_ui_browser(&self, browser: Option<&mut Browser>) -> bool {
@@ -455,12 +974,156 @@ impl SimpleHandler {
     }
 }
 
+/// Sends queued launch URLs to the UI as an `open-urls` event and brings the window forward.
+pub fn deliver_launch_urls(shell_state: &Arc<Mutex<ShellState>>) {
+    let (urls, window) = {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        (state.take_launch_urls(), state.window.clone())
+    };
+    if urls.is_empty() {
+        return;
+    }
+
+    eprintln!("Pulse host opening launch urls: {urls:?}");
+    emit_host_event(shell_state, HostEvent::OpenUrls { urls });
+
+    if let Some(window) = window {
+        if window.is_minimized() != 0 {
+            window.restore();
+        }
+        window.activate();
+    }
+}
+
+/// Asks every background tab for its scroll position and whether it is playing audio, and
+/// tells the ones the discard policy picked to be discarded after replying. Audio is only
+/// known from the page's replies, so a tab that started playing is spared on reply. Picked
+/// tabs whose renderer crashed or left an earlier request unanswered are discarded without
+/// asking again.
+pub fn sweep_background_tabs(shell_state: &Arc<Mutex<ShellState>>) {
+    debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+    let (frames, candidates, unresponsive) = {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        let now = Instant::now();
+        let live_tabs = state.live_tabs(now);
+        let candidates = tab_lifecycle::discard_candidates(&state.discard_policy, &live_tabs);
+        let unresponsive = state.request_tab_snapshots(&candidates, now);
+        (state.background_tab_frames(), candidates, unresponsive)
+    };
+    for tab_id in &unresponsive {
+        eprintln!("Pulse host warning: background tab did not reply: tab_id='{tab_id}'");
+        discard_background_tab(shell_state, tab_id);
+    }
+    for (tab_id, frame) in frames {
+        if unresponsive.contains(&tab_id) {
+            continue;
+        }
+        let request = TabSnapshotRequest {
+            discard: candidates.contains(&tab_id),
+        };
+        let Some(mut message) =
+            process_message_create(Some(&CefString::from(IPC_TAB_SNAPSHOT_REQUEST_MESSAGE)))
+        else {
+            return;
+        };
+        let Some(argument_list) = message.argument_list() else {
+            return;
+        };
+        let request = serde_json::to_string(&request).expect("snapshot requests serialize");
+        argument_list.set_size(1);
+        argument_list.set_string(0, Some(&CefString::from(request.as_str())));
+        frame.send_process_message(ProcessId::RENDERER, Some(&mut message));
+    }
+}
+
+/// Closes a background tab's browser and reports the tab discarded; does nothing for the
+/// active tab and tabs already discarded.
+fn discard_background_tab(shell_state: &Arc<Mutex<ShellState>>, tab_id: &str) {
+    let browser = {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        state.discard_tab(tab_id)
+    };
+    let Some(browser) = browser else {
+        return;
+    };
+
+    eprintln!("Pulse host discarded background tab: tab_id='{tab_id}'");
+    if let Some(browser_host) = browser.host() {
+        browser_host.close_browser(1);
+    }
+    emit_host_event(
+        shell_state,
+        HostEvent::TabLifecycleChanged {
+            tab_id: tab_id.to_string(),
+            lifecycle: TabLifecycle::Discarded,
+        },
+    );
+}
+
+/// Sends `event` to the UI's main frame. Events are numbered and sent on the UI thread, and
+/// CEF delivers a frame's process messages in order, so the UI sees them in `seq` order.
+pub fn emit_host_event(shell_state: &Arc<Mutex<ShellState>>, event: HostEvent) {
+    debug_assert_ne!(currently_on(ThreadId::UI), 0);
+
+    let message = {
+        let mut state = shell_state.lock().expect("Failed to lock shell state");
+        let ui_main_frame = state
+            .ui_browser_view
+            .as_ref()
+            .and_then(|view| view.browser())
+            .and_then(|browser| browser.main_frame());
+        ui_main_frame.map(|frame| {
+            let seq = state.next_host_event_seq();
+            (frame, HostEventMessage::new(seq, event).encode())
+        })
+    };
+    let Some((ui_main_frame, message_json)) = message else {
+        return;
+    };
+
+    let Some(mut message) = process_message_create(Some(&CefString::from(IPC_EVENT_MESSAGE)))
+    else {
+        return;
+    };
+    let Some(argument_list) = message.argument_list() else {
+        return;
+    };
+    argument_list.set_size(1);
+    argument_list.set_string(0, Some(&CefString::from(message_json.as_str())));
+    ui_main_frame.send_process_message(ProcessId::RENDERER, Some(&mut message));
+}
+
+fn unknown_tab(tab_id: &str) -> HostError {
+    HostError::new(HostErrorCode::UnknownTab, format!("No tab '{tab_id}'"))
+}
+
+fn send_host_response(frame: &Frame, id: u64, result: Result<Value, HostError>) {
+    let Some(mut message) = process_message_create(Some(&CefString::from(IPC_RESPONSE_MESSAGE)))
+    else {
+        return;
+    };
+    let Some(argument_list) = message.argument_list() else {
+        return;
+    };
+
+    let outcome = match result {
+        Ok(value) => HostOutcome::Ok { value },
+        Err(error) => HostOutcome::Error { error },
+    };
+    let response = HostResponse::new(id, outcome).encode();
+    argument_list.set_size(1);
+    argument_list.set_string(0, Some(&CefString::from(response.as_str())));
+
+    frame.send_process_message(ProcessId::RENDERER, Some(&mut message));
+}
+
 fn is_trusted_ui_url(url: &str, trusted_origin: &str) -> bool {
     let prefix = trusted_origin.trim_end_matches('/');
     url == prefix || url.starts_with(&(prefix.to_string() + "/"))
 }
 
-fn list_string_arg(args: &ListValue, index: usize) -> Option<String> {
+pub fn list_string_arg(args: &ListValue, index: usize) -> Option<String> {
     if index >= args.size() {
         return None;
     }
@@ -468,19 +1131,6 @@ fn list_string_arg(args: &ListValue, index: usize) -> Option<String> {
     Some(CefString::from(&args.string(index)).to_string())
 }
 
-fn list_i32_arg(args: &ListValue, index: usize) -> Option<i32> {
-    list_string_arg(args, index)?.trim().parse::<i32>().ok()
-}
-
-fn list_bool_arg(args: &ListValue, index: usize) -> Option<bool> {
-    let value = list_string_arg(args, index)?;
-    match value.trim().to_ascii_lowercase().as_str() {
-        "1" | "true" => Some(true),
-        "0" | "false" => Some(false),
-        _ => None,
-    }
-}
-
 wrap_browser_view_delegate! {
     struct ContentBrowserViewDelegate {
         runtime_style: RuntimeStyle,
@@ -509,6 +1159,14 @@ wrap_client! {
             Some(SimpleHandlerLifeSpanHandler::new(self.inner.clone()))
         }
 
+        fn load_handler(&self) -> Option<LoadHandler> {
+            Some(SimpleHandlerLoadHandler::new(self.inner.clone()))
+        }
+
+        fn request_handler(&self) -> Option<RequestHandler> {
+            Some(SimpleHandlerRequestHandler::new(self.inner.clone()))
+        }
+
         fn on_process_message_received(
             &self,
             browser: Option<&mut Browser>,
@@ -542,6 +1200,108 @@ wrap_display_handler! {
             let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
             inner.on_address_change(browser, frame, url);
         }
+
+        fn on_loading_progress_change(&self, browser: Option<&mut Browser>, progress: f64) {
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_loading_progress_change(browser, progress);
+        }
+
+        fn on_favicon_urlchange(
+            &self,
+            browser: Option<&mut Browser>,
+            icon_urls: Option<&mut CefStringList>,
+        ) {
+            let icon_urls = icon_urls
+                .map(|icon_urls| Vec::<String>::from(&*icon_urls))
+                .unwrap_or_default();
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_favicon_urlchange(browser, icon_urls);
+        }
+    }
+}
+
+wrap_load_handler! {
+    struct SimpleHandlerLoadHandler {
+        inner: Arc<Mutex<SimpleHandler>>,
+    }
+
+    impl LoadHandler {
+        fn on_loading_state_change(
+            &self,
+            browser: Option<&mut Browser>,
+            is_loading: c_int,
+            can_go_back: c_int,
+            can_go_forward: c_int,
+        ) {
+            let nav = NavState {
+                is_loading: is_loading != 0,
+                can_go_back: can_go_back != 0,
+                can_go_forward: can_go_forward != 0,
+            };
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_loading_state_change(browser, nav);
+        }
+
+        fn on_load_start(
+            &self,
+            browser: Option<&mut Browser>,
+            frame: Option<&mut Frame>,
+            _transition_type: TransitionType,
+        ) {
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_load_start(browser, frame);
+        }
+
+        fn on_load_end(
+            &self,
+            browser: Option<&mut Browser>,
+            frame: Option<&mut Frame>,
+            http_status_code: c_int,
+        ) {
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_load_end(browser, frame, http_status_code);
+        }
+
+        fn on_load_error(
+            &self,
+            browser: Option<&mut Browser>,
+            frame: Option<&mut Frame>,
+            error_code: Errorcode,
+            error_text: Option<&CefString>,
+            failed_url: Option<&CefString>,
+        ) {
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_load_error(
+                browser,
+                frame,
+                net_error_code(error_code),
+                error_text.map(CefString::to_string).unwrap_or_default(),
+                failed_url.map(CefString::to_string).unwrap_or_default(),
+            );
+        }
+    }
+}
+
+wrap_request_handler! {
+    struct SimpleHandlerRequestHandler {
+        inner: Arc<Mutex<SimpleHandler>>,
+    }
+
+    impl RequestHandler {
+        fn on_render_process_terminated(
+            &self,
+            browser: Option<&mut Browser>,
+            _status: TerminationStatus,
+            error_code: c_int,
+            error_string: Option<&CefString>,
+        ) {
+            let reason = error_string
+                .map(CefString::to_string)
+                .filter(|reason| !reason.is_empty())
+                .unwrap_or_else(|| String::from("terminated"));
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner.on_render_process_terminated(browser, error_code, reason);
+        }
     }
 }
 
@@ -556,6 +1316,29 @@ wrap_life_span_handler! {
             inner.on_after_created(browser);
         }
 
+        fn on_before_popup(
+            &self,
+            browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            _popup_id: c_int,
+            target_url: Option<&CefString>,
+            _target_frame_name: Option<&CefString>,
+            target_disposition: WindowOpenDisposition,
+            _user_gesture: c_int,
+            _popup_features: Option<&PopupFeatures>,
+            _window_info: Option<&mut WindowInfo>,
+            _client: Option<&mut Option<Client>>,
+            _settings: Option<&mut BrowserSettings>,
+            _extra_info: Option<&mut Option<DictionaryValue>>,
+            _no_javascript_access: Option<&mut c_int>,
+        ) -> c_int {
+            let target_url = target_url.map(CefString::to_string).unwrap_or_default();
+            let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
+            inner
+                .on_before_popup(browser, popup_request(target_disposition), target_url)
+                .into()
+        }
+
         fn do_close(&self, browser: Option<&mut Browser>) -> i32 {
             let mut inner = self.inner.lock().expect("Failed to lock SimpleHandler");
             inner.do_close(browser).into()
@@ -567,3 +1350,28 @@ wrap_life_span_handler! {
         }
     }
 }
+
+fn popup_request(disposition: WindowOpenDisposition) -> PopupRequest {
+    if disposition == WindowOpenDisposition::NEW_BACKGROUND_TAB {
+        PopupRequest::Tab { background: true }
+    } else if disposition == WindowOpenDisposition::NEW_FOREGROUND_TAB
+        || disposition == WindowOpenDisposition::NEW_WINDOW
+    {
+        PopupRequest::Tab { background: false }
+    } else {
+        PopupRequest::Window
+    }
+}
+
+/// The URL of `frame` when it is a main frame showing a web page; sub-frame loads and
+/// internal pages are not reported.
+fn main_frame_url(frame: Option<&mut Frame>) -> Option<String> {
+    let frame = frame.filter(|frame| frame.is_main() != 0)?;
+    let url = CefString::from(&frame.url()).to_string();
+    (!error_page::is_internal_url(&url)).then_some(url)
+}
+
+/// Chromium's net error number, e.g. -105 for `ERR_NAME_NOT_RESOLVED`.
+fn net_error_code(error_code: Errorcode) -> i32 {
+    sys::cef_errorcode_t::from(error_code) as i32
+}
@@ -0,0 +1,304 @@
+use anyhow::{Context, Result, anyhow};
+use pulse_state::PROFILE_LOCK_FILE_NAME;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const INSTANCE_FILE_NAME: &str = "pulse.instance.json";
+const INSTANCE_TEMP_FILE_NAME: &str = "pulse.instance.tmp.json";
+const LAUNCH_ACK: &str = "ok";
+const MAX_LAUNCH_MESSAGE_BYTES: u64 = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// The running instance may still be starting CEF when a second launch arrives.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+/// A forwarding launch writes its message as soon as it connects; a connection that stays
+/// silent longer is dropped.
+const LAUNCH_READ_TIMEOUT: Duration = Duration::from_secs(2);
+const INSTANCE_FILE_WAIT: Duration = Duration::from_secs(3);
+const INSTANCE_FILE_POLL: Duration = Duration::from_millis(50);
+/// Flags whose value is the following argument, so it is never mistaken for a launch URL.
+const VALUE_FLAGS: &[&str] = &[
+    "--pulse-ui-url",
+    "--ui-url",
+    "--pulse-content-url",
+    "--profile",
+];
+const LAUNCH_URL_SCHEMES: &[&str] = &["http", "https", "file"];
+
+/// Where the primary instance listens for forwarded launches. Lives next to the lock file
+/// because Windows blocks reads of byte ranges another process has locked.
+#[derive(Debug, Serialize, Deserialize)]
+struct InstanceEndpoint {
+    pid: u32,
+    port: u16,
+    token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LaunchMessage {
+    token: String,
+    urls: Vec<String>,
+}
+
+pub enum InstanceRole {
+    /// This process owns the profile root until the `PrimaryInstance` is dropped.
+    Primary(PrimaryInstance),
+    /// Another process owns the profile root; forward the launch to it and exit.
+    Secondary(RunningInstance),
+}
+
+pub struct PrimaryInstance {
+    // Holding the handle holds the OS lock; the OS releases it if the process dies.
+    _lock_file: File,
+    listener: TcpListener,
+    token: String,
+    instance_file: PathBuf,
+}
+
+pub struct RunningInstance {
+    root: PathBuf,
+}
+
+/// Takes the exclusive OS-level lock on `<root>/pulse.lock`, or reports who holds it.
+pub fn acquire(root: &Path) -> Result<InstanceRole> {
+    fs::create_dir_all(root)
+        .with_context(|| format!("Failed to create profile root '{}'", root.display()))?;
+
+    let lock_path = root.join(PROFILE_LOCK_FILE_NAME);
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file '{}'", lock_path.display()))?;
+
+    match lock_file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Ok(InstanceRole::Secondary(RunningInstance {
+                root: root.to_path_buf(),
+            }));
+        }
+        Err(TryLockError::Error(err)) => {
+            return Err(err).with_context(|| format!("Failed to lock '{}'", lock_path.display()));
+        }
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .context("Failed to bind the single-instance launch socket")?;
+    let endpoint = InstanceEndpoint {
+        pid: std::process::id(),
+        port: listener.local_addr()?.port(),
+        token: random_token()?,
+    };
+    let instance_file = root.join(INSTANCE_FILE_NAME);
+    write_endpoint(root, &instance_file, &endpoint)?;
+
+    Ok(InstanceRole::Primary(PrimaryInstance {
+        _lock_file: lock_file,
+        listener,
+        token: endpoint.token,
+        instance_file,
+    }))
+}
+
+impl PrimaryInstance {
+    /// Serves forwarded launches on a background thread. `on_launch` gets the URLs of each
+    /// authenticated launch; connections made before this call wait in the socket backlog.
+    /// Each connection is read on its own thread, so one that never sends does not hold up
+    /// the others.
+    pub fn listen(&self, on_launch: impl Fn(Vec<String>) + Send + Sync + 'static) -> Result<()> {
+        let listener = self
+            .listener
+            .try_clone()
+            .context("Failed to clone the single-instance launch socket")?;
+        let token = Arc::new(self.token.clone());
+        let on_launch = Arc::new(on_launch);
+
+        thread::Builder::new()
+            .name(String::from("pulse-single-instance"))
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            eprintln!("Pulse single-instance warning: accept failed: {err}");
+                            continue;
+                        }
+                    };
+                    let token = token.clone();
+                    let on_launch = on_launch.clone();
+                    let spawned = thread::Builder::new()
+                        .name(String::from("pulse-launch"))
+                        .spawn(move || match read_launch(stream, &token) {
+                            Ok(urls) => on_launch(urls),
+                            Err(err) => {
+                                eprintln!(
+                                    "Pulse single-instance warning: rejected forwarded launch: {err:#}"
+                                );
+                            }
+                        });
+                    if let Err(err) = spawned {
+                        eprintln!(
+                            "Pulse single-instance warning: failed to serve a forwarded launch: {err}"
+                        );
+                    }
+                }
+            })
+            .context("Failed to spawn the single-instance listener thread")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for PrimaryInstance {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.instance_file);
+    }
+}
+
+impl RunningInstance {
+    /// Hands `urls` to the primary instance and waits for it to acknowledge them.
+    pub fn forward(&self, urls: &[String]) -> Result<()> {
+        let endpoint = self.wait_for_endpoint()?;
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, endpoint.port));
+        let mut stream =
+            TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).with_context(|| {
+                format!(
+                    "Failed to reach the running Pulse instance (pid {}) on port {}",
+                    endpoint.pid, endpoint.port
+                )
+            })?;
+        stream.set_read_timeout(Some(FORWARD_TIMEOUT))?;
+        stream.set_write_timeout(Some(FORWARD_TIMEOUT))?;
+
+        let message = LaunchMessage {
+            token: endpoint.token,
+            urls: urls.to_vec(),
+        };
+        let mut line = serde_json::to_string(&message)?;
+        line.push('\n');
+        stream
+            .write_all(line.as_bytes())
+            .context("Failed to send the launch to the running Pulse instance")?;
+
+        let mut ack = String::new();
+        BufReader::new(stream)
+            .read_line(&mut ack)
+            .context("The running Pulse instance did not acknowledge the launch")?;
+        if ack.trim() != LAUNCH_ACK {
+            return Err(anyhow!(
+                "The running Pulse instance rejected the launch: '{}'",
+                ack.trim()
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn wait_for_endpoint(&self) -> Result<InstanceEndpoint> {
+        // The primary writes the endpoint right after taking the lock; give it a moment.
+        let instance_file = self.root.join(INSTANCE_FILE_NAME);
+        let deadline = Instant::now() + INSTANCE_FILE_WAIT;
+        loop {
+            let read = fs::read_to_string(&instance_file)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| Ok(serde_json::from_str::<InstanceEndpoint>(&text)?));
+            match read {
+                Ok(endpoint) => return Ok(endpoint),
+                Err(err) if Instant::now() >= deadline => {
+                    return Err(err).with_context(|| {
+                        format!(
+                            "Profile root '{}' is locked by another Pulse instance, but its endpoint file '{}' is unreadable",
+                            self.root.display(),
+                            instance_file.display()
+                        )
+                    });
+                }
+                Err(_) => thread::sleep(INSTANCE_FILE_POLL),
+            }
+        }
+    }
+}
+
+/// URLs to open from a launch command line: positional `http`, `https` and `file` URLs.
+/// Flags (and the values of `VALUE_FLAGS`) and anything else are ignored.
+pub fn launch_urls(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut skip_value = false;
+    for arg in args.into_iter().skip(1) {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if arg.starts_with('-') {
+            skip_value = VALUE_FLAGS.contains(&arg.as_str());
+            continue;
+        }
+
+        let Ok(url) = url::Url::parse(arg.trim()) else {
+            continue;
+        };
+        if LAUNCH_URL_SCHEMES.contains(&url.scheme()) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+fn read_launch(stream: TcpStream, token: &str) -> Result<Vec<String>> {
+    stream.set_read_timeout(Some(LAUNCH_READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(LAUNCH_READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+
+    let mut line = String::new();
+    BufReader::new(stream.take(MAX_LAUNCH_MESSAGE_BYTES))
+        .read_line(&mut line)
+        .context("Failed to read forwarded launch")?;
+    let message: LaunchMessage =
+        serde_json::from_str(line.trim()).context("Forwarded launch is not valid JSON")?;
+    if !tokens_match(&message.token, token) {
+        let _ = writer.write_all(b"unauthorized\n");
+        return Err(anyhow!("Forwarded launch carried the wrong token"));
+    }
+
+    writer.write_all(format!("{LAUNCH_ACK}\n").as_bytes())?;
+    Ok(message.urls)
+}
+
+/// Compares every byte instead of stopping at the first difference, so response times do
+/// not reveal how much of a guessed token was right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    let difference = given
+        .iter()
+        .zip(expected)
+        .fold(0u8, |difference, (given, expected)| {
+            difference | (given ^ expected)
+        });
+    given.len() == expected.len() && std::hint::black_box(difference) == 0
+}
+
+fn write_endpoint(root: &Path, instance_file: &Path, endpoint: &InstanceEndpoint) -> Result<()> {
+    let temp = root.join(INSTANCE_TEMP_FILE_NAME);
+    fs::write(&temp, serde_json::to_vec(endpoint)?)
+        .with_context(|| format!("Failed to write '{}'", temp.display()))?;
+    fs::rename(&temp, instance_file)
+        .with_context(|| format!("Failed to write '{}'", instance_file.display()))?;
+    Ok(())
+}
+
+/// 128 bits from the OS random number generator, hex-encoded.
+fn random_token() -> Result<String> {
+    let mut token = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut token)
+        .map_err(|_| anyhow!("Failed to generate a single-instance token"))?;
+    Ok(token.iter().map(|byte| format!("{byte:02x}")).collect())
+}
@@ -1,5 +1,13 @@
+use super::error_page;
 use cef::*;
+use pulse_core::history::MAX_TAB_HISTORY_ENTRIES;
+use pulse_core::protocol::{NavState, TabLifecycle};
+use pulse_core::tab_lifecycle::{
+    DiscardPolicy, LiveTab, SNAPSHOT_REPLY_TIMEOUT, ScrollPosition, TabSnapshot,
+};
+use pulse_core::{HistoryEntry, TabHistory};
 use std::collections::BTreeMap;
+use std::time::Instant;
 
 #[derive(Debug, Clone, Copy)]
 pub struct ContentBounds {
@@ -21,10 +29,87 @@ impl ContentBounds {
 }
 
 struct ContentTabSession {
+    /// `None` while the tab is discarded.
+    live: Option<LiveContent>,
+    /// As CEF reported it; `tab_nav_state` adds the restored history.
+    nav_state: NavState,
+    /// Back/forward list as last captured, including `restored_history`.
+    history: TabHistory,
+    /// The list the tab's browser was created from; empty for a tab created fresh.
+    restored_history: TabHistory,
+    /// Icon URL the page declared last; a download for any other URL is stale.
+    favicon_url: Option<String>,
+    /// What a discarded tab is recreated from; `url` is refreshed when the tab is discarded.
+    url: String,
+    title: Option<String>,
+    scroll: ScrollPosition,
+    /// Scroll position to restore once a recreated tab's page has loaded.
+    pending_scroll: Option<ScrollPosition>,
+    pinned: bool,
+    audible: bool,
+    /// When the tab was last the active one.
+    last_active: Instant,
+    /// When a sweep first asked the tab to reply before being discarded; cleared by the reply.
+    snapshot_requested_at: Option<Instant>,
+    /// The tab's renderer terminated, so it cannot reply to a snapshot request.
+    crashed: bool,
+}
+
+struct LiveContent {
     browser_view: BrowserView,
     overlay_controller: Option<OverlayController>,
 }
 
+/// What `browser-back` or `browser-forward` left for the caller.
+pub enum HistoryStep {
+    /// The tab's browser went back or forward, or there was nowhere to go.
+    Done,
+    /// The target entry is older than the tab's browser, which was detached for the caller to
+    /// close; the tab is to be recreated at `url`.
+    Recreate {
+        browser: Option<Browser>,
+        url: String,
+    },
+}
+
+impl ContentTabSession {
+    fn new(live: Option<LiveContent>, url: String, pinned: bool) -> Self {
+        Self {
+            live,
+            nav_state: NavState::default(),
+            history: TabHistory::default(),
+            restored_history: TabHistory::default(),
+            favicon_url: None,
+            url,
+            title: None,
+            scroll: ScrollPosition::default(),
+            pending_scroll: None,
+            pinned,
+            audible: false,
+            last_active: Instant::now(),
+            snapshot_requested_at: None,
+            crashed: false,
+        }
+    }
+
+    fn browser(&self) -> Option<Browser> {
+        self.live.as_ref()?.browser_view.browser()
+    }
+
+    /// Takes the browser away from the tab and returns it for the caller to close.
+    fn detach(&mut self) -> Option<Browser> {
+        let live = self.live.take()?;
+        self.nav_state = NavState::default();
+        self.audible = false;
+        self.snapshot_requested_at = None;
+        self.crashed = false;
+        if let Some(overlay_controller) = live.overlay_controller {
+            overlay_controller.destroy();
+        }
+        live.browser_view.browser()
+    }
+}
+
 pub struct ShellState {
     pub ui_browser_id: Option<i32>,
     pub ui_browser_view: Option<BrowserView>,
@@ -34,6 +119,15 @@ pub struct ShellState {
     pub window_bounds: Rect,
     pub requested_content_bounds: Option<ContentBounds>,
     pub content_visible: bool,
+    /// Launch URLs held until the UI reports it can receive `open-urls` events.
+    pending_launch_urls: Vec<String>,
+    ui_ready: bool,
+    /// `seq` of the last host event sent to the UI.
+    host_event_seq: u64,
+    pub discard_policy: DiscardPolicy,
+    /// Error page URLs the host loaded, per content browser. Only these are served and
+    /// reported as the URL that failed; a page can navigate to any `pulse://error/` URL.
+    error_pages: BTreeMap<i32, Vec<String>>,
 }
 
 impl Default for ShellState {
@@ -52,13 +146,70 @@ impl Default for ShellState {
             },
             requested_content_bounds: None,
             content_visible: true,
+            pending_launch_urls: Vec::new(),
+            ui_ready: false,
+            host_event_seq: 0,
+            discard_policy: DiscardPolicy::default(),
+            error_pages: BTreeMap::new(),
         }
     }
 }
 
 impl ShellState {
+    /// Remembers that the host is loading `url` as `browser_id`'s error page.
+    pub fn record_error_page(&mut self, browser_id: i32, url: String) {
+        let error_pages = self.error_pages.entry(browser_id).or_default();
+        error_pages.retain(|recorded| *recorded != url);
+        error_pages.push(url);
+        let excess = error_pages.len().saturating_sub(MAX_TAB_HISTORY_ENTRIES);
+        error_pages.drain(..excess);
+    }
+
+    pub fn is_host_error_page(&self, browser_id: i32, url: &str) -> bool {
+        self.error_pages
+            .get(&browser_id)
+            .is_some_and(|error_pages| error_pages.iter().any(|recorded| recorded == url))
+    }
+
+    pub fn forget_error_pages(&mut self, browser_id: i32) {
+        self.error_pages.remove(&browser_id);
+    }
+
+    /// The URL `browser_id` should report for `url`: the failed URL on an error page the
+    /// host loaded, else `url`.
+    pub fn displayed_url(&self, browser_id: i32, url: String) -> String {
+        if !self.is_host_error_page(browser_id, &url) {
+            return url;
+        }
+        error_page::parse_error_page_url(&url).map_or(url, |failure| failure.failed_url)
+    }
+
     pub fn set_ui_browser_id(&mut self, browser_id: Option<i32>) {
         self.ui_browser_id = browser_id;
+        if browser_id.is_none() {
+            self.ui_ready = false;
+        }
+    }
+
+    pub fn set_ui_ready(&mut self) {
+        self.ui_ready = true;
+    }
+
+    pub fn queue_launch_urls(&mut self, urls: Vec<String>) {
+        self.pending_launch_urls.extend(urls);
+    }
+
+    /// Drains queued launch URLs once the UI is ready; before that they stay queued.
+    pub fn take_launch_urls(&mut self) -> Vec<String> {
+        if !self.ui_ready {
+            return Vec::new();
+        }
+        std::mem::take(&mut self.pending_launch_urls)
+    }
+
+    pub fn next_host_event_seq(&mut self) -> u64 {
+        self.host_event_seq += 1;
+        self.host_event_seq
     }
 
     pub fn set_ui_view(&mut self, ui_browser_view: Option<BrowserView>) {
@@ -70,8 +221,12 @@ impl ShellState {
         self.window = window;
 
         if let Some(window) = self.window.as_mut() {
-            for tab_session in self.content_tabs.values_mut() {
-                ensure_overlay_attached(window, tab_session);
+            for live in self
+                .content_tabs
+                .values_mut()
+                .filter_map(|tab| tab.live.as_mut())
+            {
+                ensure_overlay_attached(window, live);
             }
         }
 
@@ -97,9 +252,10 @@ impl ShellState {
         self.content_tabs.contains_key(tab_id)
     }
 
+    /// Discarded tabs have no browser, so they never match.
     pub fn tab_id_for_browser_id(&self, browser_id: i32) -> Option<String> {
         for (tab_id, tab_session) in &self.content_tabs {
-            if let Some(browser) = tab_session.browser_view.browser() {
+            if let Some(browser) = tab_session.browser() {
                 if browser.identifier() == browser_id {
                     return Some(tab_id.clone());
                 }
@@ -109,24 +265,250 @@ impl ShellState {
         None
     }
 
+    /// The page's URL, or the URL a discarded tab will be recreated with.
     pub fn tab_runtime_url(&self, tab_id: &str) -> Option<String> {
         let tab_session = self.content_tabs.get(tab_id)?;
-        let browser = tab_session.browser_view.browser()?;
+        let Some(browser) = tab_session.browser() else {
+            return Some(tab_session.url.clone());
+        };
         let main_frame = browser.main_frame()?;
-        Some(CefString::from(&main_frame.url()).to_string())
+        Some(self.displayed_url(
+            browser.identifier(),
+            CefString::from(&main_frame.url()).to_string(),
+        ))
+    }
+
+    /// The page's last title, which a discarded tab keeps.
+    pub fn tab_title(&self, tab_id: &str) -> Option<String> {
+        self.content_tabs.get(tab_id)?.title.clone()
+    }
+
+    pub fn set_tab_title(&mut self, tab_id: &str, title: Option<String>) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            tab_session.title = title;
+        }
+    }
+
+    /// Returns false when there is no such tab.
+    pub fn set_tab_pinned(&mut self, tab_id: &str, pinned: bool) -> bool {
+        let Some(tab_session) = self.content_tabs.get_mut(tab_id) else {
+            return false;
+        };
+        tab_session.pinned = pinned;
+        true
+    }
+
+    pub fn tab_lifecycle(&self, tab_id: &str) -> Option<TabLifecycle> {
+        let tab_session = self.content_tabs.get(tab_id)?;
+        Some(if tab_session.live.is_none() {
+            TabLifecycle::Discarded
+        } else if self.active_tab_id.as_deref() == Some(tab_id) {
+            TabLifecycle::Active
+        } else {
+            TabLifecycle::Background
+        })
+    }
+
+    pub fn is_tab_discarded(&self, tab_id: &str) -> bool {
+        self.tab_lifecycle(tab_id) == Some(TabLifecycle::Discarded)
+    }
+
+    pub fn live_tab_count(&self) -> usize {
+        self.content_tabs
+            .values()
+            .filter(|tab_session| tab_session.live.is_some())
+            .count()
+    }
+
+    /// Tabs with a browser, for `tab_lifecycle::discard_candidates`.
+    pub fn live_tabs(&self, now: Instant) -> Vec<LiveTab> {
+        self.content_tabs
+            .iter()
+            .filter(|(_, tab_session)| tab_session.live.is_some())
+            .map(|(tab_id, tab_session)| {
+                let active = self.active_tab_id.as_deref() == Some(tab_id.as_str());
+                LiveTab {
+                    tab_id: tab_id.clone(),
+                    active,
+                    pinned: tab_session.pinned,
+                    audible: tab_session.audible,
+                    idle_for: if active {
+                        Default::default()
+                    } else {
+                        now.saturating_duration_since(tab_session.last_active)
+                    },
+                }
+            })
+            .collect()
     }
 
-    pub fn register_content_tab(&mut self, tab_id: String, browser_view: BrowserView) {
-        let mut tab_session = ContentTabSession {
+    /// Main frames of the live tabs that are not active.
+    pub fn background_tab_frames(&self) -> Vec<(String, Frame)> {
+        self.content_tabs
+            .iter()
+            .filter(|(tab_id, _)| self.active_tab_id.as_deref() != Some(tab_id.as_str()))
+            .filter_map(|(tab_id, tab_session)| {
+                Some((tab_id.clone(), tab_session.browser()?.main_frame()?))
+            })
+            .collect()
+    }
+
+    pub fn set_tab_snapshot(&mut self, tab_id: &str, snapshot: TabSnapshot) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            tab_session.scroll = snapshot.scroll;
+            tab_session.audible = snapshot.audible;
+            tab_session.snapshot_requested_at = None;
+            tab_session.crashed = false;
+        }
+    }
+
+    /// Notes that the sweep asked `candidates` to reply before being discarded, and
+    /// forgets the requests of tabs no longer picked. Returns the candidates that cannot
+    /// be waited for: their renderer crashed, or their request is older than
+    /// `SNAPSHOT_REPLY_TIMEOUT`.
+    pub fn request_tab_snapshots(&mut self, candidates: &[String], now: Instant) -> Vec<String> {
+        let mut unresponsive = Vec::new();
+        for (tab_id, tab_session) in &mut self.content_tabs {
+            if !candidates.contains(tab_id) {
+                tab_session.snapshot_requested_at = None;
+                continue;
+            }
+            let requested_at = *tab_session.snapshot_requested_at.get_or_insert(now);
+            if tab_session.crashed
+                || now.saturating_duration_since(requested_at) >= SNAPSHOT_REPLY_TIMEOUT
+            {
+                unresponsive.push(tab_id.clone());
+            }
+        }
+        unresponsive
+    }
+
+    /// Whether the tab's renderer terminated; a page load in a new renderer clears it.
+    pub fn set_tab_crashed(&mut self, tab_id: &str, crashed: bool) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            tab_session.crashed = crashed;
+        }
+    }
+
+    /// Detaches a background tab's browser and returns it for the caller to close. The
+    /// tab keeps its URL, title and scroll position. `None` for the active tab, unknown
+    /// tabs and tabs already discarded.
+    pub fn discard_tab(&mut self, tab_id: &str) -> Option<Browser> {
+        if self.active_tab_id.as_deref() == Some(tab_id) {
+            return None;
+        }
+        let url = self.tab_runtime_url(tab_id);
+        let tab_session = self.content_tabs.get_mut(tab_id)?;
+        tab_session.live.as_ref()?;
+        if let Some(url) = url {
+            tab_session.url = url;
+        }
+        let browser = tab_session.detach();
+
+        self.apply_layout();
+        browser
+    }
+
+    /// Gives a discarded tab its new browser, created at its history's current entry. Returns
+    /// false when the tab is unknown or live.
+    pub fn attach_browser(&mut self, tab_id: &str, browser_view: BrowserView) -> bool {
+        let Some(tab_session) = self.content_tabs.get_mut(tab_id) else {
+            return false;
+        };
+        if tab_session.live.is_some() {
+            return false;
+        }
+        let mut live = LiveContent {
             browser_view,
             overlay_controller: None,
         };
+        if let Some(window) = self.window.as_mut() {
+            ensure_overlay_attached(window, &mut live);
+        }
+        tab_session.live = Some(live);
+        tab_session.restored_history = tab_session.history.clone();
+        tab_session.pending_scroll =
+            Some(tab_session.scroll).filter(|scroll| *scroll != ScrollPosition::default());
+
+        self.apply_layout();
+        true
+    }
+
+    /// Makes `url` the current history entry of a discarded tab about to be recreated there,
+    /// dropping the forward entries.
+    pub fn push_history_entry(&mut self, tab_id: &str, url: &str) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            if tab_session.history.current().is_some() {
+                tab_session.history = tab_session.history.pushed(HistoryEntry {
+                    url: url.to_string(),
+                    title: None,
+                });
+            }
+        }
+    }
+
+    /// Lays the back/forward list the tab's browser reported over the restored one. Returns
+    /// the tab's whole list when it changed.
+    pub fn set_live_history(&mut self, tab_id: &str, live: TabHistory) -> Option<TabHistory> {
+        let tab_session = self.content_tabs.get_mut(tab_id)?;
+        let history = TabHistory::rebased(&tab_session.restored_history, &live).normalized()?;
+        if history == tab_session.history {
+            return None;
+        }
+        tab_session.history = history.clone();
+        Some(history)
+    }
+
+    pub fn take_pending_scroll(&mut self, tab_id: &str) -> Option<ScrollPosition> {
+        self.content_tabs.get_mut(tab_id)?.pending_scroll.take()
+    }
+
+    /// The tab's latest `on_loading_state_change`, or the default before the first one. Back
+    /// and forward are also possible into entries from before the tab was recreated.
+    pub fn tab_nav_state(&self, tab_id: &str) -> Option<NavState> {
+        let tab_session = self.content_tabs.get(tab_id)?;
+        let history = &tab_session.history;
+        let mut nav = tab_session.nav_state;
+        nav.can_go_back |= !history.back().is_empty();
+        nav.can_go_forward |= !history.forward().is_empty();
+        Some(nav)
+    }
+
+    pub fn set_tab_nav_state(&mut self, tab_id: &str, nav_state: NavState) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            tab_session.nav_state = nav_state;
+        }
+    }
+
+    pub fn tab_favicon_url(&self, tab_id: &str) -> Option<String> {
+        self.content_tabs.get(tab_id)?.favicon_url.clone()
+    }
+
+    pub fn set_tab_favicon_url(&mut self, tab_id: &str, favicon_url: Option<String>) {
+        if let Some(tab_session) = self.content_tabs.get_mut(tab_id) {
+            tab_session.favicon_url = favicon_url;
+        }
+    }
 
+    pub fn register_content_tab(
+        &mut self,
+        tab_id: String,
+        browser_view: BrowserView,
+        url: String,
+        pinned: bool,
+    ) {
+        let mut live = LiveContent {
+            browser_view,
+            overlay_controller: None,
+        };
         if let Some(window) = self.window.as_mut() {
-            ensure_overlay_attached(window, &mut tab_session);
+            ensure_overlay_attached(window, &mut live);
         }
 
-        self.content_tabs.insert(tab_id.clone(), tab_session);
+        self.content_tabs.insert(
+            tab_id.clone(),
+            ContentTabSession::new(Some(live), url, pinned),
+        );
         if self.active_tab_id.is_none() {
             self.active_tab_id = Some(tab_id);
         }
@@ -134,24 +516,56 @@ impl ShellState {
         self.apply_layout();
     }
 
-    pub fn activate_tab(&mut self, tab_id: &str) {
-        if self.content_tabs.contains_key(tab_id) {
-            self.active_tab_id = Some(tab_id.to_string());
-            self.apply_layout();
+    /// Registers a restored tab without a browser, as if it had been discarded. A history
+    /// whose current entry is not `url` is dropped. Returns false, changing nothing, when the
+    /// tab already exists.
+    pub fn register_restored_tab(
+        &mut self,
+        tab_id: String,
+        url: String,
+        title: Option<String>,
+        pinned: bool,
+        history: Option<TabHistory>,
+    ) -> bool {
+        if self.content_tabs.contains_key(&tab_id) {
+            return false;
         }
+        let mut tab_session = ContentTabSession::new(None, url, pinned);
+        tab_session.title = title;
+        tab_session.history = history
+            .and_then(TabHistory::normalized)
+            .filter(|history| {
+                history.current().map(|entry| entry.url.as_str()) == Some(tab_session.url.as_str())
+            })
+            .unwrap_or_default();
+        self.content_tabs.insert(tab_id, tab_session);
+        true
     }
 
-    pub fn navigate_active_tab(&mut self, url: &str) {
-        let Some(active_tab_id) = self.active_tab_id.clone() else {
-            return;
-        };
-
-        self.navigate_tab(&active_tab_id, url);
+    /// Returns false when there is no such tab. A discarded tab becomes active without a
+    /// browser; the caller recreates it with `attach_browser`.
+    pub fn activate_tab(&mut self, tab_id: &str) -> bool {
+        if !self.content_tabs.contains_key(tab_id) {
+            return false;
+        }
+        let now = Instant::now();
+        // The tab that goes to the background starts idling now.
+        for active_id in [self.active_tab_id.as_deref(), Some(tab_id)]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(tab_session) = self.content_tabs.get_mut(active_id) {
+                tab_session.last_active = now;
+            }
+        }
+        self.active_tab_id = Some(tab_id.to_string());
+        self.apply_layout();
+        true
     }
 
     pub fn navigate_tab(&mut self, tab_id: &str, url: &str) {
         if let Some(tab_session) = self.content_tabs.get(tab_id) {
-            if let Some(browser) = tab_session.browser_view.browser() {
+            if let Some(browser) = tab_session.browser() {
                 if let Some(frame) = browser.main_frame() {
                     frame.load_url(Some(&CefString::from(url)));
                 }
@@ -162,39 +576,66 @@ impl ShellState {
         self.apply_layout();
     }
 
-    pub fn browser_back(&self, tab_id: &str) {
-        if let Some(browser) = self.browser_for_tab(tab_id) {
-            if browser.can_go_back() != 0 {
-                browser.go_back();
-            }
+    /// Goes back (`offset` -1) or forward (1). The tab's browser handles its own entries; an
+    /// entry from before it was created needs a new browser, which the caller creates.
+    /// The page is loaded afresh at each such step: its form input, script state and the
+    /// scroll position of the entry left are lost. `None` when there is no such tab; a
+    /// discarded tab does nothing.
+    pub fn step_history(&mut self, tab_id: &str, offset: isize) -> Option<HistoryStep> {
+        let tab_session = self.content_tabs.get_mut(tab_id)?;
+        let Some(browser) = tab_session.browser() else {
+            return Some(HistoryStep::Done);
+        };
+        if offset < 0 && browser.can_go_back() != 0 {
+            browser.go_back();
+            return Some(HistoryStep::Done);
         }
+        if offset > 0 && browser.can_go_forward() != 0 {
+            browser.go_forward();
+            return Some(HistoryStep::Done);
+        }
+        let Some(target) = tab_session.history.stepped(offset) else {
+            return Some(HistoryStep::Done);
+        };
+        let Some(url) = target.current().map(|entry| entry.url.clone()) else {
+            return Some(HistoryStep::Done);
+        };
+
+        tab_session.history = target;
+        tab_session.url = url.clone();
+        tab_session.scroll = ScrollPosition::default();
+        let browser = tab_session.detach();
+        self.apply_layout();
+        Some(HistoryStep::Recreate { browser, url })
     }
 
-    pub fn browser_forward(&self, tab_id: &str) {
-        if let Some(browser) = self.browser_for_tab(tab_id) {
-            if browser.can_go_forward() != 0 {
-                browser.go_forward();
-            }
-        }
+    /// `browser_reload` and `browser_stop` return false when there is no such tab, and do
+    /// nothing for a discarded one.
+    pub fn browser_reload(&self, tab_id: &str) -> bool {
+        self.with_tab_browser(tab_id, |browser| browser.reload())
     }
 
-    pub fn browser_reload(&self, tab_id: &str) {
-        if let Some(browser) = self.browser_for_tab(tab_id) {
-            browser.reload();
-        }
+    pub fn browser_stop(&self, tab_id: &str) -> bool {
+        self.with_tab_browser(tab_id, |browser| browser.stop_load())
     }
 
-    pub fn browser_stop(&self, tab_id: &str) {
-        if let Some(browser) = self.browser_for_tab(tab_id) {
-            browser.stop_load();
+    /// Runs `command` on the tab's browser, if it has one. Returns false for unknown tabs.
+    fn with_tab_browser(&self, tab_id: &str, command: impl FnOnce(&Browser)) -> bool {
+        let Some(tab_session) = self.content_tabs.get(tab_id) else {
+            return false;
+        };
+        if let Some(browser) = tab_session.browser() {
+            command(&browser);
         }
+        true
     }
 
-    pub fn remove_content_tab_by_browser_id(&mut self, browser_id: i32) {
+    /// Returns the id of the removed tab, if the browser still belonged to one.
+    pub fn remove_content_tab_by_browser_id(&mut self, browser_id: i32) -> Option<String> {
         let mut removed_tab: Option<String> = None;
 
         for (tab_id, tab_session) in &self.content_tabs {
-            if let Some(browser) = tab_session.browser_view.browser() {
+            if let Some(browser) = tab_session.browser() {
                 if browser.identifier() == browser_id {
                     removed_tab = Some(tab_id.clone());
                     break;
@@ -202,29 +643,34 @@ impl ShellState {
             }
         }
 
-        if let Some(tab_id) = removed_tab {
-            if let Some(tab_session) = self.content_tabs.remove(&tab_id) {
-                if let Some(overlay_controller) = tab_session.overlay_controller {
-                    overlay_controller.destroy();
-                }
+        let tab_id = removed_tab?;
+        if let Some(live) = self
+            .content_tabs
+            .remove(&tab_id)
+            .and_then(|tab_session| tab_session.live)
+        {
+            if let Some(overlay_controller) = live.overlay_controller {
+                overlay_controller.destroy();
             }
+        }
 
-            if self.active_tab_id.as_deref() == Some(tab_id.as_str()) {
-                self.active_tab_id = self.content_tabs.keys().next().cloned();
-            }
-            self.apply_layout();
+        if self.active_tab_id.as_deref() == Some(tab_id.as_str()) {
+            self.active_tab_id = self.content_tabs.keys().next().cloned();
         }
+        self.apply_layout();
+        Some(tab_id)
     }
 
+    /// Returns the tab's browser for the caller to close; `None` for a discarded tab.
     pub fn close_tab(&mut self, tab_id: &str) -> Option<Browser> {
         let tab_session = self.content_tabs.remove(tab_id)?;
+        let browser = tab_session.browser();
 
-        if let Some(overlay_controller) = tab_session.overlay_controller {
+        if let Some(overlay_controller) = tab_session.live.and_then(|live| live.overlay_controller)
+        {
             overlay_controller.destroy();
         }
 
-        let browser = tab_session.browser_view.browser();
-
         if self.active_tab_id.as_deref() == Some(tab_id) {
             self.active_tab_id = self.content_tabs.keys().next().cloned();
         }
@@ -234,8 +680,12 @@ impl ShellState {
     }
 
     pub fn clear_content_tabs(&mut self) {
-        for tab_session in self.content_tabs.values() {
-            if let Some(overlay_controller) = tab_session.overlay_controller.as_ref() {
+        for live in self
+            .content_tabs
+            .values()
+            .filter_map(|tab| tab.live.as_ref())
+        {
+            if let Some(overlay_controller) = live.overlay_controller.as_ref() {
                 overlay_controller.destroy();
             }
         }
@@ -267,10 +717,13 @@ impl ShellState {
         let content_bounds = clamp_to_window(content_bounds, &window_bounds);
 
         for (tab_id, tab_session) in &self.content_tabs {
+            let Some(live) = tab_session.live.as_ref() else {
+                continue;
+            };
             let is_active = self.active_tab_id.as_deref() == Some(tab_id.as_str());
             let should_show = self.content_visible && is_active;
 
-            if let Some(overlay_controller) = tab_session.overlay_controller.as_ref() {
+            if let Some(overlay_controller) = live.overlay_controller.as_ref() {
                 if should_show {
                     overlay_controller.set_bounds(Some(&content_bounds));
                     overlay_controller.set_visible(1);
@@ -281,7 +734,7 @@ impl ShellState {
             }
 
             // Fallback path when overlay has not yet been attached.
-            let content_view = View::from(&tab_session.browser_view);
+            let content_view = View::from(&live.browser_view);
             if should_show {
                 content_view.set_bounds(Some(&content_bounds));
                 content_view.set_visible(1);
@@ -292,14 +745,13 @@ impl ShellState {
     }
 }
 
-fn ensure_overlay_attached(window: &mut Window, tab_session: &mut ContentTabSession) {
-    if tab_session.overlay_controller.is_some() {
+fn ensure_overlay_attached(window: &mut Window, live: &mut LiveContent) {
+    if live.overlay_controller.is_some() {
         return;
     }
 
-    let mut view = View::from(&tab_session.browser_view);
-    tab_session.overlay_controller =
-        window.add_overlay_view(Some(&mut view), DockingMode::CUSTOM, 1);
+    let mut view = View::from(&live.browser_view);
+    live.overlay_controller = window.add_overlay_view(Some(&mut view), DockingMode::CUSTOM, 1);
 }
 
 fn fallback_content_bounds(window_bounds: &Rect) -> Rect {
@@ -340,10 +792,3 @@ fn clamp_to_window(rect: Rect, window: &Rect) -> Rect {
         height: rect.height.max(1).min(max_height),
     }
 }
-
-impl ShellState {
-    fn browser_for_tab(&self, tab_id: &str) -> Option<Browser> {
-        let tab_session = self.content_tabs.get(tab_id)?;
-        tab_session.browser_view.browser()
-    }
-}
@@ -15,11 +15,11 @@ import {
   type ShortcutMap,
 } from "@/lib/commands";
 import {
-  PULSE_TAB_RUNTIME_EVENT,
   loadPulseHostState,
-  parsePulseTabRuntimeEventDetail,
   savePulseHostState,
   sendPulseHostCommand,
+  subscribePulseHostEvents,
+  type PulseTabLifecycle,
 } from "@/lib/pulse-host";
 import {
   defaultUiSessionState,
@@ -29,6 +29,7 @@ import {
 } from "@/lib/session-state";
 import {
   type BrowserTabItem,
+  type TabLoadFailure,
   type WorkspaceItem,
   addBrowserTab,
   addGroup,
@@ -36,7 +37,10 @@ import {
   childrenOf,
   moveItemByDrop,
   normalizeUrl,
+  promoteChildren,
   removeWorkspaceItem,
+  setTabHistory,
+  setTabPinned,
   toggleGroupCollapsed,
   updateTabUrl,
 } from "@/lib/workspace";
@@ -47,6 +51,21 @@ const THEME_STORAGE_KEY = "pulse.ui.theme";
 const SHORTCUTS_STORAGE_KEY = "pulse.ui.shortcuts.v1";
 const SAVE_DEBOUNCE_MS = 250;
 
+interface TabNavState {
+  isLoading: boolean;
+  canGoBack: boolean;
+  canGoForward: boolean;
+}
+
+function withoutKey<T>(record: Record<string, T>, key: string): Record<string, T> {
+  if (!(key in record)) {
+    return record;
+  }
+  const next = { ...record };
+  delete next[key];
+  return next;
+}
+
 const GRANTED_CAPABILITIES = new Set([
   "workspace.mutate",
   "workspace.navigate",
@@ -78,8 +97,8 @@ function writeLocalSessionState(serializedState: string) {
   }
 }
 
-function loadInitialSessionState(): UiSessionState {
-  const hostSerialized = loadPulseHostState();
+async function loadInitialSessionState(): Promise<UiSessionState> {
+  const hostSerialized = await loadPulseHostState();
   if (hostSerialized) {
     const parsed = parseUiSessionState(hostSerialized);
     if (parsed) {
@@ -153,13 +172,13 @@ function loadShortcutMap() {
 
 function reportContentBounds(contentSlot: HTMLDivElement) {
   const rect = contentSlot.getBoundingClientRect();
-  sendPulseHostCommand(
-    "set-content-bounds",
-    Math.round(rect.left),
-    Math.round(rect.top),
-    Math.max(1, Math.round(rect.width)),
-    Math.max(1, Math.round(rect.height)),
-  );
+  void sendPulseHostCommand({
+    type: "set-content-bounds",
+    x: Math.round(rect.left),
+    y: Math.round(rect.top),
+    width: Math.max(1, Math.round(rect.width)),
+    height: Math.max(1, Math.round(rect.height)),
+  });
 }
 
 function resolveNextSelectionAfterTabClose(items: WorkspaceItem[], closedTab: BrowserTabItem) {
@@ -183,14 +202,45 @@ function resolveNextSelectionAfterTabClose(items: WorkspaceItem[], closedTab: Br
   return items[0]?.id ?? "";
 }
 
-const initialSessionState = loadInitialSessionState();
+const initialSessionStatePromise = loadInitialSessionState();
 
 function App() {
+  const [initialSessionState, setInitialSessionState] = useState<UiSessionState | null>(null);
+
+  useEffect(() => {
+    let cancelled = false;
+    void initialSessionStatePromise.then((sessionState) => {
+      if (!cancelled) {
+        setInitialSessionState(sessionState);
+      }
+    });
+
+    return () => {
+      cancelled = true;
+    };
+  }, []);
+
+  if (!initialSessionState) {
+    return null;
+  }
+
+  return <PulseWorkspace initialSessionState={initialSessionState} />;
+}
+
+function PulseWorkspace({ initialSessionState }: { initialSessionState: UiSessionState }) {
   const [items, setItems] = useState<WorkspaceItem[]>(initialSessionState.items);
   const [selectedItemId, setSelectedItemId] = useState<string>(initialSessionState.selectedItemId);
   const [address, setAddress] = useState<string>(initialSessionState.address);
   const [isDarkMode, setIsDarkMode] = useState<boolean>(loadInitialDarkMode);
   const [settingsOpen, setSettingsOpen] = useState(false);
+  // Per tab, from the host's `loading-state-changed` events.
+  const [navStates, setNavStates] = useState<Record<string, TabNavState>>({});
+  // Tabs whose last navigation failed, until their next load starts.
+  const [tabFailures, setTabFailures] = useState<Record<string, TabLoadFailure>>({});
+  // Cached favicon per tab, from `favicon-changed`; `null` when the page declares none.
+  const [tabIcons, setTabIcons] = useState<Record<string, string | null>>({});
+  // From `tab-lifecycle-changed`; tabs the host never reported are treated as loaded.
+  const [tabLifecycles, setTabLifecycles] = useState<Record<string, PulseTabLifecycle>>({});
   const [shortcutMap] = useState<ShortcutMap>(loadShortcutMap);
 
   const contentViewportRef = useRef<HTMLDivElement | null>(null);
@@ -203,24 +253,56 @@ function App() {
   );
   const selectedTab = selectedItem?.kind === "browser-tab" ? selectedItem : null;
   const selectedTabId = selectedTab?.id ?? null;
+  const selectedNavState = selectedTabId ? navStates[selectedTabId] : undefined;
   const selectedTabUrl = selectedTab?.url ?? null;
+  const selectedTabPinned = selectedTab?.pinned ?? false;
   const rootGroups = useMemo(() => childrenOf(items, null), [items]);
   const defaultParentId = rootGroups[0]?.id ?? null;
 
+  // Registers the saved tabs with the host once; it loads only the selected tab and a few
+  // pinned ones until the others are activated.
+  useEffect(() => {
+    const restoredTabs = initialSessionState.items.filter(
+      (item): item is BrowserTabItem => item.kind === "browser-tab",
+    );
+    if (restoredTabs.length === 0) {
+      return;
+    }
+
+    const activeTab = restoredTabs.find((tab) => tab.id === initialSessionState.selectedItemId);
+    void sendPulseHostCommand({
+      type: "restore-tabs",
+      tabs: restoredTabs.map((tab) => ({
+        tab_id: tab.id,
+        url: tab.url,
+        title: tab.title,
+        pinned: tab.pinned ?? false,
+        history: tab.history,
+      })),
+      active_tab_id: activeTab?.id,
+      prefetch_tab_ids: restoredTabs.filter((tab) => tab.pinned).map((tab) => tab.id),
+    });
+  }, [initialSessionState]);
+
   useEffect(() => {
     if (selectedTabId && selectedTabUrl) {
-      sendPulseHostCommand("ensure-tab", selectedTabId, selectedTabUrl);
-      sendPulseHostCommand("activate-tab", selectedTabId);
+      void sendPulseHostCommand({
+        type: "ensure-tab",
+        tab_id: selectedTabId,
+        initial_url: selectedTabUrl,
+        pinned: selectedTabPinned,
+      });
+      void sendPulseHostCommand({ type: "activate-tab", tab_id: selectedTabId });
     }
-  }, [selectedTabId, selectedTabUrl]);
+  }, [selectedTabId, selectedTabUrl, selectedTabPinned]);
 
   useEffect(() => {
     if (!selectedTabId) {
-      sendPulseHostCommand("set-content-visible", false);
+      void sendPulseHostCommand({ type: "set-content-visible", visible: false });
       return;
     }
 
-    sendPulseHostCommand("set-content-visible", !settingsOpen);
+    void sendPulseHostCommand({ type: "set-content-visible", visible: !settingsOpen });
   }, [selectedTabId, settingsOpen]);
 
   useEffect(() => {
@@ -254,13 +336,9 @@ function App() {
     });
 
     const timeoutId = window.setTimeout(() => {
-      const savedToHost = savePulseHostState(serialized);
-      if (!savedToHost) {
-        writeLocalSessionState(serialized);
-        return;
-      }
-
+      // The host coalesces bursts of saves; localStorage stays the synchronous fallback.
       writeLocalSessionState(serialized);
+      void savePulseHostState(serialized);
     }, SAVE_DEBOUNCE_MS);
 
     return () => window.clearTimeout(timeoutId);
@@ -285,28 +363,122 @@ function App() {
     }
   }, [shortcutMap]);
 
+  useEffect(
+    () =>
+      subscribePulseHostEvents((event) => {
+        switch (event.type) {
+          case "tab-updated":
+            setItems((prev) => applyTabRuntimeUpdate(prev, event.tab_id, event));
+            if (selectedTabId === event.tab_id && event.url) {
+              setAddress(event.url);
+            }
+            break;
+          case "loading-state-changed":
+            setNavStates((prev) => ({
+              ...prev,
+              [event.tab_id]: {
+                isLoading: event.is_loading,
+                canGoBack: event.can_go_back,
+                canGoForward: event.can_go_forward,
+              },
+            }));
+            break;
+          case "load-started":
+            setTabFailures((prev) => withoutKey(prev, event.tab_id));
+            break;
+          case "load-failed":
+            setTabFailures((prev) => ({
+              ...prev,
+              [event.tab_id]: { errorCode: event.error_code, errorText: event.error_text },
+            }));
+            break;
+          case "favicon-changed":
+            setTabIcons((prev) => ({ ...prev, [event.tab_id]: event.icon_url }));
+            break;
+          case "tab-lifecycle-changed":
+            setTabLifecycles((prev) => ({ ...prev, [event.tab_id]: event.lifecycle }));
+            break;
+          case "tab-history-changed":
+            setItems((prev) => setTabHistory(prev, event.tab_id, event.history));
+            break;
+          case "tab-closed":
+            setNavStates((prev) => withoutKey(prev, event.tab_id));
+            setTabFailures((prev) => withoutKey(prev, event.tab_id));
+            setTabIcons((prev) => withoutKey(prev, event.tab_id));
+            setTabLifecycles((prev) => withoutKey(prev, event.tab_id));
+            break;
+        }
+      }),
+    [selectedTabId],
+  );
+
   useEffect(() => {
-    const listener: EventListener = (event) => {
-      const detail = parsePulseTabRuntimeEventDetail((event as CustomEvent<unknown>).detail);
-      if (!detail) {
+    const unsubscribe = subscribePulseHostEvents((event) => {
+      if (event.type === "tab-open-requested") {
+        setItems((prev) => {
+          const opener = prev.find((item) => item.id === event.opener_tab_id);
+          const result = addBrowserTab(
+            prev,
+            opener ? opener.id : defaultParentId,
+            event.url,
+            event.opener_tab_id,
+          );
+          void sendPulseHostCommand({
+            type: "ensure-tab",
+            tab_id: result.newId,
+            initial_url: normalizeUrl(event.url),
+          });
+          if (!event.background) {
+            setSelectedItemId(result.newId);
+            setAddress(normalizeUrl(event.url));
+            void sendPulseHostCommand({ type: "activate-tab", tab_id: result.newId });
+          }
+          return result.items;
+        });
         return;
       }
-
-      setItems((prev) => applyTabRuntimeUpdate(prev, detail.tabId, detail));
-      if (selectedTabId === detail.tabId && detail.url) {
-        setAddress(detail.url);
+      if (event.type !== "open-urls") {
+        return;
+      }
+      const urls = event.urls.filter((url) => url.trim().length > 0);
+      if (urls.length === 0) {
+        return;
       }
-    };
 
-    window.addEventListener(PULSE_TAB_RUNTIME_EVENT, listener);
-    return () => window.removeEventListener(PULSE_TAB_RUNTIME_EVENT, listener);
-  }, [selectedTabId]);
+      setItems((prev) => {
+        let nextItems = prev;
+        let lastTab: { id: string; url: string } | null = null;
+        for (const url of urls) {
+          const targetUrl = normalizeUrl(url);
+          const result = addBrowserTab(nextItems, defaultParentId, targetUrl);
+          nextItems = result.items;
+          lastTab = { id: result.newId, url: targetUrl };
+          void sendPulseHostCommand({
+            type: "ensure-tab",
+            tab_id: result.newId,
+            initial_url: targetUrl,
+          });
+        }
+
+        if (lastTab) {
+          setSelectedItemId(lastTab.id);
+          setAddress(lastTab.url);
+          void sendPulseHostCommand({ type: "activate-tab", tab_id: lastTab.id });
+        }
+        return nextItems;
+      });
+    });
+
+    // Launch URLs queue in the host until the UI reports ready.
+    void sendPulseHostCommand({ type: "ui-ready" });
+    return unsubscribe;
+  }, [defaultParentId]);
 
   function onSelect(item: WorkspaceItem) {
     setSelectedItemId(item.id);
     if (item.kind === "browser-tab") {
       setAddress(item.url);
-      sendPulseHostCommand("activate-tab", item.id);
+      void sendPulseHostCommand({ type: "activate-tab", tab_id: item.id });
     }
   }
 
@@ -323,21 +495,36 @@ function App() {
       const result = addBrowserTab(prev, parentId, "https://duckduckgo.com");
       setSelectedItemId(result.newId);
       setAddress("https://duckduckgo.com");
-      sendPulseHostCommand("ensure-tab", result.newId, "https://duckduckgo.com");
-      sendPulseHostCommand("activate-tab", result.newId);
+      void sendPulseHostCommand({
+        type: "ensure-tab",
+        tab_id: result.newId,
+        initial_url: "https://duckduckgo.com",
+      });
+      void sendPulseHostCommand({ type: "activate-tab", tab_id: result.newId });
       return result.items;
     });
   }
 
+  function onTogglePinned(id: string) {
+    const tab = items.find((item): item is BrowserTabItem => item.id === id && item.kind === "browser-tab");
+    if (!tab) {
+      return;
+    }
+
+    const pinned = !tab.pinned;
+    setItems((prev) => setTabPinned(prev, id, pinned));
+    void sendPulseHostCommand({ type: "set-tab-pinned", tab_id: id, pinned });
+  }
+
   function onCloseTab(id: string) {
     const tab = items.find((item): item is BrowserTabItem => item.id === id && item.kind === "browser-tab");
     if (!tab) {
       return;
     }
 
-    const nextItems = removeWorkspaceItem(items, id);
+    const nextItems = removeWorkspaceItem(promoteChildren(items, id), id);
     setItems(nextItems);
-    sendPulseHostCommand("close-tab", id);
+    void sendPulseHostCommand({ type: "close-tab", tab_id: id });
 
     if (selectedItemId !== id) {
       return;
@@ -364,15 +551,19 @@ function App() {
 
     if (selectedTab) {
       setItems((prev) => updateTabUrl(prev, selectedTab.id, targetUrl));
-      sendPulseHostCommand("navigate-tab", selectedTab.id, targetUrl);
+      void sendPulseHostCommand({ type: "navigate-tab", tab_id: selectedTab.id, url: targetUrl });
       return;
     }
 
     setItems((prev) => {
       const result = addBrowserTab(prev, defaultParentId, targetUrl);
       setSelectedItemId(result.newId);
-      sendPulseHostCommand("ensure-tab", result.newId, targetUrl);
-      sendPulseHostCommand("activate-tab", result.newId);
+      void sendPulseHostCommand({
+        type: "ensure-tab",
+        tab_id: result.newId,
+        initial_url: targetUrl,
+      });
+      void sendPulseHostCommand({ type: "activate-tab", tab_id: result.newId });
       return result.items;
     });
   }
@@ -386,7 +577,7 @@ function App() {
       return;
     }
 
-    sendPulseHostCommand(action, selectedTabId);
+    void sendPulseHostCommand({ type: action, tab_id: selectedTabId });
   }
 
   function focusAddressBar() {
@@ -481,12 +672,16 @@ function App() {
           <WorkspaceSidebar
             items={items}
             selectedItemId={selectedItemId || null}
+            tabFailures={tabFailures}
+            tabIcons={tabIcons}
+            tabLifecycles={tabLifecycles}
             onSelect={onSelect}
             onToggleGroup={onToggleGroup}
             onAddGroup={onAddGroup}
             onAddTab={onAddTab}
             onMoveByDrop={onMoveByDrop}
             onCloseTab={onCloseTab}
+            onTogglePinned={onTogglePinned}
             onOpenSettings={() => setSettingsOpen(true)}
             isDarkMode={isDarkMode}
             onToggleTheme={() => setIsDarkMode((prev) => !prev)}
@@ -500,7 +695,7 @@ function App() {
                   size="icon"
                   title="Back"
                   onClick={() => runCommand("browser.back")}
-                  disabled={!selectedTabId}
+                  disabled={!selectedNavState?.canGoBack}
                 >
                   <ArrowLeft className="h-4 w-4" />
                 </Button>
@@ -509,7 +704,7 @@ function App() {
                   size="icon"
                   title="Forward"
                   onClick={() => runCommand("browser.forward")}
-                  disabled={!selectedTabId}
+                  disabled={!selectedNavState?.canGoForward}
                 >
                   <ArrowRight className="h-4 w-4" />
                 </Button>
@@ -527,7 +722,7 @@ function App() {
                   size="icon"
                   title="Stop"
                   onClick={() => runCommand("browser.stop")}
-                  disabled={!selectedTabId}
+                  disabled={!selectedNavState?.isLoading}
                 >
                   <Square className="h-3.5 w-3.5" />
                 </Button>
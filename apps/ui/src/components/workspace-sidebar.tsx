@@ -15,33 +15,40 @@ import { CSS } from "@dnd-kit/utilities";
 import * as ContextMenu from "@radix-ui/react-context-menu";
 import {
   ChevronDown,
+  CircleAlert,
   ChevronRight,
   FileCode2,
   FolderTree,
   Globe,
   GripVertical,
   MoonStar,
+  Pin,
   Plus,
   Settings2,
   Sun,
   X,
 } from "lucide-react";
-import { type ButtonHTMLAttributes, type ReactNode, useMemo } from "react";
+import { type ButtonHTMLAttributes, type ReactNode, useMemo, useState } from "react";
 
 import { Button } from "@/components/ui/button";
 import { ScrollArea } from "@/components/ui/scroll-area";
-import { type WorkspaceItem, childrenOf } from "@/lib/workspace";
+import { type TabLoadFailure, type WorkspaceItem, childrenOf } from "@/lib/workspace";
+import { type PulseTabLifecycle } from "@/lib/pulse-host";
 import { cn } from "@/lib/utils";
 
 interface WorkspaceSidebarProps {
   items: WorkspaceItem[];
   selectedItemId: string | null;
+  tabFailures: Record<string, TabLoadFailure>;
+  tabIcons: Record<string, string | null>;
+  tabLifecycles: Record<string, PulseTabLifecycle>;
   onSelect: (item: WorkspaceItem) => void;
   onToggleGroup: (id: string) => void;
   onAddGroup: () => void;
   onAddTab: (parentId: string | null) => void;
   onMoveByDrop: (activeId: string, overId: string) => void;
   onCloseTab: (id: string) => void;
+  onTogglePinned: (id: string) => void;
   onOpenSettings: () => void;
   isDarkMode: boolean;
   onToggleTheme: () => void;
@@ -60,12 +67,16 @@ const idleRowClass = "hover:bg-accent/60";
 export function WorkspaceSidebar({
   items,
   selectedItemId,
+  tabFailures,
+  tabIcons,
+  tabLifecycles,
   onSelect,
   onToggleGroup,
   onAddGroup,
   onAddTab,
   onMoveByDrop,
   onCloseTab,
+  onTogglePinned,
   onOpenSettings,
   isDarkMode,
   onToggleTheme,
@@ -109,10 +120,14 @@ export function WorkspaceSidebar({
                   key={row.item.id}
                   row={row}
                   selected={selectedItemId === row.item.id}
+                  failure={tabFailures[row.item.id]}
+                  iconUrl={tabIcons[row.item.id]}
+                  discarded={tabLifecycles[row.item.id] === "discarded"}
                   onSelect={onSelect}
                   onToggleGroup={onToggleGroup}
                   onAddTab={onAddTab}
                   onCloseTab={onCloseTab}
+                  onTogglePinned={onTogglePinned}
                 />
               ))}
             </SortableContext>
@@ -145,22 +160,49 @@ export function WorkspaceSidebar({
   );
 }
 
+// Tabs not loaded this session fall back to the icon the host cached for their page.
+function TabIcon({ pageUrl, iconUrl }: { pageUrl: string; iconUrl?: string | null }) {
+  const [failed, setFailed] = useState(false);
+  const src = iconUrl === undefined ? `pulse://favicon/?page=${encodeURIComponent(pageUrl)}` : iconUrl;
+
+  if (!src || failed) {
+    return <Globe className="h-3.5 w-3.5 shrink-0 opacity-80" />;
+  }
+  return (
+    <img
+      src={src}
+      alt=""
+      className="h-3.5 w-3.5 shrink-0 object-contain"
+      draggable={false}
+      onError={() => setFailed(true)}
+    />
+  );
+}
+
 interface SortableTreeRowProps {
   row: VisibleTreeRow;
   selected: boolean;
+  failure?: TabLoadFailure;
+  iconUrl?: string | null;
+  discarded: boolean;
   onSelect: (item: WorkspaceItem) => void;
   onToggleGroup: (id: string) => void;
   onAddTab: (parentId: string | null) => void;
   onCloseTab: (id: string) => void;
+  onTogglePinned: (id: string) => void;
 }
 
 function SortableTreeRow({
   row,
   selected,
+  failure,
+  iconUrl,
+  discarded,
   onSelect,
   onToggleGroup,
   onAddTab,
   onCloseTab,
+  onTogglePinned,
 }: SortableTreeRowProps) {
   const { item, depth } = row;
   const { attributes, listeners, setNodeRef, transform, transition, isDragging } = useSortable({
@@ -214,13 +256,29 @@ function SortableTreeRow({
             ) : null}
 
             {item.kind === "browser-tab" ? (
-              <Globe className="h-3.5 w-3.5 shrink-0 opacity-80" />
+              <TabIcon key={iconUrl ?? item.url} pageUrl={item.url} iconUrl={iconUrl} />
             ) : item.kind === "group" ? (
               <FolderTree className="h-3.5 w-3.5 shrink-0 opacity-80" />
             ) : (
               <FileCode2 className="h-3.5 w-3.5 shrink-0 opacity-80" />
             )}
-            <span className="min-w-0 truncate text-sm">{item.title}</span>
+            <span
+              className={cn("min-w-0 truncate text-sm", discarded && "opacity-60")}
+              title={discarded ? "Unloaded to save memory; reloads when opened" : undefined}
+            >
+              {item.title}
+            </span>
+            {item.kind === "browser-tab" && item.pinned ? (
+              <Pin className="h-3 w-3 shrink-0 opacity-70" aria-label="Pinned" />
+            ) : null}
+            {failure ? (
+              <CircleAlert
+                className="h-3.5 w-3.5 shrink-0 text-destructive"
+                aria-label="Page failed to load"
+              >
+                <title>{`${failure.errorText || "Load failed"} (${failure.errorCode})`}</title>
+              </CircleAlert>
+            ) : null}
           </div>
 
           {item.kind === "group" ? (
@@ -273,6 +331,7 @@ function SortableTreeRow({
           {item.kind === "browser-tab" ? (
             <>
               <MenuItem onSelect={() => onAddTab(item.parentId)} label="New Tab Nearby" />
+              <MenuItem onSelect={() => onTogglePinned(item.id)} label={item.pinned ? "Unpin Tab" : "Pin Tab"} />
               <MenuItem onSelect={() => onCloseTab(item.id)} label="Close Tab" destructive />
             </>
           ) : null}
@@ -333,24 +392,17 @@ function MenuItem({
 
 function buildVisibleRows(items: WorkspaceItem[]): VisibleTreeRow[] {
   const rows: VisibleTreeRow[] = [];
-  const rootItems = childrenOf(items, null);
-
-  for (const rootItem of rootItems) {
-    rows.push({
-      item: rootItem,
-      depth: 0,
-    });
 
-    if (rootItem.kind === "group" && !rootItem.collapsed) {
-      const groupChildren = childrenOf(items, rootItem.id);
-      for (const child of groupChildren) {
-        rows.push({
-          item: child,
-          depth: 1,
-        });
+  // Tabs opened from a tab nest under it, so any item can have children.
+  function addRows(parentId: string | null, depth: number) {
+    for (const item of childrenOf(items, parentId)) {
+      rows.push({ item, depth });
+      if (item.kind !== "group" || !item.collapsed) {
+        addRows(item.id, depth + 1);
       }
     }
   }
 
+  addRows(null, 0);
   return rows;
 }
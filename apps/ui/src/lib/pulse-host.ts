@@ -1,38 +1,301 @@
-export type PulseHostArg = string | number | boolean;
+// Mirrors `pulse_core::protocol::HostCommand` (serde tag `type`) for the commands `send`
+// posts; persistence commands go through the dedicated bridge functions below.
+export type PulseHostCommand =
+  | { type: "ensure-tab"; tab_id: string; initial_url?: string; pinned?: boolean }
+  | { type: "activate-tab"; tab_id: string }
+  | { type: "navigate-tab"; tab_id: string; url: string }
+  | { type: "close-tab"; tab_id: string }
+  | { type: "set-tab-pinned"; tab_id: string; pinned: boolean }
+  | {
+      type: "restore-tabs";
+      tabs: PulseRestoredTab[];
+      active_tab_id?: string;
+      prefetch_tab_ids?: string[];
+    }
+  | { type: "browser-back"; tab_id: string }
+  | { type: "browser-forward"; tab_id: string }
+  | { type: "browser-reload"; tab_id: string }
+  | { type: "browser-stop"; tab_id: string }
+  | { type: "set-content-bounds"; x: number; y: number; width: number; height: number }
+  | { type: "set-content-visible"; visible: boolean }
+  | { type: "ui-ready" };
 
-export const PULSE_TAB_RUNTIME_EVENT = "pulse:tab-runtime-updated";
+// Mirrors `pulse_core::protocol::RestoredTab`.
+export interface PulseRestoredTab {
+  tab_id: string;
+  url: string;
+  title?: string;
+  pinned?: boolean;
+  history?: PulseTabHistory;
+}
+
+// Mirrors `pulse_core::TabHistory`: a tab's back/forward list, `index` being the current entry.
+export interface PulseTabHistory {
+  entries: { url: string; title?: string }[];
+  index: number;
+}
+
+// Mirrors `pulse_core::protocol::HostError`. Bridge Promises reject with this, JSON-encoded.
+export interface PulseHostError {
+  code:
+    | "invalid-request"
+    | "unsupported-version"
+    | "capability-denied"
+    | "unknown-tab"
+    | "failed"
+    | "timeout";
+  message: string;
+  // The missing grant, for `capability-denied`.
+  capability?: string;
+}
+
+export type PulseHostCommandResult =
+  | { ok: true; value: unknown }
+  | { ok: false; error: PulseHostError };
+
+// Mirrors `pulse_core::protocol::TabLifecycle`. A discarded tab has no browser until it is
+// activated again.
+export type PulseTabLifecycle = "active" | "background" | "discarded";
+
+// Mirrors `pulse_core::protocol::HostEventMessage`: `seq` grows by one per host event.
+export type PulseHostEvent = { version: number; seq: number } & (
+  | { type: "tab-updated"; tab_id: string; url?: string; title?: string }
+  | {
+      type: "loading-state-changed";
+      tab_id: string;
+      is_loading: boolean;
+      can_go_back: boolean;
+      can_go_forward: boolean;
+    }
+  | { type: "load-started"; tab_id: string; url: string }
+  // `http_status_code` is 0 for non-HTTP loads.
+  | { type: "load-finished"; tab_id: string; url: string; http_status_code: number }
+  | { type: "load-progress"; tab_id: string; progress: number }
+  // `icon_url` is a `pulse://favicon/<sha256>` URL of the cached icon.
+  | { type: "favicon-changed"; tab_id: string; icon_url: string | null }
+  | { type: "load-failed"; tab_id: string; url: string; error_code: number; error_text: string }
+  | { type: "tab-crashed"; tab_id: string; error_code: number; reason: string }
+  | { type: "tab-closed"; tab_id: string }
+  | { type: "tab-lifecycle-changed"; tab_id: string; lifecycle: PulseTabLifecycle }
+  // The whole list, including entries from before the tab was last recreated.
+  | { type: "tab-history-changed"; tab_id: string; history: PulseTabHistory }
+  // The host opened nothing; the UI adds the tab under its opener and sends `ensure-tab`.
+  | { type: "tab-open-requested"; opener_tab_id: string; url: string; background: boolean }
+  | { type: "popup-opened"; opener_tab_id: string | null; url: string | null }
+  // URLs from the command line, including launches forwarded by a second Pulse process.
+  | { type: "open-urls"; urls: string[] }
+);
 
 export interface PulseHostBridge {
-  send: (...args: PulseHostArg[]) => boolean;
-  loadState?: () => string | null;
-  saveState?: (serializedState: string) => boolean;
+  // Resolves with the command's result (`{ created }` for `ensure-tab` and `navigate-tab`,
+  // `{ loaded }` for `restore-tabs`, otherwise null) and rejects with a JSON-encoded `PulseHostError`, including a `timeout`
+  // one after `timeoutMs` (30s by default). Throws when `command` does not match the protocol.
+  send: (command: PulseHostCommand, timeoutMs?: number) => Promise<unknown>;
+  // Host events, in order. Events sent before the first subscription are queued for it.
+  subscribe?: (listener: (event: PulseHostEvent) => void) => number;
+  unsubscribe?: (subscriptionId: number) => void;
+  // Persistence calls round-trip to the browser process and settle asynchronously.
+  loadState?: () => Promise<string | null>;
+  saveState?: (serializedState: string) => Promise<null>;
+  workspace?: (serializedCommand: string) => Promise<string>;
+  recoveryReport?: () => Promise<string | null>;
+  listSnapshots?: () => Promise<string>;
+  restoreSnapshot?: (snapshotId: string) => Promise<string>;
+  exportBundle?: (serializedRequest: string) => Promise<string>;
+  importBundle?: (serializedRequest: string) => Promise<string>;
+  listProfiles?: () => Promise<string>;
+  listGrants?: () => Promise<string>;
+  revokeGrant?: (serializedRequest: string) => Promise<string>;
+  capabilityAudit?: () => Promise<string>;
 }
 
-export interface PulseTabRuntimeEventDetail {
-  tabId: string;
-  url?: string;
-  title?: string;
+// Mirrors `snapshots::SnapshotInfo`.
+export interface PulseSnapshotInfo {
+  id: string;
+  created_at_unix_ms: number;
+  size_bytes: number;
+}
+
+// Mirrors `bundle::BundleManifest`.
+export interface PulseBundleManifest {
+  format: string;
+  bundle_version: number;
+  schema_version: number;
+  created_at_unix_ms: number;
+  pulse_version: string;
+  item_count: number;
+  state_checksum: string;
+}
+
+export type PulseBundleImportMode = "merge" | "replace";
+
+export interface PulseBundleImportResult {
+  manifest: PulseBundleManifest;
+  // Imported ids renamed to avoid collisions, original -> new.
+  remapped_ids: Record<string, string>;
+  // Workspace after the import, same shape as `loadState`.
+  session: unknown;
+}
+
+// Mirrors `profiles::Profile`.
+export interface PulseProfile {
+  id: string;
+  name: string;
+  created_at_unix_ms: number;
+}
+
+export interface PulseProfileList {
+  // Profile this Pulse process runs.
+  current: string;
+  // Profile launched when Pulse starts without `--profile`.
+  selected: string;
+  profiles: PulseProfile[];
+}
+
+// Mirrors `pulse_core::Grant`; `capability` uses the `kind:scope` grammar.
+export interface PulseCapabilityGrant {
+  holder: string;
+  capability: string;
+  granted_at_unix_ms: number;
+  expires_at_unix_ms?: number;
 }
 
+// Mirrors `pulse_core::AuditEntry` (serde tag `event`).
+export type PulseCapabilityAuditEntry = {
+  at_unix_ms: number;
+  holder: string;
+  capability: string;
+} & (
+  | { event: "granted"; expires_at_unix_ms?: number }
+  | { event: "revoked" }
+  | { event: "expired" }
+  | { event: "allowed"; grant: string }
+  | { event: "denied" }
+);
+
+// Mirrors `persistence::RecoveryReport`.
+export interface PulseRecoveryReport {
+  source: "primary" | "backup" | "snapshot" | "none";
+  source_path: string | null;
+  migrated_from_version: number | null;
+  quarantined: {
+    original_path: string;
+    quarantine_path: string;
+    reason: string;
+  }[];
+  loaded_at_unix_ms: number;
+}
+
+// Mirrors `pulse_core::WorkspaceCommand` (serde tag `op`).
+export type PulseWorkspaceCommand =
+  | { op: "list-items" }
+  | { op: "get-item"; id: string }
+  | { op: "insert-item"; entry: PulseWorkspaceEntry }
+  | { op: "set-title"; id: string; title: string }
+  | { op: "set-tab-url"; id: string; url: string }
+  | { op: "set-group-collapsed"; id: string; collapsed: boolean }
+  | { op: "move-item"; id: string; parent_id: string | null; order: number }
+  | { op: "remove-item"; id: string }
+  | { op: "list-settings" }
+  | { op: "get-setting"; key: string }
+  | { op: "set-setting"; key: string; value: string }
+  | { op: "remove-setting"; key: string };
+
+export interface PulseWorkspaceEntry {
+  item: {
+    id: string;
+    kind: "group" | "browser-tab" | "file-ref" | "capsule-view";
+    parent_id: string | null;
+    title: string;
+    order: number;
+  };
+  tab?: { url: string; opener_id?: string };
+  group?: { collapsed: boolean };
+  file?: { path: string };
+}
+
+// Mirrors `pulse_core::WorkspaceResponse` (serde tag `kind`).
+export type PulseWorkspaceResponse =
+  | { kind: "items"; entries: PulseWorkspaceEntry[] }
+  | { kind: "item"; entry: PulseWorkspaceEntry | null }
+  | { kind: "removed"; ids: string[] }
+  | { kind: "settings"; settings: Record<string, string> }
+  | { kind: "setting"; key: string; value: string | null }
+  | { kind: "done" };
+
 declare global {
   interface Window {
     __pulseHost?: PulseHostBridge;
   }
 }
 
-export function sendPulseHostCommand(...args: PulseHostArg[]) {
+// Reads the `PulseHostError` a bridge Promise rejected with.
+export function parsePulseHostError(reason: unknown): PulseHostError {
+  const message = reason instanceof Error ? reason.message : String(reason);
+  try {
+    const parsed = JSON.parse(message) as Partial<PulseHostError>;
+    if (typeof parsed.code === "string" && typeof parsed.message === "string") {
+      return parsed as PulseHostError;
+    }
+  } catch {
+    // Not a host error; report the raw message below.
+  }
+  return { code: "failed", message };
+}
+
+// Never rejects; failures are logged and returned so callers can resync with the host.
+export async function sendPulseHostCommand(
+  command: PulseHostCommand,
+  timeoutMs?: number,
+): Promise<PulseHostCommandResult> {
   if (typeof window === "undefined" || !window.__pulseHost) {
-    return false;
+    return { ok: false, error: { code: "failed", message: "Pulse host bridge is unavailable" } };
   }
 
   try {
-    return window.__pulseHost.send(...args);
-  } catch {
-    return false;
+    return { ok: true, value: await window.__pulseHost.send(command, timeoutMs) };
+  } catch (reason) {
+    const error = parsePulseHostError(reason);
+    console.warn(`Pulse host ${command.type} failed (${error.code}): ${error.message}`);
+    return { ok: false, error };
+  }
+}
+
+const hostEventListeners = new Set<(event: PulseHostEvent) => void>();
+let hostEventSubscription: number | null = null;
+let lastHostEventSeq: number | null = null;
+
+function dispatchPulseHostEvent(event: PulseHostEvent) {
+  if (lastHostEventSeq !== null && event.seq !== lastHostEventSeq + 1) {
+    console.warn(`Pulse host events ${lastHostEventSeq + 1}..${event.seq - 1} were missed`);
+  }
+  lastHostEventSeq = event.seq;
+  for (const listener of [...hostEventListeners]) {
+    listener(event);
   }
 }
 
-export function loadPulseHostState(): string | null {
+// Shares one bridge subscription between all listeners. Returns the unsubscribe function.
+export function subscribePulseHostEvents(listener: (event: PulseHostEvent) => void): () => void {
+  const bridge = typeof window === "undefined" ? undefined : window.__pulseHost;
+  if (typeof bridge?.subscribe !== "function") {
+    return () => {};
+  }
+
+  hostEventListeners.add(listener);
+  if (hostEventSubscription === null) {
+    hostEventSubscription = bridge.subscribe(dispatchPulseHostEvent);
+  }
+  return () => {
+    hostEventListeners.delete(listener);
+    if (hostEventListeners.size === 0 && hostEventSubscription !== null) {
+      bridge.unsubscribe?.(hostEventSubscription);
+      hostEventSubscription = null;
+    }
+  };
+}
+
+export async function loadPulseHostState(): Promise<string | null> {
   if (typeof window === "undefined") {
     return null;
   }
@@ -43,14 +306,15 @@ export function loadPulseHostState(): string | null {
   }
 
   try {
-    const loaded = hostLoadState();
+    const loaded = await hostLoadState();
     return typeof loaded === "string" ? loaded : null;
   } catch {
     return null;
   }
 }
 
-export function savePulseHostState(serializedState: string): boolean {
+// Resolves once the host has written the (possibly coalesced) save to disk.
+export async function savePulseHostState(serializedState: string): Promise<boolean> {
   if (typeof window === "undefined") {
     return false;
   }
@@ -61,34 +325,199 @@ export function savePulseHostState(serializedState: string): boolean {
   }
 
   try {
-    return hostSaveState(serializedState);
+    await hostSaveState(serializedState);
+    return true;
   } catch {
     return false;
   }
 }
 
-export function parsePulseTabRuntimeEventDetail(
-  value: unknown,
-): PulseTabRuntimeEventDetail | null {
-  if (!value || typeof value !== "object") {
+export async function loadPulseRecoveryReport(): Promise<PulseRecoveryReport | null> {
+  if (typeof window === "undefined") {
     return null;
   }
 
-  const candidate = value as Record<string, unknown>;
-  if (typeof candidate.tabId !== "string" || candidate.tabId.trim().length === 0) {
+  const hostRecoveryReport = window.__pulseHost?.recoveryReport;
+  if (typeof hostRecoveryReport !== "function") {
     return null;
   }
 
-  const detail: PulseTabRuntimeEventDetail = {
-    tabId: candidate.tabId,
-  };
+  try {
+    const report = await hostRecoveryReport();
+    return typeof report === "string" ? (JSON.parse(report) as PulseRecoveryReport) : null;
+  } catch {
+    return null;
+  }
+}
+
+export async function runPulseWorkspaceCommand(
+  command: PulseWorkspaceCommand,
+): Promise<PulseWorkspaceResponse | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostWorkspace = window.__pulseHost?.workspace;
+  if (typeof hostWorkspace !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(await hostWorkspace(JSON.stringify(command))) as PulseWorkspaceResponse;
+  } catch {
+    return null;
+  }
+}
+
+export async function listPulseSnapshots(): Promise<PulseSnapshotInfo[]> {
+  if (typeof window === "undefined") {
+    return [];
+  }
+
+  const hostListSnapshots = window.__pulseHost?.listSnapshots;
+  if (typeof hostListSnapshots !== "function") {
+    return [];
+  }
+
+  try {
+    return JSON.parse(await hostListSnapshots()) as PulseSnapshotInfo[];
+  } catch {
+    return [];
+  }
+}
+
+// Returns the restored session payload (same shape as `loadState`), or null on failure.
+export async function restorePulseSnapshot(snapshotId: string): Promise<string | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
 
-  if (typeof candidate.url === "string" && candidate.url.trim().length > 0) {
-    detail.url = candidate.url;
+  const hostRestoreSnapshot = window.__pulseHost?.restoreSnapshot;
+  if (typeof hostRestoreSnapshot !== "function") {
+    return null;
+  }
+
+  try {
+    return await hostRestoreSnapshot(snapshotId);
+  } catch {
+    return null;
+  }
+}
+
+// Writes a `.pulse` bundle; without `path` the host picks `<profile>/exports/`. Returns the
+// written path and manifest, or null on failure.
+export async function exportPulseBundle(
+  path?: string,
+): Promise<{ path: string; manifest: PulseBundleManifest } | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostExportBundle = window.__pulseHost?.exportBundle;
+  if (typeof hostExportBundle !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(await hostExportBundle(JSON.stringify(path ? { path } : {})));
+  } catch {
+    return null;
+  }
+}
+
+// The UI must adopt `session` from the result; saving its previous session would undo the import.
+export async function importPulseBundle(
+  path: string,
+  mode: PulseBundleImportMode,
+): Promise<PulseBundleImportResult | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostImportBundle = window.__pulseHost?.importBundle;
+  if (typeof hostImportBundle !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(
+      await hostImportBundle(JSON.stringify({ path, mode })),
+    ) as PulseBundleImportResult;
+  } catch {
+    return null;
+  }
+}
+
+export async function listPulseProfiles(): Promise<PulseProfileList | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostListProfiles = window.__pulseHost?.listProfiles;
+  if (typeof hostListProfiles !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(await hostListProfiles()) as PulseProfileList;
+  } catch {
+    return null;
   }
-  if (typeof candidate.title === "string" && candidate.title.trim().length > 0) {
-    detail.title = candidate.title;
+}
+
+export async function listPulseGrants(): Promise<PulseCapabilityGrant[] | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostListGrants = window.__pulseHost?.listGrants;
+  if (typeof hostListGrants !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(await hostListGrants()) as PulseCapabilityGrant[];
+  } catch {
+    return null;
+  }
+}
+
+// Returns the revoked grant, or null when the holder had no such grant or the call failed.
+export async function revokePulseGrant(
+  holder: string,
+  capability: string,
+): Promise<PulseCapabilityGrant | null> {
+  if (typeof window === "undefined") {
+    return null;
   }
 
-  return detail;
+  const hostRevokeGrant = window.__pulseHost?.revokeGrant;
+  if (typeof hostRevokeGrant !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(
+      await hostRevokeGrant(JSON.stringify({ holder, capability })),
+    ) as PulseCapabilityGrant;
+  } catch {
+    return null;
+  }
+}
+
+export async function listPulseCapabilityAudit(): Promise<PulseCapabilityAuditEntry[] | null> {
+  if (typeof window === "undefined") {
+    return null;
+  }
+
+  const hostCapabilityAudit = window.__pulseHost?.capabilityAudit;
+  if (typeof hostCapabilityAudit !== "function") {
+    return null;
+  }
+
+  try {
+    return JSON.parse(await hostCapabilityAudit()) as PulseCapabilityAuditEntry[];
+  } catch {
+    return null;
+  }
 }
@@ -1,3 +1,4 @@
+import type { PulseTabHistory } from "@/lib/pulse-host";
 import {
   type BrowserTabItem,
   type WorkspaceItem,
@@ -149,6 +150,8 @@ function parseWorkspaceItem(value: unknown): WorkspaceItem | null {
     if (!rawUrl) {
       return null;
     }
+    const openerId = asNonEmptyString(value.openerId);
+    const history = parseTabHistory(value.history);
     return {
       id,
       kind,
@@ -156,6 +159,9 @@ function parseWorkspaceItem(value: unknown): WorkspaceItem | null {
       parentId,
       order,
       url: normalizeUrl(rawUrl),
+      ...(openerId ? { openerId } : {}),
+      ...(value.pinned === true ? { pinned: true } : {}),
+      ...(history ? { history } : {}),
     };
   }
 
@@ -221,6 +227,28 @@ function cloneWorkspaceItems(items: WorkspaceItem[]): WorkspaceItem[] {
   return items.map((item) => ({ ...item }));
 }
 
+// The host validates the list again; this only drops what is not shaped like one.
+function parseTabHistory(value: unknown): PulseTabHistory | null {
+  if (!isRecord(value) || !Array.isArray(value.entries)) {
+    return null;
+  }
+
+  const index = asInteger(value.index);
+  const entries = value.entries.flatMap((entry) => {
+    if (!isRecord(entry)) {
+      return [];
+    }
+    const url = asNonEmptyString(entry.url);
+    const title = asNonEmptyString(entry.title);
+    return url ? [{ url, ...(title ? { title } : {}) }] : [];
+  });
+  if (index === null || entries.length !== value.entries.length || index >= entries.length) {
+    return null;
+  }
+
+  return { entries, index };
+}
+
 function isRecord(value: unknown): value is Record<string, unknown> {
   return value !== null && typeof value === "object";
 }
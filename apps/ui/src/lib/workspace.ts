@@ -1,3 +1,5 @@
+import type { PulseTabHistory } from "@/lib/pulse-host";
+
 export type WorkspaceItemKind = "group" | "browser-tab" | "file-ref";
 
 interface WorkspaceBase {
@@ -16,6 +18,12 @@ export interface WorkspaceGroup extends WorkspaceBase {
 export interface BrowserTabItem extends WorkspaceBase {
   kind: "browser-tab";
   url: string;
+  // Tab whose page opened this one; it may have been closed since.
+  openerId?: string;
+  // Pinned tabs are never discarded by the host.
+  pinned?: boolean;
+  // Back/forward list the host last reported, handed back to it on restore.
+  history?: PulseTabHistory;
 }
 
 export interface FileRefItem extends WorkspaceBase {
@@ -25,6 +33,12 @@ export interface FileRefItem extends WorkspaceBase {
 
 export type WorkspaceItem = WorkspaceGroup | BrowserTabItem | FileRefItem;
 
+// A tab's last failed navigation, from the host's `load-failed` event. Not persisted.
+export interface TabLoadFailure {
+  errorCode: number;
+  errorText: string;
+}
+
 export const initialWorkspace: WorkspaceItem[] = [
   {
     id: "group-research",
@@ -106,6 +120,26 @@ export function toggleGroupCollapsed(items: WorkspaceItem[], groupId: string) {
   });
 }
 
+export function setTabPinned(items: WorkspaceItem[], id: string, pinned: boolean) {
+  return items.map((item) => {
+    if (item.id !== id || item.kind !== "browser-tab") {
+      return item;
+    }
+
+    return { ...item, pinned: pinned || undefined };
+  });
+}
+
+export function setTabHistory(items: WorkspaceItem[], id: string, history: PulseTabHistory) {
+  return items.map((item) => {
+    if (item.id !== id || item.kind !== "browser-tab") {
+      return item;
+    }
+
+    return { ...item, history };
+  });
+}
+
 export function updateTabUrl(items: WorkspaceItem[], id: string, url: string) {
   return items.map((item) => {
     if (item.id !== id || item.kind !== "browser-tab") {
@@ -158,6 +192,7 @@ export function addBrowserTab(
   items: WorkspaceItem[],
   parentId: string | null,
   url: string,
+  openerId?: string,
 ): { items: WorkspaceItem[]; newId: string } {
   const normalized = normalizeUrl(url);
   const newId = crypto.randomUUID();
@@ -168,6 +203,7 @@ export function addBrowserTab(
     order: nextOrder(items, parentId),
     title: titleFromUrl(normalized),
     url: normalized,
+    ...(openerId ? { openerId } : {}),
   };
 
   return {
@@ -230,6 +266,24 @@ export function removeWorkspaceItem(items: WorkspaceItem[], itemId: string) {
   return reindexAllParents(filtered);
 }
 
+// Moves the children of `itemId` up to its parent, after the existing siblings there, so
+// closing a tab keeps the tabs it opened.
+export function promoteChildren(items: WorkspaceItem[], itemId: string) {
+  const target = items.find((item) => item.id === itemId);
+  if (!target) {
+    return items;
+  }
+
+  const firstOrder = nextOrder(items, target.parentId);
+  const promotedOrder = new Map(
+    childrenOf(items, itemId).map((child, index) => [child.id, firstOrder + index]),
+  );
+  return items.map((item) => {
+    const order = promotedOrder.get(item.id);
+    return order === undefined ? item : { ...item, parentId: target.parentId, order };
+  });
+}
+
 export function moveItemByDrop(items: WorkspaceItem[], activeId: string, overId: string) {
   if (activeId === overId) {
     return items;
@@ -285,13 +339,27 @@ function moveGroupByDrop(items: WorkspaceItem[], active: WorkspaceGroup, over: W
   });
 }
 
+// Whether `itemId` is `ancestorId` or sits anywhere below it.
+function isSelfOrDescendant(items: WorkspaceItem[], itemId: string, ancestorId: string) {
+  let current: string | null = itemId;
+  const seen = new Set<string>();
+  while (current && !seen.has(current)) {
+    if (current === ancestorId) {
+      return true;
+    }
+    seen.add(current);
+    current = items.find((item) => item.id === current)?.parentId ?? null;
+  }
+  return false;
+}
+
 function moveChildByDrop(
   items: WorkspaceItem[],
   active: Exclude<WorkspaceItem, WorkspaceGroup>,
   over: WorkspaceItem,
 ) {
   const targetParentId = over.kind === "group" ? over.id : over.parentId;
-  if (!targetParentId) {
+  if (!targetParentId || isSelfOrDescendant(items, targetParentId, active.id)) {
     return items;
   }
 
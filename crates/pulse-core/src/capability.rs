@@ -0,0 +1,493 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Permission families from `docs/capabilities.md`. Each is written as its dotted name,
+/// followed by `:<scope>` for the scoped ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CapabilityKind {
+    BrowserNavigate,
+    BrowserCookiesRead,
+    BrowserCookiesWrite,
+    FsRead,
+    FsWrite,
+    TerminalExec,
+    McpToolInvoke,
+    NetworkFetch,
+    WorkspaceRead,
+    WorkspaceMutate,
+    WorkspaceNavigate,
+    UiLayout,
+    GrantsRead,
+    GrantsRevoke,
+}
+
+impl CapabilityKind {
+    pub const ALL: [CapabilityKind; 14] = [
+        CapabilityKind::BrowserNavigate,
+        CapabilityKind::BrowserCookiesRead,
+        CapabilityKind::BrowserCookiesWrite,
+        CapabilityKind::FsRead,
+        CapabilityKind::FsWrite,
+        CapabilityKind::TerminalExec,
+        CapabilityKind::McpToolInvoke,
+        CapabilityKind::NetworkFetch,
+        CapabilityKind::WorkspaceRead,
+        CapabilityKind::WorkspaceMutate,
+        CapabilityKind::WorkspaceNavigate,
+        CapabilityKind::UiLayout,
+        CapabilityKind::GrantsRead,
+        CapabilityKind::GrantsRevoke,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CapabilityKind::BrowserNavigate => "browser.navigate",
+            CapabilityKind::BrowserCookiesRead => "browser.cookies.read",
+            CapabilityKind::BrowserCookiesWrite => "browser.cookies.write",
+            CapabilityKind::FsRead => "fs.read",
+            CapabilityKind::FsWrite => "fs.write",
+            CapabilityKind::TerminalExec => "terminal.exec",
+            CapabilityKind::McpToolInvoke => "mcp.tool.invoke",
+            CapabilityKind::NetworkFetch => "network.fetch",
+            CapabilityKind::WorkspaceRead => "workspace.read",
+            CapabilityKind::WorkspaceMutate => "workspace.mutate",
+            CapabilityKind::WorkspaceNavigate => "workspace.navigate",
+            CapabilityKind::UiLayout => "ui.layout",
+            CapabilityKind::GrantsRead => "grants.read",
+            CapabilityKind::GrantsRevoke => "grants.revoke",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Whether the capability must be written with a `:<scope>` suffix.
+    pub fn is_scoped(self) -> bool {
+        matches!(
+            self,
+            CapabilityKind::FsRead
+                | CapabilityKind::FsWrite
+                | CapabilityKind::TerminalExec
+                | CapabilityKind::McpToolInvoke
+                | CapabilityKind::NetworkFetch
+        )
+    }
+
+    /// Filesystem scopes are absolute paths matched by component; `network.fetch` scopes are
+    /// URLs matched part by part; the other scopes are plain glob patterns.
+    pub fn has_path_scope(self) -> bool {
+        matches!(self, CapabilityKind::FsRead | CapabilityKind::FsWrite)
+    }
+
+    pub fn has_url_scope(self) -> bool {
+        self == CapabilityKind::NetworkFetch
+    }
+}
+
+impl fmt::Display for CapabilityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CapabilityError {
+    #[error("unknown capability '{0}'")]
+    UnknownKind(String),
+    #[error("capability '{0}' requires a scope")]
+    MissingScope(CapabilityKind),
+    #[error("capability '{0}' does not take a scope")]
+    UnexpectedScope(CapabilityKind),
+    #[error("scope '{scope}' of capability '{kind}' must not contain control characters")]
+    InvalidScope { kind: CapabilityKind, scope: String },
+    #[error(
+        "scope '{scope}' of capability '{kind}' must be an absolute path without '.' or '..' segments"
+    )]
+    InvalidPath { kind: CapabilityKind, scope: String },
+    #[error("scope '{scope}' of capability '{kind}' must be a URL with a scheme and a host")]
+    InvalidUrl { kind: CapabilityKind, scope: String },
+}
+
+/// A capability with its scope, written `kind` or `kind:scope`.
+///
+/// The same type describes both grants and requests. Grant scopes may use `*` (any run of
+/// characters) and `?` (one character); request scopes are taken literally. Filesystem
+/// scopes are normalized to `/` separators, and a grant covers a request when the grant
+/// path is a component prefix of the request path or, when it contains wildcards, when
+/// the request matches it component by component. There `*` and `?` stay inside one
+/// component and a `**` component spans any number of them. Components compare
+/// case-sensitively, so a differently cased request is denied rather than allowed.
+///
+/// `network.fetch` scopes are URLs whose scheme, host, port and path are matched
+/// separately, so a wildcard never reaches past its part: host labels are matched one by
+/// one and the path segment by segment, as filesystem components are.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Capability {
+    kind: CapabilityKind,
+    scope: Option<String>,
+}
+
+impl Capability {
+    pub fn new(kind: CapabilityKind, scope: Option<&str>) -> Result<Self, CapabilityError> {
+        let scope = match (kind.is_scoped(), scope) {
+            (false, None) => None,
+            (false, Some(_)) => return Err(CapabilityError::UnexpectedScope(kind)),
+            (true, None) => return Err(CapabilityError::MissingScope(kind)),
+            (true, Some("")) => return Err(CapabilityError::MissingScope(kind)),
+            (true, Some(scope)) if scope.chars().any(char::is_control) => {
+                return Err(CapabilityError::InvalidScope {
+                    kind,
+                    scope: scope.to_string(),
+                });
+            }
+            (true, Some(scope)) if kind.has_path_scope() => Some(
+                normalize_path(scope).ok_or_else(|| CapabilityError::InvalidPath {
+                    kind,
+                    scope: scope.to_string(),
+                })?,
+            ),
+            (true, Some(scope)) if kind.has_url_scope() && UrlScope::parse(scope).is_none() => {
+                return Err(CapabilityError::InvalidUrl {
+                    kind,
+                    scope: scope.to_string(),
+                });
+            }
+            (true, Some(scope)) => Some(scope.to_string()),
+        };
+        Ok(Self { kind, scope })
+    }
+
+    pub fn kind(&self) -> CapabilityKind {
+        self.kind
+    }
+
+    pub fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    /// Whether holding `self` as a grant allows `request`.
+    pub fn covers(&self, request: &Capability) -> bool {
+        if self.kind != request.kind {
+            return false;
+        }
+        match (self.scope.as_deref(), request.scope.as_deref()) {
+            (None, None) => true,
+            (Some(grant), Some(requested)) if self.kind.has_path_scope() => {
+                path_covers(grant, requested)
+            }
+            (Some(grant), Some(requested)) if self.kind.has_url_scope() => {
+                match (UrlScope::parse(grant), UrlScope::parse(requested)) {
+                    (Some(grant), Some(requested)) => grant.covers(&requested),
+                    _ => false,
+                }
+            }
+            (Some(grant), Some(requested)) => glob_matches(&chars(grant), &chars(requested)),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scope {
+            Some(scope) => write!(f, "{}:{scope}", self.kind),
+            None => f.write_str(self.kind.name()),
+        }
+    }
+}
+
+impl FromStr for Capability {
+    type Err = CapabilityError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, scope) = match value.split_once(':') {
+            Some((name, scope)) => (name, Some(scope)),
+            None => (value, None),
+        };
+        let kind = CapabilityKind::from_name(name)
+            .ok_or_else(|| CapabilityError::UnknownKind(name.to_string()))?;
+        Self::new(kind, scope)
+    }
+}
+
+impl TryFrom<String> for Capability {
+    type Error = CapabilityError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Capability> for String {
+    fn from(capability: Capability) -> Self {
+        capability.to_string()
+    }
+}
+
+/// `/`-separated form of an absolute Unix (`/...`) or Windows (`C:\...`, `\\server\...`)
+/// path with empty segments and trailing separators dropped.
+fn normalize_path(scope: &str) -> Option<String> {
+    let unified = scope.replace('\\', "/");
+    let (root, rest) = if let Some(rest) = unified.strip_prefix("//") {
+        ("//", rest)
+    } else if let Some(rest) = unified.strip_prefix('/') {
+        ("/", rest)
+    } else {
+        let bytes = unified.as_bytes();
+        if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == b":/" {
+            (&unified[..3], &unified[3..])
+        } else {
+            return None;
+        }
+    };
+
+    let segments = rest
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    if segments
+        .iter()
+        .any(|segment| *segment == "." || *segment == "..")
+    {
+        return None;
+    }
+    Some(format!("{root}{}", segments.join("/")))
+}
+
+/// Root (`/`, `//` or `C:/`) followed by the path's segments.
+fn path_components(path: &str) -> Vec<&str> {
+    let root_len = if path.starts_with("//") {
+        2
+    } else if path.starts_with('/') {
+        1
+    } else {
+        3
+    };
+    std::iter::once(&path[..root_len])
+        .chain(
+            path[root_len..]
+                .split('/')
+                .filter(|segment| !segment.is_empty()),
+        )
+        .collect()
+}
+
+fn path_covers(grant: &str, request: &str) -> bool {
+    let grant = path_components(grant);
+    let request = path_components(request);
+    if grant.iter().any(|component| component.contains(['*', '?'])) {
+        components_match(&grant, &request)
+    } else {
+        request.starts_with(&grant)
+    }
+}
+
+fn components_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| components_match(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path_rest)) => {
+                glob_matches(&chars(first), &chars(component)) && components_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// A `network.fetch` scope split at its separators. User info is dropped, since the host
+/// after it is where the request goes.
+#[derive(Debug, PartialEq, Eq)]
+struct UrlScope<'a> {
+    scheme: String,
+    host: String,
+    port: &'a str,
+    path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> UrlScope<'a> {
+    fn parse(scope: &'a str) -> Option<Self> {
+        let (scheme, rest) = scope.split_once("://")?;
+        let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let (authority, rest) = rest.split_at(authority_end);
+        let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        let host_port = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port),
+            _ => (host_port, ""),
+        };
+        if scheme.is_empty() || host.is_empty() {
+            return None;
+        }
+        Some(Self {
+            scheme: scheme.to_ascii_lowercase(),
+            host: host.to_ascii_lowercase(),
+            port,
+            path: if path.is_empty() { "/" } else { path },
+            query,
+        })
+    }
+
+    /// A grant without a query covers any query on the paths it matches.
+    fn covers(&self, request: &UrlScope) -> bool {
+        fn labels(host: &str) -> Vec<&str> {
+            host.split('.').collect()
+        }
+        fn segments(path: &str) -> Vec<&str> {
+            path.split('/').skip(1).collect()
+        }
+        glob_matches(&chars(&self.scheme), &chars(&request.scheme))
+            && components_match(&labels(&self.host), &labels(&request.host))
+            && glob_matches(&chars(self.port), &chars(request.port))
+            && components_match(&segments(self.path), &segments(request.path))
+            && self.query.is_none_or(|query| {
+                glob_matches(&chars(query), &chars(request.query.unwrap_or_default()))
+            })
+    }
+}
+
+fn chars(text: &str) -> Vec<char> {
+    text.chars().collect()
+}
+
+/// `*` matches any run of characters and `?` exactly one; everything else is literal.
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap(value: &str) -> Capability {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_the_documented_grammar() {
+        for value in [
+            "browser.navigate",
+            "browser.cookies.write",
+            "fs.read:/home/ada/projects",
+            "fs.write:C:/Users/ada/**/*.md",
+            "terminal.exec:default",
+            "mcp.tool.invoke:github.*",
+            "network.fetch:https://*.example.com/*",
+        ] {
+            assert_eq!(cap(value).to_string(), value);
+        }
+        assert_eq!(
+            cap(r"fs.read:C:\Users\ada\\").to_string(),
+            "fs.read:C:/Users/ada"
+        );
+
+        assert_eq!(
+            "fs.list:/".parse::<Capability>(),
+            Err(CapabilityError::UnknownKind("fs.list".into()))
+        );
+        assert_eq!(
+            "fs.read".parse::<Capability>(),
+            Err(CapabilityError::MissingScope(CapabilityKind::FsRead))
+        );
+        assert_eq!(
+            "browser.navigate:*".parse::<Capability>(),
+            Err(CapabilityError::UnexpectedScope(
+                CapabilityKind::BrowserNavigate
+            ))
+        );
+        for value in [
+            "fs.read:projects",
+            "fs.read:/home/ada/../eve",
+            "fs.write:/./x",
+        ] {
+            assert!(matches!(
+                value.parse::<Capability>(),
+                Err(CapabilityError::InvalidPath { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn grants_cover_matching_requests_only() {
+        let prefix = cap("fs.read:/home/ada/projects");
+        assert!(prefix.covers(&cap("fs.read:/home/ada/projects")));
+        assert!(prefix.covers(&cap("fs.read:/home/ada/projects/pulse/Cargo.toml")));
+        assert!(!prefix.covers(&cap("fs.read:/home/ada/projects2")));
+        assert!(!prefix.covers(&cap("fs.read:/home/ada")));
+        assert!(!prefix.covers(&cap("fs.write:/home/ada/projects/a")));
+
+        let glob = cap("fs.write:/home/*/notes/**/*.md");
+        assert!(glob.covers(&cap("fs.write:/home/ada/notes/todo.md")));
+        assert!(glob.covers(&cap("fs.write:/home/ada/notes/2026/10/log.md")));
+        assert!(!glob.covers(&cap("fs.write:/home/ada/eve/notes/todo.md")));
+        assert!(!glob.covers(&cap("fs.write:/home/ada/notes/todo.txt")));
+
+        let tools = cap("mcp.tool.invoke:github.*");
+        assert!(tools.covers(&cap("mcp.tool.invoke:github.create_issue")));
+        assert!(!tools.covers(&cap("mcp.tool.invoke:gitlab.create_issue")));
+        assert!(cap("terminal.exec:*").covers(&cap("terminal.exec:pwsh")));
+        assert!(!cap("terminal.exec:bash").covers(&cap("terminal.exec:bash2")));
+        assert!(cap("browser.navigate").covers(&cap("browser.navigate")));
+    }
+
+    #[test]
+    fn network_wildcards_stay_inside_their_url_part() {
+        let sites = cap("network.fetch:https://*.example.com/*");
+        assert!(sites.covers(&cap("network.fetch:https://api.example.com/")));
+        assert!(sites.covers(&cap("network.fetch:https://API.example.com/search?q=a/b")));
+        assert!(sites.covers(&cap("network.fetch:https://api.example.com")));
+        assert!(!sites.covers(&cap("network.fetch:https://evil.test/x.example.com/")));
+        assert!(!sites.covers(&cap("network.fetch:https://api.example.com@evil.test/")));
+        assert!(!sites.covers(&cap("network.fetch:https://api.example.com.evil.test/")));
+        assert!(!sites.covers(&cap("network.fetch:https://a.b.example.com/")));
+        assert!(!sites.covers(&cap("network.fetch:https://api.example.com/v1/users")));
+        assert!(!sites.covers(&cap("network.fetch:https://api.example.com:8443/")));
+        assert!(!sites.covers(&cap("network.fetch:http://api.example.com/")));
+
+        let api = cap("network.fetch:https://api.example.com/v1/**");
+        assert!(api.covers(&cap("network.fetch:https://api.example.com/v1/users/7")));
+        assert!(!api.covers(&cap("network.fetch:https://api.example.com/v2/users")));
+        assert!(
+            cap("network.fetch:https://api.example.com/search?q=*")
+                .covers(&cap("network.fetch:https://api.example.com/search?q=pulse"))
+        );
+
+        assert!(matches!(
+            "network.fetch:*".parse::<Capability>(),
+            Err(CapabilityError::InvalidUrl { .. })
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Capability;
+
+/// One capability held by one holder (the trusted UI, a capsule, ...).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Grant {
+    pub holder: String,
+    pub capability: Capability,
+    pub granted_at_unix_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at_unix_ms: Option<u64>,
+}
+
+impl Grant {
+    pub fn is_expired(&self, now_unix_ms: u64) -> bool {
+        self.expires_at_unix_ms
+            .is_some_and(|expires_at| expires_at <= now_unix_ms)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GrantError {
+    #[error("grant holder must not be empty")]
+    EmptyHolder,
+    #[error("grant of '{capability}' to '{holder}' would already be expired")]
+    AlreadyExpired {
+        holder: String,
+        capability: Capability,
+    },
+    #[error("'{holder}' holds no grant of '{capability}'")]
+    UnknownGrant {
+        holder: String,
+        capability: Capability,
+    },
+}
+
+/// What an audit entry records. Checks name the grant that allowed them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum AuditEvent {
+    Granted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at_unix_ms: Option<u64>,
+    },
+    Revoked,
+    Expired,
+    Allowed {
+        grant: Capability,
+    },
+    Denied,
+}
+
+/// One line of the append-only capability audit log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at_unix_ms: u64,
+    pub holder: String,
+    pub capability: Capability,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+/// Host-owned capability grants, at most one per `(holder, capability)` pair.
+///
+/// Mutations and checks return the `AuditEntry` describing them; whoever persists the
+/// store appends those to the audit log. Nothing is granted implicitly: a request is
+/// allowed only by an unexpired grant to the same holder that covers it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GrantStore {
+    #[serde(default)]
+    grants: Vec<Grant>,
+}
+
+impl GrantStore {
+    pub fn grants(&self) -> &[Grant] {
+        &self.grants
+    }
+
+    pub fn held_by<'a>(&'a self, holder: &'a str) -> impl Iterator<Item = &'a Grant> {
+        self.grants
+            .iter()
+            .filter(move |grant| grant.holder == holder)
+    }
+
+    /// Grants `capability` to `holder`, replacing an existing grant of the same capability.
+    pub fn grant(
+        &mut self,
+        holder: &str,
+        capability: Capability,
+        now_unix_ms: u64,
+        expires_at_unix_ms: Option<u64>,
+    ) -> Result<AuditEntry, GrantError> {
+        if holder.is_empty() {
+            return Err(GrantError::EmptyHolder);
+        }
+        let grant = Grant {
+            holder: holder.to_string(),
+            capability,
+            granted_at_unix_ms: now_unix_ms,
+            expires_at_unix_ms,
+        };
+        if grant.is_expired(now_unix_ms) {
+            return Err(GrantError::AlreadyExpired {
+                holder: grant.holder,
+                capability: grant.capability,
+            });
+        }
+
+        let entry = AuditEntry {
+            at_unix_ms: now_unix_ms,
+            holder: grant.holder.clone(),
+            capability: grant.capability.clone(),
+            event: AuditEvent::Granted { expires_at_unix_ms },
+        };
+        match self.position(holder, &grant.capability) {
+            Some(index) => self.grants[index] = grant,
+            None => self.grants.push(grant),
+        }
+        self.grants.sort_by(|left, right| {
+            (&left.holder, &left.capability).cmp(&(&right.holder, &right.capability))
+        });
+        Ok(entry)
+    }
+
+    pub fn revoke(
+        &mut self,
+        holder: &str,
+        capability: &Capability,
+        now_unix_ms: u64,
+    ) -> Result<(Grant, AuditEntry), GrantError> {
+        let Some(index) = self.position(holder, capability) else {
+            return Err(GrantError::UnknownGrant {
+                holder: holder.to_string(),
+                capability: capability.clone(),
+            });
+        };
+        let grant = self.grants.remove(index);
+        let entry = AuditEntry {
+            at_unix_ms: now_unix_ms,
+            holder: grant.holder.clone(),
+            capability: grant.capability.clone(),
+            event: AuditEvent::Revoked,
+        };
+        Ok((grant, entry))
+    }
+
+    /// Drops grants that expired by `now_unix_ms`.
+    pub fn remove_expired(&mut self, now_unix_ms: u64) -> Vec<AuditEntry> {
+        let (expired, live) = std::mem::take(&mut self.grants)
+            .into_iter()
+            .partition::<Vec<_>, _>(|grant| grant.is_expired(now_unix_ms));
+        self.grants = live;
+        expired
+            .into_iter()
+            .map(|grant| AuditEntry {
+                at_unix_ms: now_unix_ms,
+                holder: grant.holder,
+                capability: grant.capability,
+                event: AuditEvent::Expired,
+            })
+            .collect()
+    }
+
+    /// Decides `request` for `holder`; the entry's event is `Allowed` or `Denied`.
+    pub fn check(&self, holder: &str, request: &Capability, now_unix_ms: u64) -> AuditEntry {
+        let event = self
+            .held_by(holder)
+            .find(|grant| !grant.is_expired(now_unix_ms) && grant.capability.covers(request))
+            .map_or(AuditEvent::Denied, |grant| AuditEvent::Allowed {
+                grant: grant.capability.clone(),
+            });
+        AuditEntry {
+            at_unix_ms: now_unix_ms,
+            holder: holder.to_string(),
+            capability: request.clone(),
+            event,
+        }
+    }
+
+    fn position(&self, holder: &str, capability: &Capability) -> Option<usize> {
+        self.grants
+            .iter()
+            .position(|grant| grant.holder == holder && &grant.capability == capability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap(value: &str) -> Capability {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn checks_follow_grants_revocation_and_expiry() {
+        let mut store = GrantStore::default();
+        let request = cap("fs.read:/home/ada/notes/todo.md");
+        assert_eq!(store.check("ui", &request, 0).event, AuditEvent::Denied);
+
+        store
+            .grant("ui", cap("fs.read:/home/ada"), 10, Some(100))
+            .unwrap();
+        store
+            .grant("ui", cap("fs.read:/home/ada"), 20, Some(200))
+            .unwrap();
+        assert_eq!(store.grants().len(), 1);
+        assert_eq!(
+            store.check("ui", &request, 150).event,
+            AuditEvent::Allowed {
+                grant: cap("fs.read:/home/ada")
+            }
+        );
+        assert_eq!(
+            store.check("capsule", &request, 150).event,
+            AuditEvent::Denied
+        );
+        assert_eq!(store.check("ui", &request, 200).event, AuditEvent::Denied);
+
+        assert_eq!(store.remove_expired(200).len(), 1);
+        assert!(store.grants().is_empty());
+        assert!(matches!(
+            store.revoke("ui", &cap("fs.read:/home/ada"), 210),
+            Err(GrantError::UnknownGrant { .. })
+        ));
+        assert!(matches!(
+            store.grant("ui", cap("browser.navigate"), 300, Some(300)),
+            Err(GrantError::AlreadyExpired { .. })
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+//! A tab's back/forward list as the host persists it.
+//!
+//! CEF cannot be handed navigation entries, so a recreated browser only knows the page it
+//! was created at. The host keeps the list it recreated the tab from and lays the
+//! browser's own entries over it with `TabHistory::rebased`.
+
+use serde::{Deserialize, Serialize};
+
+/// Most entries kept per tab; the oldest back entries go first.
+pub const MAX_TAB_HISTORY_ENTRIES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// `index` is the entry the tab shows; the entries before it are its back list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TabHistory {
+    pub entries: Vec<HistoryEntry>,
+    pub index: usize,
+}
+
+impl TabHistory {
+    /// `None` without entries. Clamps `index` and keeps at most `MAX_TAB_HISTORY_ENTRIES`,
+    /// dropping the oldest back entries, then the furthest forward ones.
+    pub fn normalized(mut self) -> Option<Self> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.index = self.index.min(self.entries.len() - 1);
+        let excess = self.entries.len().saturating_sub(MAX_TAB_HISTORY_ENTRIES);
+        let dropped_back = excess.min(self.index);
+        self.entries.drain(..dropped_back);
+        self.index -= dropped_back;
+        self.entries.truncate(MAX_TAB_HISTORY_ENTRIES);
+        Some(self)
+    }
+
+    pub fn current(&self) -> Option<&HistoryEntry> {
+        self.entries.get(self.index)
+    }
+
+    pub fn back(&self) -> &[HistoryEntry] {
+        &self.entries[..self.index.min(self.entries.len())]
+    }
+
+    pub fn forward(&self) -> &[HistoryEntry] {
+        self.entries.get(self.index + 1..).unwrap_or_default()
+    }
+
+    /// The list after navigating to `entry`: the forward entries are dropped.
+    pub fn pushed(&self, entry: HistoryEntry) -> Self {
+        let mut entries = self.entries[..(self.index + 1).min(self.entries.len())].to_vec();
+        entries.push(entry);
+        Self {
+            index: entries.len() - 1,
+            entries,
+        }
+    }
+
+    /// The same list with the current entry moved by `offset`; `None` past either end.
+    pub fn stepped(&self, offset: isize) -> Option<Self> {
+        let index = self.index.checked_add_signed(offset)?;
+        (index < self.entries.len()).then(|| Self {
+            entries: self.entries.clone(),
+            index,
+        })
+    }
+
+    /// `live`, the entries of a browser created at `restored`'s current entry, inside the
+    /// list it was restored from. The restored forward entries only last while the browser
+    /// has not navigated away from its first page.
+    pub fn rebased(restored: &Self, live: &Self) -> Self {
+        let back = restored.back();
+        let mut entries = back.to_vec();
+        entries.extend(live.entries.iter().cloned());
+        if live.entries.len() == 1 {
+            entries.extend(restored.forward().iter().cloned());
+        }
+        Self {
+            entries,
+            index: back.len() + live.index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(urls: &[&str], index: usize) -> TabHistory {
+        TabHistory {
+            entries: urls
+                .iter()
+                .map(|url| HistoryEntry {
+                    url: url.to_string(),
+                    title: None,
+                })
+                .collect(),
+            index,
+        }
+    }
+
+    #[test]
+    fn recreated_browsers_are_laid_over_the_restored_list() {
+        let restored = history(&["a", "b", "c", "d"], 1);
+        assert_eq!((restored.back().len(), restored.forward().len()), (1, 2));
+
+        let fresh = history(&["b"], 0);
+        assert_eq!(
+            TabHistory::rebased(&restored, &fresh),
+            history(&["a", "b", "c", "d"], 1)
+        );
+        let navigated = history(&["b", "x"], 1);
+        assert_eq!(
+            TabHistory::rebased(&restored, &navigated),
+            history(&["a", "b", "x"], 2)
+        );
+        assert_eq!(
+            TabHistory::rebased(&TabHistory::default(), &navigated),
+            navigated
+        );
+
+        let entry = HistoryEntry {
+            url: "x".into(),
+            title: None,
+        };
+        assert_eq!(restored.pushed(entry), history(&["a", "b", "x"], 2));
+        assert_eq!(
+            restored.stepped(-1),
+            Some(history(&["a", "b", "c", "d"], 0))
+        );
+        assert_eq!(restored.stepped(-2), None);
+        assert_eq!(restored.stepped(3), None);
+    }
+
+    #[test]
+    fn normalizing_clamps_the_index_and_drops_old_entries_first() {
+        assert_eq!(TabHistory::default().normalized(), None);
+        assert_eq!(
+            history(&["a", "b"], 5).normalized(),
+            Some(history(&["a", "b"], 1))
+        );
+
+        let urls = (0..MAX_TAB_HISTORY_ENTRIES + 10)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        let urls = urls.iter().map(String::as_str).collect::<Vec<_>>();
+        let long = history(&urls, MAX_TAB_HISTORY_ENTRIES + 5)
+            .normalized()
+            .unwrap();
+        assert_eq!(long.entries.len(), MAX_TAB_HISTORY_ENTRIES);
+        assert_eq!(
+            long.current().unwrap().url,
+            (MAX_TAB_HISTORY_ENTRIES + 5).to_string()
+        );
+        assert_eq!(long.entries[0].url, "10");
+
+        let near_start = history(&urls, 2).normalized().unwrap();
+        assert_eq!(
+            (near_start.index, near_start.entries[0].url.as_str()),
+            (0, "2")
+        );
+        assert_eq!(near_start.entries.len(), MAX_TAB_HISTORY_ENTRIES);
+    }
+}
@@ -1,5 +1,21 @@
 use serde::{Deserialize, Serialize};
 
+pub mod capability;
+pub mod grants;
+pub mod history;
+pub mod protocol;
+pub mod tab_lifecycle;
+pub mod workspace;
+
+pub use capability::{Capability, CapabilityError, CapabilityKind};
+pub use grants::{AuditEntry, AuditEvent, Grant, GrantError, GrantStore};
+pub use history::{HistoryEntry, TabHistory};
+
+pub use workspace::{
+    FileRecord, GroupRecord, ImportMode, TabRecord, WorkspaceCommand, WorkspaceEntry,
+    WorkspaceError, WorkspaceResponse, WorkspaceStore,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ItemKind {
@@ -17,16 +33,3 @@ pub struct WorkspaceItem {
     pub title: String,
     pub order: i32,
 }
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub enum Capability {
-    BrowserNavigate,
-    BrowserCookiesRead,
-    BrowserCookiesWrite,
-    FsRead,
-    FsWrite,
-    TerminalExec,
-    McpToolInvoke,
-    NetworkFetch,
-}
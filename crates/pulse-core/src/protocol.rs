@@ -0,0 +1,523 @@
+//! JSON messages between the trusted UI's renderer and the host browser process.
+//!
+//! Every request is `{ "version", "id", "command": { "type", ... } }` and is answered by a
+//! response with the same `id`. The host decodes requests with `HostRequest::decode`, so
+//! anything that does not match these types is rejected before it runs.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Capability, ImportMode, TabHistory, WorkspaceCommand};
+
+/// Bump when a change would make an older UI or host misread a message.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostRequest {
+    pub version: u32,
+    pub id: u64,
+    pub command: HostCommand,
+}
+
+/// Everything the UI can ask of the host.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum HostCommand {
+    EnsureTab {
+        tab_id: String,
+        #[serde(default)]
+        initial_url: Option<String>,
+        /// Pinned tabs are never discarded.
+        #[serde(default)]
+        pinned: bool,
+    },
+    ActivateTab {
+        tab_id: String,
+    },
+    NavigateTab {
+        tab_id: String,
+        url: String,
+    },
+    CloseTab {
+        tab_id: String,
+    },
+    SetTabPinned {
+        tab_id: String,
+        pinned: bool,
+    },
+    /// Registers a restored session's tabs without loading them. Only `active_tab_id` and
+    /// the first `prefetch_tab_ids` (up to the host's prefetch limit) get a browser; the
+    /// others stay discarded until `activate-tab`. Tabs the host already has are skipped.
+    RestoreTabs {
+        tabs: Vec<RestoredTab>,
+        #[serde(default)]
+        active_tab_id: Option<String>,
+        #[serde(default)]
+        prefetch_tab_ids: Vec<String>,
+    },
+    BrowserBack {
+        tab_id: String,
+    },
+    BrowserForward {
+        tab_id: String,
+    },
+    BrowserReload {
+        tab_id: String,
+    },
+    BrowserStop {
+        tab_id: String,
+    },
+    SetContentBounds {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    SetContentVisible {
+        visible: bool,
+    },
+    UiReady,
+    LoadState,
+    /// `session` is the UI session payload (`{ version, items, selectedItemId, address }`).
+    SaveState {
+        session: Value,
+    },
+    Workspace {
+        request: WorkspaceCommand,
+    },
+    RecoveryReport,
+    ListSnapshots,
+    RestoreSnapshot {
+        id: String,
+    },
+    ExportBundle {
+        #[serde(default)]
+        path: Option<String>,
+    },
+    ImportBundle {
+        path: String,
+        mode: ImportMode,
+    },
+    ListProfiles,
+    ListGrants,
+    RevokeGrant {
+        holder: String,
+        capability: Capability,
+    },
+    CapabilityAudit,
+}
+
+/// A tab of `restore-tabs`, as the UI last saved it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RestoredTab {
+    pub tab_id: String,
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Back/forward list to recreate the tab with; its current entry should be `url`.
+    #[serde(default)]
+    pub history: Option<TabHistory>,
+}
+
+impl HostCommand {
+    /// The `type` tag, for logs and denials.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::EnsureTab { .. } => "ensure-tab",
+            Self::ActivateTab { .. } => "activate-tab",
+            Self::NavigateTab { .. } => "navigate-tab",
+            Self::CloseTab { .. } => "close-tab",
+            Self::SetTabPinned { .. } => "set-tab-pinned",
+            Self::RestoreTabs { .. } => "restore-tabs",
+            Self::BrowserBack { .. } => "browser-back",
+            Self::BrowserForward { .. } => "browser-forward",
+            Self::BrowserReload { .. } => "browser-reload",
+            Self::BrowserStop { .. } => "browser-stop",
+            Self::SetContentBounds { .. } => "set-content-bounds",
+            Self::SetContentVisible { .. } => "set-content-visible",
+            Self::UiReady => "ui-ready",
+            Self::LoadState => "load-state",
+            Self::SaveState { .. } => "save-state",
+            Self::Workspace { .. } => "workspace",
+            Self::RecoveryReport => "recovery-report",
+            Self::ListSnapshots => "list-snapshots",
+            Self::RestoreSnapshot { .. } => "restore-snapshot",
+            Self::ExportBundle { .. } => "export-bundle",
+            Self::ImportBundle { .. } => "import-bundle",
+            Self::ListProfiles => "list-profiles",
+            Self::ListGrants => "list-grants",
+            Self::RevokeGrant { .. } => "revoke-grant",
+            Self::CapabilityAudit => "capability-audit",
+        }
+    }
+}
+
+/// A request that could not be decoded, with its id when one could be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRequest {
+    pub id: Option<u64>,
+    pub error: HostError,
+}
+
+impl HostRequest {
+    pub fn new(id: u64, command: HostCommand) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            command,
+        }
+    }
+
+    /// Parses and validates one request. The version is checked before the command, so a
+    /// newer UI gets `unsupported-version` rather than a confusing shape error.
+    pub fn decode(json: &str) -> Result<Self, RejectedRequest> {
+        let value: Value = serde_json::from_str(json).map_err(|error| RejectedRequest {
+            id: None,
+            error: HostError::new(
+                HostErrorCode::InvalidRequest,
+                format!("request is not JSON: {error}"),
+            ),
+        })?;
+        let id = value.get("id").and_then(Value::as_u64);
+        let reject = |code, message: String| RejectedRequest {
+            id,
+            error: HostError::new(code, message),
+        };
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version == u64::from(PROTOCOL_VERSION) => {}
+            Some(version) => {
+                return Err(reject(
+                    HostErrorCode::UnsupportedVersion,
+                    format!(
+                        "protocol version {version} is not supported; this host speaks {PROTOCOL_VERSION}"
+                    ),
+                ));
+            }
+            None => {
+                return Err(reject(
+                    HostErrorCode::InvalidRequest,
+                    String::from("request has no protocol version"),
+                ));
+            }
+        }
+        serde_json::from_value(value).map_err(|error| {
+            reject(
+                HostErrorCode::InvalidRequest,
+                format!("invalid request: {error}"),
+            )
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("host requests always serialize")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostResponse {
+    pub version: u32,
+    pub id: u64,
+    #[serde(flatten)]
+    pub outcome: HostOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum HostOutcome {
+    /// `value` is the command's result; `null` for commands without one.
+    Ok {
+        #[serde(default)]
+        value: Value,
+    },
+    Error {
+        error: HostError,
+    },
+}
+
+impl HostResponse {
+    pub fn new(id: u64, outcome: HostOutcome) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            outcome,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("host responses always serialize")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostErrorCode {
+    InvalidRequest,
+    UnsupportedVersion,
+    CapabilityDenied,
+    /// The command names a tab the host does not have.
+    UnknownTab,
+    Failed,
+    /// Set by the renderer when no response arrived in time; the command may still run.
+    Timeout,
+}
+
+/// Why a request did not succeed. Denials name the capability that was missing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HostError {
+    pub code: HostErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capability: Option<Capability>,
+}
+
+impl HostError {
+    pub fn new(code: HostErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            capability: None,
+        }
+    }
+}
+
+/// One host notification. `seq` grows by one per event from the same host process, so the
+/// UI can tell when it missed one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostEventMessage {
+    pub version: u32,
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: HostEvent,
+}
+
+impl HostEventMessage {
+    pub fn new(seq: u64, event: HostEvent) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            seq,
+            event,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("host events always serialize")
+    }
+}
+
+/// Where a tab's navigation stands, as last reported by CEF.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NavState {
+    pub is_loading: bool,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+}
+
+/// Where a tab is in its lifecycle. Background tabs keep their browser and renderer; a
+/// discarded tab has closed them, or never had them since `restore-tabs`, and keeps only its
+/// URL, title and scroll position until `activate-tab` recreates it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TabLifecycle {
+    Active,
+    Background,
+    Discarded,
+}
+
+/// What the host tells the UI without being asked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum HostEvent {
+    /// The tab's main frame committed a new URL or its page changed title.
+    TabUpdated {
+        tab_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+    /// Also sent on `activate-tab`, so the UI starts from the tab's current state.
+    LoadingStateChanged {
+        tab_id: String,
+        #[serde(flatten)]
+        nav: NavState,
+    },
+    /// The main frame started loading `url`.
+    LoadStarted { tab_id: String, url: String },
+    /// The main frame finished loading. `http_status_code` is 0 for non-HTTP loads.
+    LoadFinished {
+        tab_id: String,
+        url: String,
+        http_status_code: i32,
+    },
+    /// `progress` runs from 0.0 to 1.0.
+    LoadProgress { tab_id: String, progress: f64 },
+    /// `icon_url` is the cached icon's `pulse://favicon/<sha256>` URL, `None` when the page
+    /// has no icon.
+    FaviconChanged {
+        tab_id: String,
+        icon_url: Option<String>,
+    },
+    /// `error_code` is Chromium's net error, e.g. -105 for a failed DNS lookup.
+    LoadFailed {
+        tab_id: String,
+        url: String,
+        error_code: i32,
+        error_text: String,
+    },
+    /// The tab's renderer process died; the tab stays open showing nothing.
+    TabCrashed {
+        tab_id: String,
+        error_code: i32,
+        reason: String,
+    },
+    /// The tab's browser closed without the UI asking, for example through `window.close()`.
+    TabClosed { tab_id: String },
+    /// The tab became active, went to the background, or was discarded to save memory.
+    TabLifecycleChanged {
+        tab_id: String,
+        lifecycle: TabLifecycle,
+    },
+    /// The tab's back/forward list changed. Entries from before the tab was recreated are
+    /// included, so the UI can persist the whole list.
+    TabHistoryChanged { tab_id: String, history: TabHistory },
+    /// A tab's page opened `url` as a new tab (`target=_blank`, or `window.open` without
+    /// window features). The host opened nothing; the UI adds the tab under its opener and
+    /// creates it with `ensure-tab`.
+    TabOpenRequested {
+        opener_tab_id: String,
+        url: String,
+        /// Opened in the background (middle click), so the opener stays active.
+        background: bool,
+    },
+    /// A page opened a popup window, which the host keeps out of the workspace because its
+    /// page may need `window.opener` (sign-in flows). `opener_tab_id` is `None` for popups of
+    /// the UI itself or of other popups.
+    PopupOpened {
+        opener_tab_id: Option<String>,
+        url: Option<String>,
+    },
+    /// URLs passed on the command line, including launches forwarded by a second process.
+    OpenUrls { urls: Vec<String> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_validated_against_the_command_types() {
+        let request = HostRequest::decode(
+            r#"{"version":1,"id":7,"command":{"type":"set-content-bounds","x":0,"y":40,"width":800,"height":600}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.command,
+            HostCommand::SetContentBounds {
+                x: 0,
+                y: 40,
+                width: 800,
+                height: 600
+            }
+        );
+        assert_eq!(HostRequest::decode(&request.encode()), Ok(request));
+
+        let workspace = HostRequest::decode(
+            r#"{"version":1,"id":8,"command":{"type":"workspace","request":{"op":"get-item","id":"tab-1"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            workspace.command,
+            HostCommand::Workspace {
+                request: WorkspaceCommand::GetItem { id: "tab-1".into() }
+            }
+        );
+
+        let restore = HostRequest::decode(
+            r#"{"version":1,"id":9,"command":{"type":"restore-tabs","tabs":[{"tab_id":"tab-1","url":"https://example.com/","pinned":true}],"active_tab_id":"tab-1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            restore.command,
+            HostCommand::RestoreTabs {
+                tabs: vec![RestoredTab {
+                    tab_id: "tab-1".into(),
+                    url: "https://example.com/".into(),
+                    title: None,
+                    pinned: true,
+                    history: None,
+                }],
+                active_tab_id: Some("tab-1".into()),
+                prefetch_tab_ids: Vec::new(),
+            }
+        );
+
+        for (json, id, code) in [
+            ("not json", None, HostErrorCode::InvalidRequest),
+            (
+                r#"{"version":2,"id":3,"command":{"type":"ui-ready"}}"#,
+                Some(3),
+                HostErrorCode::UnsupportedVersion,
+            ),
+            (
+                r#"{"version":1,"id":4,"command":{"type":"set-content-bounds","x":"0"}}"#,
+                Some(4),
+                HostErrorCode::InvalidRequest,
+            ),
+            (
+                r#"{"version":1,"id":5,"command":{"type":"close-tab","tab_id":"a","extra":1}}"#,
+                Some(5),
+                HostErrorCode::InvalidRequest,
+            ),
+        ] {
+            let rejected = HostRequest::decode(json).unwrap_err();
+            assert_eq!((rejected.id, rejected.error.code), (id, code), "{json}");
+        }
+    }
+
+    #[test]
+    fn events_are_flat_objects_tagged_with_their_type() {
+        let message = HostEventMessage::new(
+            3,
+            HostEvent::LoadingStateChanged {
+                tab_id: "tab-1".into(),
+                nav: NavState {
+                    is_loading: true,
+                    ..NavState::default()
+                },
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "version": 1,
+                "seq": 3,
+                "type": "loading-state-changed",
+                "tab_id": "tab-1",
+                "is_loading": true,
+                "can_go_back": false,
+                "can_go_forward": false,
+            })
+        );
+        assert_eq!(
+            serde_json::from_str::<HostEventMessage>(&message.encode()).unwrap(),
+            message
+        );
+
+        let discarded = HostEvent::TabLifecycleChanged {
+            tab_id: "tab-2".into(),
+            lifecycle: TabLifecycle::Discarded,
+        };
+        assert_eq!(
+            serde_json::to_value(&discarded).unwrap(),
+            serde_json::json!({
+                "type": "tab-lifecycle-changed",
+                "tab_id": "tab-2",
+                "lifecycle": "discarded",
+            })
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Which background tabs to discard: close their browser and renderer, and keep only what
+//! recreating them on `activate-tab` needs.
+//!
+//! The policy is read from host settings in the workspace store at startup:
+//! `tabs.max-live-tabs` caps how many tabs keep a browser, and
+//! `tabs.discard-after-minutes` discards tabs left in the background that long (0 never
+//! does). Settings that do not parse, and a live-tab cap of 0, keep their default. The
+//! active tab, pinned tabs and tabs playing audio are never discarded.
+//! A picked tab whose renderer crashed, or has not replied within `SNAPSHOT_REPLY_TIMEOUT`
+//! by the next sweep, is discarded without its scroll position.
+//! `tabs.restore-prefetch` caps how many tabs besides the active one `restore-tabs` loads
+//! up front.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const MAX_LIVE_TABS_SETTING: &str = "tabs.max-live-tabs";
+pub const DISCARD_AFTER_MINUTES_SETTING: &str = "tabs.discard-after-minutes";
+pub const RESTORE_PREFETCH_SETTING: &str = "tabs.restore-prefetch";
+const DEFAULT_MAX_LIVE_TABS: usize = 16;
+const DEFAULT_DISCARD_AFTER_MINUTES: u64 = 30;
+const DEFAULT_RESTORE_PREFETCH: usize = 2;
+/// How often the host checks background tabs against the policy.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a picked tab's renderer has to reply before the sweep discards the tab anyway.
+pub const SNAPSHOT_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscardPolicy {
+    /// At least 1: the active tab always keeps its browser.
+    pub max_live_tabs: usize,
+    /// `None` when tabs are only discarded to stay under `max_live_tabs`.
+    pub idle_timeout: Option<Duration>,
+    /// Tabs besides the active one that `restore-tabs` loads before they are activated.
+    pub restore_prefetch: usize,
+}
+
+impl Default for DiscardPolicy {
+    fn default() -> Self {
+        Self {
+            max_live_tabs: DEFAULT_MAX_LIVE_TABS,
+            idle_timeout: Some(Duration::from_secs(DEFAULT_DISCARD_AFTER_MINUTES * 60)),
+            restore_prefetch: DEFAULT_RESTORE_PREFETCH,
+        }
+    }
+}
+
+impl DiscardPolicy {
+    /// The policy, and the keys of the settings it ignored because they were unreadable or
+    /// out of range. Unset and ignored settings keep their default.
+    pub fn from_settings(settings: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut policy = Self::default();
+        let mut ignored = Vec::new();
+        if let Some(max_live_tabs) =
+            parse_setting::<usize>(settings, MAX_LIVE_TABS_SETTING, &mut ignored)
+        {
+            if max_live_tabs > 0 {
+                policy.max_live_tabs = max_live_tabs;
+            } else {
+                ignored.push(MAX_LIVE_TABS_SETTING.to_string());
+            }
+        }
+        if let Some(minutes) =
+            parse_setting::<u64>(settings, DISCARD_AFTER_MINUTES_SETTING, &mut ignored)
+        {
+            policy.idle_timeout =
+                (minutes > 0).then(|| Duration::from_secs(minutes.saturating_mul(60)));
+        }
+        if let Some(prefetch) =
+            parse_setting::<usize>(settings, RESTORE_PREFETCH_SETTING, &mut ignored)
+        {
+            policy.restore_prefetch = prefetch;
+        }
+        (policy, ignored)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScrollPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl ScrollPosition {
+    /// Script that puts a recreated page back where the discarded one was.
+    pub fn restore_script(self) -> String {
+        format!("window.scrollTo({}, {});", self.x, self.y)
+    }
+}
+
+/// The host's request to a background tab's renderer.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TabSnapshotRequest {
+    /// Discard the tab after the reply unless it is playing audio.
+    pub discard: bool,
+}
+
+/// A background tab's renderer's reply to `TabSnapshotRequest`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TabSnapshot {
+    pub scroll: ScrollPosition,
+    /// A media element of the page is playing with sound.
+    pub audible: bool,
+    /// Echoes the request.
+    pub discard: bool,
+}
+
+/// A tab that still has a browser, as the policy sees it.
+#[derive(Debug, Clone)]
+pub struct LiveTab {
+    pub tab_id: String,
+    pub active: bool,
+    pub pinned: bool,
+    /// As of the tab's last snapshot.
+    pub audible: bool,
+    /// Time since the tab was last active.
+    pub idle_for: Duration,
+}
+
+/// Tabs `restore-tabs` loads: the active one, then the requested prefetch tabs that are
+/// restored, up to the policy's prefetch limit and never more than `max_live_tabs`.
+/// `restored` are the tabs the command registered.
+pub fn restore_load_set(
+    policy: &DiscardPolicy,
+    restored: &[String],
+    active_tab_id: Option<&str>,
+    prefetch_tab_ids: &[String],
+) -> Vec<String> {
+    let mut load = active_tab_id
+        .filter(|tab_id| restored.iter().any(|restored| restored == tab_id))
+        .map(String::from)
+        .into_iter()
+        .collect::<Vec<_>>();
+    let limit = (load.len() + policy.restore_prefetch).min(policy.max_live_tabs);
+    for tab_id in prefetch_tab_ids {
+        if load.len() >= limit {
+            break;
+        }
+        if restored.contains(tab_id) && !load.contains(tab_id) {
+            load.push(tab_id.clone());
+        }
+    }
+    load
+}
+
+/// Tabs to discard, longest idle first: every tab idle past the timeout, then the least
+/// recently used until at most `max_live_tabs` remain.
+pub fn discard_candidates(policy: &DiscardPolicy, tabs: &[LiveTab]) -> Vec<String> {
+    let mut discardable = tabs
+        .iter()
+        .filter(|tab| !tab.active && !tab.pinned && !tab.audible)
+        .collect::<Vec<_>>();
+    discardable.sort_by_key(|tab| Reverse(tab.idle_for));
+
+    let excess = tabs.len().saturating_sub(policy.max_live_tabs);
+    discardable
+        .into_iter()
+        .enumerate()
+        .take_while(|(index, tab)| {
+            *index < excess
+                || policy
+                    .idle_timeout
+                    .is_some_and(|timeout| tab.idle_for >= timeout)
+        })
+        .map(|(_, tab)| tab.tab_id.clone())
+        .collect()
+}
+
+fn parse_setting<T: std::str::FromStr>(
+    settings: &BTreeMap<String, String>,
+    key: &str,
+    ignored: &mut Vec<String>,
+) -> Option<T> {
+    let parsed = settings.get(key)?.trim().parse().ok();
+    if parsed.is_none() {
+        ignored.push(key.to_string());
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(tab_id: &str, idle_minutes: u64) -> LiveTab {
+        LiveTab {
+            tab_id: tab_id.to_string(),
+            active: false,
+            pinned: false,
+            audible: false,
+            idle_for: Duration::from_secs(idle_minutes * 60),
+        }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn active_pinned_and_audible_tabs_are_never_discarded() {
+        let policy = DiscardPolicy {
+            max_live_tabs: 1,
+            ..DiscardPolicy::default()
+        };
+        let tabs = [
+            LiveTab {
+                active: true,
+                ..tab("active", 0)
+            },
+            LiveTab {
+                pinned: true,
+                ..tab("pinned", 120)
+            },
+            LiveTab {
+                audible: true,
+                ..tab("audible", 120)
+            },
+        ];
+        assert!(discard_candidates(&policy, &tabs).is_empty());
+    }
+
+    #[test]
+    fn idle_tabs_and_the_least_recently_used_excess_are_discarded() {
+        let policy = DiscardPolicy {
+            max_live_tabs: 3,
+            ..DiscardPolicy::default()
+        };
+        let tabs = [
+            LiveTab {
+                active: true,
+                ..tab("active", 0)
+            },
+            tab("recent", 1),
+            tab("older", 5),
+            tab("oldest", 10),
+            tab("idle", 45),
+        ];
+        // Two over the cap: the idle tab and the next least recently used.
+        assert_eq!(discard_candidates(&policy, &tabs), ids(&["idle", "oldest"]));
+
+        let roomy = DiscardPolicy {
+            max_live_tabs: 16,
+            ..DiscardPolicy::default()
+        };
+        assert_eq!(discard_candidates(&roomy, &tabs), ids(&["idle"]));
+        let no_timeout = DiscardPolicy {
+            idle_timeout: None,
+            ..roomy
+        };
+        assert!(discard_candidates(&no_timeout, &tabs).is_empty());
+    }
+
+    #[test]
+    fn unreadable_or_out_of_range_settings_keep_their_default() {
+        let settings = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let (policy, ignored) = DiscardPolicy::from_settings(&settings(&[
+            (MAX_LIVE_TABS_SETTING, "0"),
+            (DISCARD_AFTER_MINUTES_SETTING, "-5"),
+            (RESTORE_PREFETCH_SETTING, "lots"),
+        ]));
+        assert_eq!(policy, DiscardPolicy::default());
+        assert_eq!(
+            ignored,
+            ids(&[
+                MAX_LIVE_TABS_SETTING,
+                DISCARD_AFTER_MINUTES_SETTING,
+                RESTORE_PREFETCH_SETTING
+            ])
+        );
+
+        let (policy, ignored) = DiscardPolicy::from_settings(&settings(&[
+            (MAX_LIVE_TABS_SETTING, " 4 "),
+            (DISCARD_AFTER_MINUTES_SETTING, "0"),
+            (RESTORE_PREFETCH_SETTING, "7"),
+        ]));
+        assert!(ignored.is_empty());
+        assert_eq!(
+            policy,
+            DiscardPolicy {
+                max_live_tabs: 4,
+                idle_timeout: None,
+                restore_prefetch: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn restore_loads_known_tabs_once_up_to_the_live_tab_limit() {
+        let policy = DiscardPolicy {
+            max_live_tabs: 3,
+            restore_prefetch: 5,
+            ..DiscardPolicy::default()
+        };
+        let restored = ids(&["a", "b", "c", "d"]);
+        let prefetch = ids(&["a", "unknown", "b", "b", "c", "d"]);
+        assert_eq!(
+            restore_load_set(&policy, &restored, Some("a"), &prefetch),
+            ids(&["a", "b", "c"])
+        );
+        assert_eq!(
+            restore_load_set(&policy, &restored, Some("unknown"), &prefetch),
+            ids(&["a", "b", "c"])
+        );
+
+        let one = DiscardPolicy {
+            restore_prefetch: 1,
+            ..policy
+        };
+        assert_eq!(
+            restore_load_set(&one, &restored, Some("c"), &prefetch),
+            ids(&["c", "a"])
+        );
+    }
+}
@@ -0,0 +1,925 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+use super::{ItemKind, TabHistory, WorkspaceItem};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TabRecord {
+    pub url: String,
+    /// Tab whose page opened this one with `window.open` or `target=_blank`. The opener may
+    /// have been closed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opener_id: Option<String>,
+    /// Pinned tabs stay loaded when the host discards background tabs.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Back/forward list the host last reported; restored when the tab is recreated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<TabHistory>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GroupRecord {
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileRecord {
+    pub path: String,
+}
+
+/// One item joined with its kind-specific table row.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkspaceEntry {
+    pub item: WorkspaceItem,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab: Option<TabRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WorkspaceError {
+    #[error("workspace item '{0}' already exists")]
+    DuplicateItem(String),
+    #[error("workspace item '{0}' does not exist")]
+    UnknownItem(String),
+    #[error("workspace item id must not be empty")]
+    EmptyId,
+    #[error("parent '{parent_id}' of workspace item '{id}' does not exist")]
+    MissingParent { id: String, parent_id: String },
+    #[error("workspace item '{id}' cannot be placed under '{parent_id}'")]
+    InvalidParent { id: String, parent_id: String },
+    #[error("placing workspace item '{id}' under '{parent_id}' would create a cycle")]
+    Cycle { id: String, parent_id: String },
+    #[error("workspace items '{first}' and '{second}' share order {order} under the same parent")]
+    DuplicateOrder {
+        first: String,
+        second: String,
+        order: i32,
+    },
+    #[error("workspace item '{id}' of kind {kind:?} is missing its {table} record")]
+    MissingRecord {
+        id: String,
+        kind: ItemKind,
+        table: &'static str,
+    },
+    #[error("{table} record '{id}' does not belong to a matching workspace item")]
+    OrphanRecord { id: String, table: &'static str },
+}
+
+/// Host-owned workspace tables.
+///
+/// `items` holds the tree (`parent_id`, `order`); `tabs`, `groups` and `files` hold
+/// kind-specific rows keyed by item id; `settings` is a flat key/value table.
+/// Groups hold any item and a tab holds only the tabs it opened. Mutations keep sibling
+/// orders unique and contiguous so tree order stays deterministic.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkspaceStore {
+    #[serde(default)]
+    items: BTreeMap<String, WorkspaceItem>,
+    #[serde(default)]
+    tabs: BTreeMap<String, TabRecord>,
+    #[serde(default)]
+    groups: BTreeMap<String, GroupRecord>,
+    #[serde(default)]
+    files: BTreeMap<String, FileRecord>,
+    #[serde(default)]
+    settings: BTreeMap<String, String>,
+}
+
+impl WorkspaceStore {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn item(&self, id: &str) -> Option<&WorkspaceItem> {
+        self.items.get(id)
+    }
+
+    pub fn tab(&self, id: &str) -> Option<&TabRecord> {
+        self.tabs.get(id)
+    }
+
+    pub fn group(&self, id: &str) -> Option<&GroupRecord> {
+        self.groups.get(id)
+    }
+
+    pub fn file(&self, id: &str) -> Option<&FileRecord> {
+        self.files.get(id)
+    }
+
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings.get(key).map(String::as_str)
+    }
+
+    pub fn settings(&self) -> &BTreeMap<String, String> {
+        &self.settings
+    }
+
+    pub fn entry(&self, id: &str) -> Option<WorkspaceEntry> {
+        let item = self.items.get(id)?;
+        Some(WorkspaceEntry {
+            item: item.clone(),
+            tab: self.tabs.get(id).cloned(),
+            group: self.groups.get(id).cloned(),
+            file: self.files.get(id).cloned(),
+        })
+    }
+
+    /// Children of `parent_id` (roots when `None`), sorted by `order`.
+    pub fn children(&self, parent_id: Option<&str>) -> Vec<&WorkspaceItem> {
+        let mut children = self
+            .items
+            .values()
+            .filter(|item| item.parent_id.as_deref() == parent_id)
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.id.cmp(&b.id)));
+        children
+    }
+
+    /// All entries in depth-first tree order.
+    pub fn entries(&self) -> Vec<WorkspaceEntry> {
+        let mut ordered = Vec::with_capacity(self.items.len());
+        let mut stack = self.children(None);
+        stack.reverse();
+
+        while let Some(item) = stack.pop() {
+            if let Some(entry) = self.entry(&item.id) {
+                ordered.push(entry);
+            }
+            let mut children = self.children(Some(&item.id));
+            children.reverse();
+            stack.extend(children);
+        }
+
+        ordered
+    }
+
+    pub fn browser_tabs(&self) -> impl Iterator<Item = (&WorkspaceItem, &TabRecord)> {
+        self.items
+            .values()
+            .filter(|item| item.kind == ItemKind::BrowserTab)
+            .filter_map(|item| self.tabs.get(&item.id).map(|tab| (item, tab)))
+    }
+
+    /// Inserts an entry at `entry.item.order` among its siblings, shifting later siblings.
+    pub fn insert(&mut self, entry: WorkspaceEntry) -> Result<(), WorkspaceError> {
+        let WorkspaceEntry {
+            mut item,
+            tab,
+            group,
+            file,
+        } = entry;
+
+        if item.id.trim().is_empty() {
+            return Err(WorkspaceError::EmptyId);
+        }
+        if self.items.contains_key(&item.id) {
+            return Err(WorkspaceError::DuplicateItem(item.id));
+        }
+        if let Some(parent_id) = item.parent_id.as_deref() {
+            self.check_parent(&item.id, &item.kind, parent_id)?;
+        }
+
+        match item.kind {
+            ItemKind::BrowserTab => {
+                let Some(tab) = tab else {
+                    return Err(missing_record(&item, "tabs"));
+                };
+                self.tabs.insert(item.id.clone(), tab);
+            }
+            ItemKind::FileRef => {
+                let Some(file) = file else {
+                    return Err(missing_record(&item, "files"));
+                };
+                self.files.insert(item.id.clone(), file);
+            }
+            ItemKind::Group => {
+                self.groups
+                    .insert(item.id.clone(), group.unwrap_or_default());
+            }
+            ItemKind::CapsuleView => {}
+        }
+
+        let parent_id = item.parent_id.clone();
+        let position = item.order;
+        item.order = i32::MAX;
+        let id = item.id.clone();
+        self.items.insert(id.clone(), item);
+        self.place(&id, parent_id.as_deref(), position);
+        Ok(())
+    }
+
+    pub fn set_title(&mut self, id: &str, title: &str) -> Result<(), WorkspaceError> {
+        let item = self
+            .items
+            .get_mut(id)
+            .ok_or_else(|| WorkspaceError::UnknownItem(id.to_string()))?;
+        item.title = title.to_string();
+        Ok(())
+    }
+
+    pub fn set_tab_url(&mut self, id: &str, url: &str) -> Result<(), WorkspaceError> {
+        let tab = self
+            .tabs
+            .get_mut(id)
+            .ok_or_else(|| WorkspaceError::UnknownItem(id.to_string()))?;
+        tab.url = url.to_string();
+        Ok(())
+    }
+
+    pub fn set_group_collapsed(&mut self, id: &str, collapsed: bool) -> Result<(), WorkspaceError> {
+        let group = self
+            .groups
+            .get_mut(id)
+            .ok_or_else(|| WorkspaceError::UnknownItem(id.to_string()))?;
+        group.collapsed = collapsed;
+        Ok(())
+    }
+
+    /// Moves `id` under `parent_id` at sibling position `order`.
+    pub fn move_item(
+        &mut self,
+        id: &str,
+        parent_id: Option<&str>,
+        order: i32,
+    ) -> Result<(), WorkspaceError> {
+        let Some(item) = self.items.get(id) else {
+            return Err(WorkspaceError::UnknownItem(id.to_string()));
+        };
+        let previous_parent = item.parent_id.clone();
+
+        if let Some(parent_id) = parent_id {
+            self.check_parent(id, &item.kind, parent_id)?;
+            if parent_id == id || self.is_ancestor(id, parent_id) {
+                return Err(WorkspaceError::Cycle {
+                    id: id.to_string(),
+                    parent_id: parent_id.to_string(),
+                });
+            }
+        }
+
+        if let Some(item) = self.items.get_mut(id) {
+            item.parent_id = parent_id.map(str::to_string);
+            item.order = i32::MAX;
+        }
+        self.compact(previous_parent.as_deref());
+        self.place(id, parent_id, order);
+        Ok(())
+    }
+
+    /// Removes `id` and its whole subtree. Returns the removed ids.
+    pub fn remove(&mut self, id: &str) -> Result<Vec<String>, WorkspaceError> {
+        let Some(item) = self.items.get(id) else {
+            return Err(WorkspaceError::UnknownItem(id.to_string()));
+        };
+        let parent_id = item.parent_id.clone();
+
+        let mut removed = Vec::new();
+        let mut pending = vec![id.to_string()];
+        while let Some(current) = pending.pop() {
+            pending.extend(
+                self.items
+                    .values()
+                    .filter(|item| item.parent_id.as_deref() == Some(current.as_str()))
+                    .map(|item| item.id.clone()),
+            );
+            self.items.remove(&current);
+            self.tabs.remove(&current);
+            self.groups.remove(&current);
+            self.files.remove(&current);
+            removed.push(current);
+        }
+
+        self.compact(parent_id.as_deref());
+        Ok(removed)
+    }
+
+    pub fn set_setting(&mut self, key: &str, value: &str) {
+        self.settings.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn remove_setting(&mut self, key: &str) -> Option<String> {
+        self.settings.remove(key)
+    }
+
+    /// Checks every table invariant. Stores loaded from disk must pass before use.
+    pub fn validate(&self) -> Result<(), WorkspaceError> {
+        let mut sibling_orders: BTreeMap<(Option<&str>, i32), &str> = BTreeMap::new();
+
+        for (key, item) in &self.items {
+            if item.id.trim().is_empty() {
+                return Err(WorkspaceError::EmptyId);
+            }
+            if key != &item.id {
+                return Err(WorkspaceError::OrphanRecord {
+                    id: key.clone(),
+                    table: "items",
+                });
+            }
+
+            if let Some(parent_id) = item.parent_id.as_deref() {
+                self.check_parent(&item.id, &item.kind, parent_id)?;
+                if self.is_ancestor(&item.id, parent_id) {
+                    return Err(WorkspaceError::Cycle {
+                        id: item.id.clone(),
+                        parent_id: parent_id.to_string(),
+                    });
+                }
+            }
+
+            if let Some(first) =
+                sibling_orders.insert((item.parent_id.as_deref(), item.order), &item.id)
+            {
+                return Err(WorkspaceError::DuplicateOrder {
+                    first: first.to_string(),
+                    second: item.id.clone(),
+                    order: item.order,
+                });
+            }
+
+            match item.kind {
+                ItemKind::BrowserTab if !self.tabs.contains_key(&item.id) => {
+                    return Err(missing_record(item, "tabs"));
+                }
+                ItemKind::FileRef if !self.files.contains_key(&item.id) => {
+                    return Err(missing_record(item, "files"));
+                }
+                _ => {}
+            }
+        }
+
+        check_orphans(&self.items, self.tabs.keys(), ItemKind::BrowserTab, "tabs")?;
+        check_orphans(&self.items, self.groups.keys(), ItemKind::Group, "groups")?;
+        check_orphans(&self.items, self.files.keys(), ItemKind::FileRef, "files")?;
+        Ok(())
+    }
+
+    /// Rewrites every sibling list to contiguous `0..n` orders, keeping relative order.
+    pub fn normalize_orders(&mut self) {
+        let parents = self
+            .items
+            .values()
+            .map(|item| item.parent_id.clone())
+            .collect::<BTreeSet<_>>();
+        for parent_id in parents {
+            self.compact(parent_id.as_deref());
+        }
+    }
+
+    fn check_parent(
+        &self,
+        id: &str,
+        kind: &ItemKind,
+        parent_id: &str,
+    ) -> Result<(), WorkspaceError> {
+        let Some(parent) = self.items.get(parent_id) else {
+            return Err(WorkspaceError::MissingParent {
+                id: id.to_string(),
+                parent_id: parent_id.to_string(),
+            });
+        };
+        match (&parent.kind, kind) {
+            (ItemKind::Group, _) | (ItemKind::BrowserTab, ItemKind::BrowserTab) => Ok(()),
+            _ => Err(WorkspaceError::InvalidParent {
+                id: id.to_string(),
+                parent_id: parent_id.to_string(),
+            }),
+        }
+    }
+
+    fn is_ancestor(&self, ancestor_id: &str, id: &str) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut current = self
+            .items
+            .get(id)
+            .and_then(|item| item.parent_id.as_deref());
+        while let Some(parent_id) = current {
+            if parent_id == ancestor_id {
+                return true;
+            }
+            if !visited.insert(parent_id) {
+                // Pre-existing cycle that does not pass through `ancestor_id`.
+                return true;
+            }
+            current = self
+                .items
+                .get(parent_id)
+                .and_then(|item| item.parent_id.as_deref());
+        }
+
+        false
+    }
+
+    fn place(&mut self, id: &str, parent_id: Option<&str>, position: i32) {
+        let mut siblings = self
+            .children(parent_id)
+            .into_iter()
+            .map(|item| item.id.clone())
+            .filter(|sibling| sibling != id)
+            .collect::<Vec<_>>();
+        let index = usize::try_from(position.max(0))
+            .unwrap_or(0)
+            .min(siblings.len());
+        siblings.insert(index, id.to_string());
+        self.renumber(&siblings);
+    }
+
+    fn compact(&mut self, parent_id: Option<&str>) {
+        let siblings = self
+            .children(parent_id)
+            .into_iter()
+            .map(|item| item.id.clone())
+            .collect::<Vec<_>>();
+        self.renumber(&siblings);
+    }
+
+    fn renumber(&mut self, ordered_ids: &[String]) {
+        for (order, id) in ordered_ids.iter().enumerate() {
+            if let Some(item) = self.items.get_mut(id) {
+                item.order = order as i32;
+            }
+        }
+    }
+}
+
+fn missing_record(item: &WorkspaceItem, table: &'static str) -> WorkspaceError {
+    WorkspaceError::MissingRecord {
+        id: item.id.clone(),
+        kind: item.kind.clone(),
+        table,
+    }
+}
+
+fn check_orphans<'a>(
+    items: &BTreeMap<String, WorkspaceItem>,
+    keys: impl Iterator<Item = &'a String>,
+    kind: ItemKind,
+    table: &'static str,
+) -> Result<(), WorkspaceError> {
+    for id in keys {
+        if items.get(id).map(|item| &item.kind) != Some(&kind) {
+            return Err(WorkspaceError::OrphanRecord {
+                id: id.clone(),
+                table,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Typed CRUD requests accepted by the host workspace bridge.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum WorkspaceCommand {
+    ListItems,
+    GetItem {
+        id: String,
+    },
+    InsertItem {
+        entry: WorkspaceEntry,
+    },
+    SetTitle {
+        id: String,
+        title: String,
+    },
+    SetTabUrl {
+        id: String,
+        url: String,
+    },
+    SetGroupCollapsed {
+        id: String,
+        collapsed: bool,
+    },
+    MoveItem {
+        id: String,
+        parent_id: Option<String>,
+        order: i32,
+    },
+    RemoveItem {
+        id: String,
+    },
+    ListSettings,
+    GetSetting {
+        key: String,
+    },
+    SetSetting {
+        key: String,
+        value: String,
+    },
+    RemoveSetting {
+        key: String,
+    },
+}
+
+impl WorkspaceCommand {
+    /// Whether applying the command can change the store.
+    pub fn is_mutation(&self) -> bool {
+        !matches!(
+            self,
+            Self::ListItems | Self::GetItem { .. } | Self::ListSettings | Self::GetSetting { .. }
+        )
+    }
+}
+
+/// How an imported `.pulse` bundle combines with the current workspace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportMode {
+    /// Adds the bundle's tree after the current roots, renaming colliding ids.
+    Merge,
+    /// Swaps the current tree for the bundle's.
+    Replace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum WorkspaceResponse {
+    Items { entries: Vec<WorkspaceEntry> },
+    Item { entry: Option<WorkspaceEntry> },
+    Removed { ids: Vec<String> },
+    Settings { settings: BTreeMap<String, String> },
+    Setting { key: String, value: Option<String> },
+    Done,
+}
+
+impl WorkspaceStore {
+    pub fn apply(
+        &mut self,
+        command: WorkspaceCommand,
+    ) -> Result<WorkspaceResponse, WorkspaceError> {
+        match command {
+            WorkspaceCommand::ListItems => Ok(WorkspaceResponse::Items {
+                entries: self.entries(),
+            }),
+            WorkspaceCommand::GetItem { id } => Ok(WorkspaceResponse::Item {
+                entry: self.entry(&id),
+            }),
+            WorkspaceCommand::InsertItem { entry } => {
+                let id = entry.item.id.clone();
+                self.insert(entry)?;
+                Ok(WorkspaceResponse::Item {
+                    entry: self.entry(&id),
+                })
+            }
+            WorkspaceCommand::SetTitle { id, title } => {
+                self.set_title(&id, &title)?;
+                Ok(WorkspaceResponse::Done)
+            }
+            WorkspaceCommand::SetTabUrl { id, url } => {
+                self.set_tab_url(&id, &url)?;
+                Ok(WorkspaceResponse::Done)
+            }
+            WorkspaceCommand::SetGroupCollapsed { id, collapsed } => {
+                self.set_group_collapsed(&id, collapsed)?;
+                Ok(WorkspaceResponse::Done)
+            }
+            WorkspaceCommand::MoveItem {
+                id,
+                parent_id,
+                order,
+            } => {
+                self.move_item(&id, parent_id.as_deref(), order)?;
+                Ok(WorkspaceResponse::Item {
+                    entry: self.entry(&id),
+                })
+            }
+            WorkspaceCommand::RemoveItem { id } => Ok(WorkspaceResponse::Removed {
+                ids: self.remove(&id)?,
+            }),
+            WorkspaceCommand::ListSettings => Ok(WorkspaceResponse::Settings {
+                settings: self.settings.clone(),
+            }),
+            WorkspaceCommand::GetSetting { key } => {
+                let value = self.setting(&key).map(str::to_string);
+                Ok(WorkspaceResponse::Setting { key, value })
+            }
+            WorkspaceCommand::SetSetting { key, value } => {
+                self.set_setting(&key, &value);
+                Ok(WorkspaceResponse::Setting {
+                    key,
+                    value: Some(value),
+                })
+            }
+            WorkspaceCommand::RemoveSetting { key } => {
+                self.remove_setting(&key);
+                Ok(WorkspaceResponse::Setting { key, value: None })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, kind: ItemKind, parent_id: Option<&str>, order: i32) -> WorkspaceItem {
+        WorkspaceItem {
+            id: id.to_string(),
+            kind,
+            parent_id: parent_id.map(str::to_string),
+            title: id.to_string(),
+            order,
+        }
+    }
+
+    fn group(id: &str, parent_id: Option<&str>, order: i32) -> WorkspaceEntry {
+        WorkspaceEntry {
+            item: item(id, ItemKind::Group, parent_id, order),
+            tab: None,
+            group: None,
+            file: None,
+        }
+    }
+
+    fn tab(id: &str, parent_id: Option<&str>, order: i32) -> WorkspaceEntry {
+        WorkspaceEntry {
+            item: item(id, ItemKind::BrowserTab, parent_id, order),
+            tab: Some(TabRecord {
+                url: format!("https://{id}.test/"),
+                opener_id: None,
+                pinned: false,
+                history: None,
+            }),
+            group: None,
+            file: None,
+        }
+    }
+
+    /// `(id, order)` of the children of `parent_id`.
+    fn children(store: &WorkspaceStore, parent_id: Option<&str>) -> Vec<(String, i32)> {
+        store
+            .children(parent_id)
+            .into_iter()
+            .map(|item| (item.id.clone(), item.order))
+            .collect()
+    }
+
+    fn ordered(ids: &[&str]) -> Vec<(String, i32)> {
+        ids.iter()
+            .enumerate()
+            .map(|(order, id)| (id.to_string(), order as i32))
+            .collect()
+    }
+
+    fn sample() -> WorkspaceStore {
+        let mut store = WorkspaceStore::default();
+        store.insert(group("g", None, 0)).unwrap();
+        store.insert(tab("a", Some("g"), 0)).unwrap();
+        store.insert(tab("b", Some("g"), 1)).unwrap();
+        store.insert(tab("c", None, 1)).unwrap();
+        store
+    }
+
+    #[test]
+    fn sibling_orders_stay_contiguous() {
+        let mut store = sample();
+        // Inserting at an occupied order shifts the later siblings; far orders append.
+        store.insert(tab("x", Some("g"), 1)).unwrap();
+        store.insert(tab("y", Some("g"), 99)).unwrap();
+        store.insert(tab("z", Some("g"), -5)).unwrap();
+        assert_eq!(
+            children(&store, Some("g")),
+            ordered(&["z", "a", "x", "b", "y"])
+        );
+
+        store.move_item("a", None, 1).unwrap();
+        assert_eq!(children(&store, Some("g")), ordered(&["z", "x", "b", "y"]));
+        assert_eq!(children(&store, None), ordered(&["g", "a", "c"]));
+        store.move_item("y", Some("g"), 0).unwrap();
+        assert_eq!(children(&store, Some("g")), ordered(&["y", "z", "x", "b"]));
+
+        store.remove("x").unwrap();
+        assert_eq!(children(&store, Some("g")), ordered(&["y", "z", "b"]));
+        assert_eq!(
+            store
+                .entries()
+                .iter()
+                .map(|entry| entry.item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["g", "y", "z", "b", "a", "c"]
+        );
+        store.validate().unwrap();
+    }
+
+    #[test]
+    fn moves_into_cycles_or_under_tabs_are_rejected() {
+        let mut store = sample();
+        store.insert(group("inner", Some("g"), 2)).unwrap();
+        let before = store.clone();
+
+        assert!(matches!(
+            store.move_item("g", Some("inner"), 0),
+            Err(WorkspaceError::Cycle { .. })
+        ));
+        assert!(matches!(
+            store.move_item("g", Some("g"), 0),
+            Err(WorkspaceError::Cycle { .. })
+        ));
+        assert_eq!(
+            store.move_item("inner", Some("a"), 0),
+            Err(WorkspaceError::InvalidParent {
+                id: "inner".into(),
+                parent_id: "a".into(),
+            })
+        );
+        assert!(matches!(
+            store.insert(group("h", Some("c"), 0)),
+            Err(WorkspaceError::InvalidParent { .. })
+        ));
+        assert!(matches!(
+            store.move_item("c", Some("gone"), 0),
+            Err(WorkspaceError::MissingParent { .. })
+        ));
+        assert_eq!(
+            store.move_item("gone", None, 0),
+            Err(WorkspaceError::UnknownItem("gone".into()))
+        );
+        assert_eq!(store, before);
+
+        // Tabs opened from a tab sit under it.
+        store.move_item("c", Some("a"), 0).unwrap();
+        assert_eq!(children(&store, Some("a")), ordered(&["c"]));
+        assert!(matches!(
+            store.move_item("a", Some("c"), 0),
+            Err(WorkspaceError::Cycle { .. })
+        ));
+
+        assert_eq!(
+            store.remove("g").unwrap(),
+            ["g", "inner", "b", "a", "c"].map(String::from)
+        );
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn validation_finds_broken_tables() {
+        let store = sample();
+        store.validate().unwrap();
+
+        let mut duplicate = store.clone();
+        duplicate.items.get_mut("b").unwrap().order = 0;
+        assert!(matches!(
+            duplicate.validate(),
+            Err(WorkspaceError::DuplicateOrder { order: 0, .. })
+        ));
+
+        let mut orphan = store.clone();
+        orphan.groups.insert("a".into(), GroupRecord::default());
+        assert_eq!(
+            orphan.validate(),
+            Err(WorkspaceError::OrphanRecord {
+                id: "a".into(),
+                table: "groups",
+            })
+        );
+
+        let mut missing = store.clone();
+        missing.tabs.remove("c");
+        assert!(matches!(
+            missing.validate(),
+            Err(WorkspaceError::MissingRecord { table: "tabs", .. })
+        ));
+
+        let mut dangling = store.clone();
+        dangling.items.get_mut("a").unwrap().parent_id = Some("gone".into());
+        assert!(matches!(
+            dangling.validate(),
+            Err(WorkspaceError::MissingParent { .. })
+        ));
+
+        let mut cycle = store.clone();
+        cycle.insert(group("h", Some("g"), 2)).unwrap();
+        cycle.items.get_mut("g").unwrap().parent_id = Some("h".into());
+        assert!(matches!(
+            cycle.validate(),
+            Err(WorkspaceError::Cycle { .. })
+        ));
+
+        let mut rekeyed = store;
+        let item = rekeyed.items.remove("c").unwrap();
+        rekeyed.items.insert("d".into(), item);
+        assert!(matches!(
+            rekeyed.validate(),
+            Err(WorkspaceError::OrphanRecord { table: "items", .. })
+        ));
+    }
+
+    #[test]
+    fn every_command_applies_to_the_store() {
+        let mut store = sample();
+        let mut apply = |command| store.apply(command).unwrap();
+
+        let WorkspaceResponse::Item { entry: Some(entry) } = apply(WorkspaceCommand::InsertItem {
+            entry: tab("d", Some("g"), 0),
+        }) else {
+            panic!("insert-item returns the inserted entry");
+        };
+        assert_eq!((entry.item.id.as_str(), entry.item.order), ("d", 0));
+        assert_eq!(
+            apply(WorkspaceCommand::SetTitle {
+                id: "d".into(),
+                title: "Docs".into(),
+            }),
+            WorkspaceResponse::Done
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::SetTabUrl {
+                id: "d".into(),
+                url: "https://docs.test/".into(),
+            }),
+            WorkspaceResponse::Done
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::SetGroupCollapsed {
+                id: "g".into(),
+                collapsed: true,
+            }),
+            WorkspaceResponse::Done
+        );
+        let WorkspaceResponse::Item { entry: Some(moved) } = apply(WorkspaceCommand::MoveItem {
+            id: "d".into(),
+            parent_id: None,
+            order: 0,
+        }) else {
+            panic!("move-item returns the moved entry");
+        };
+        assert_eq!((moved.item.parent_id, moved.item.order), (None, 0));
+
+        let WorkspaceResponse::Item {
+            entry: Some(fetched),
+        } = apply(WorkspaceCommand::GetItem { id: "d".into() })
+        else {
+            panic!("get-item finds an existing item");
+        };
+        assert_eq!(fetched.item.title, "Docs");
+        assert_eq!(fetched.tab.unwrap().url, "https://docs.test/");
+        assert_eq!(
+            apply(WorkspaceCommand::GetItem { id: "gone".into() }),
+            WorkspaceResponse::Item { entry: None }
+        );
+        let WorkspaceResponse::Items { entries } = apply(WorkspaceCommand::ListItems) else {
+            panic!("list-items lists entries");
+        };
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["d", "g", "a", "b", "c"]
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::RemoveItem { id: "g".into() }),
+            WorkspaceResponse::Removed {
+                ids: ["g", "b", "a"].map(String::from).to_vec(),
+            }
+        );
+
+        assert_eq!(
+            apply(WorkspaceCommand::SetSetting {
+                key: "k".into(),
+                value: "v".into(),
+            }),
+            WorkspaceResponse::Setting {
+                key: "k".into(),
+                value: Some("v".into()),
+            }
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::GetSetting { key: "k".into() }),
+            WorkspaceResponse::Setting {
+                key: "k".into(),
+                value: Some("v".into()),
+            }
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::ListSettings),
+            WorkspaceResponse::Settings {
+                settings: BTreeMap::from([("k".into(), "v".into())]),
+            }
+        );
+        assert_eq!(
+            apply(WorkspaceCommand::RemoveSetting { key: "k".into() }),
+            WorkspaceResponse::Setting {
+                key: "k".into(),
+                value: None,
+            }
+        );
+
+        assert!(store.group("g").is_none());
+        assert_eq!(
+            store.apply(WorkspaceCommand::SetTitle {
+                id: "g".into(),
+                title: "x".into(),
+            }),
+            Err(WorkspaceError::UnknownItem("g".into()))
+        );
+        store.validate().unwrap();
+    }
+}
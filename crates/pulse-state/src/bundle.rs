@@ -0,0 +1,383 @@
+use anyhow::{Context, Result, anyhow};
+pub use pulse_core::ImportMode;
+use pulse_core::WorkspaceStore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::migrations::{self, CURRENT_SCHEMA_VERSION};
+
+pub const BUNDLE_EXTENSION: &str = "pulse";
+const BUNDLE_FORMAT: &str = "pulse-bundle";
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const STATE_ENTRY: &str = "state.json";
+/// Bundles come from other machines and people; refuse to inflate anything huge.
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// `manifest.json` of a `.pulse` bundle, a zip archive next to `state.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: String,
+    pub bundle_version: u32,
+    /// `schema_version` of the envelope in `state.json`.
+    pub schema_version: u32,
+    pub created_at_unix_ms: u64,
+    pub pulse_version: String,
+    pub item_count: usize,
+    /// `sha256:<hex>` over the exact bytes of `state.json`.
+    pub state_checksum: String,
+}
+
+pub struct Bundle {
+    pub manifest: BundleManifest,
+    pub workspace: WorkspaceStore,
+}
+
+/// Writes `envelope` (a plaintext state envelope for `workspace`) as a bundle at `path`.
+pub fn write(
+    path: &Path,
+    envelope: &[u8],
+    workspace: &WorkspaceStore,
+    created_at_unix_ms: u64,
+) -> Result<BundleManifest> {
+    let manifest = BundleManifest {
+        format: String::from(BUNDLE_FORMAT),
+        bundle_version: BUNDLE_VERSION,
+        schema_version: CURRENT_SCHEMA_VERSION,
+        created_at_unix_ms,
+        pulse_version: String::from(env!("CARGO_PKG_VERSION")),
+        item_count: workspace.entries().len(),
+        state_checksum: migrations::sha256_checksum(envelope),
+    };
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create bundle directory '{}'", parent.display()))?;
+    }
+    let temp = path.with_extension("tmp");
+    let file = File::create(&temp)
+        .with_context(|| format!("Failed to create bundle '{}'", temp.display()))?;
+
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut archive = ZipWriter::new(file);
+    archive.start_file(MANIFEST_ENTRY, options)?;
+    archive.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    archive.start_file(STATE_ENTRY, options)?;
+    archive.write_all(envelope)?;
+    archive
+        .finish()
+        .and_then(|file| Ok(file.sync_all()?))
+        .with_context(|| format!("Failed to write bundle '{}'", temp.display()))?;
+
+    fs::rename(&temp, path)
+        .with_context(|| format!("Failed to commit bundle '{}'", path.display()))?;
+    Ok(manifest)
+}
+
+/// Opens a bundle and checks its manifest, checksum, migrations and table invariants.
+pub fn read(path: &Path) -> Result<Bundle> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open bundle '{}'", path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("'{}' is not a .pulse bundle", path.display()))?;
+
+    let manifest: BundleManifest =
+        serde_json::from_slice(&read_entry(&mut archive, MANIFEST_ENTRY)?)
+            .context("Bundle manifest is malformed")?;
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(anyhow!("Unknown bundle format '{}'", manifest.format));
+    }
+    if manifest.bundle_version > BUNDLE_VERSION {
+        return Err(anyhow!(
+            "Bundle version {} is newer than this build supports ({BUNDLE_VERSION})",
+            manifest.bundle_version
+        ));
+    }
+
+    let state = read_entry(&mut archive, STATE_ENTRY)?;
+    let checksum = migrations::sha256_checksum(&state);
+    if checksum != manifest.state_checksum {
+        return Err(anyhow!(
+            "Bundle state checksum mismatch: manifest has {}, contents hash to {checksum}",
+            manifest.state_checksum
+        ));
+    }
+
+    let raw: Value = serde_json::from_slice(&state).context("Bundle state is not valid JSON")?;
+    let outcome = migrations::migrate_to_current(raw).context("Bundle state is not valid")?;
+    if outcome.source_version != manifest.schema_version {
+        return Err(anyhow!(
+            "Bundle manifest says schema v{}, but its state is v{}",
+            manifest.schema_version,
+            outcome.source_version
+        ));
+    }
+
+    Ok(Bundle {
+        manifest,
+        workspace: outcome.workspace,
+    })
+}
+
+/// Combines `imported` with `current`. Settings outside `ui.` are host-owned and
+/// machine-specific, so an import never changes them. Returns the new workspace and
+/// the imported ids that were renamed (original -> new).
+pub fn apply(
+    current: WorkspaceStore,
+    imported: WorkspaceStore,
+    mode: ImportMode,
+) -> Result<(WorkspaceStore, BTreeMap<String, String>)> {
+    match mode {
+        ImportMode::Merge => {
+            let mut workspace = current;
+            let remapped = merge(&mut workspace, &imported)?;
+            Ok((workspace, remapped))
+        }
+        ImportMode::Replace => {
+            let mut workspace = imported;
+            for key in workspace
+                .settings()
+                .keys()
+                .filter(|key| !key.starts_with("ui."))
+                .cloned()
+                .collect::<Vec<_>>()
+            {
+                workspace.remove_setting(&key);
+            }
+            for (key, value) in current.settings() {
+                if !key.starts_with("ui.") {
+                    workspace.set_setting(key, value);
+                }
+            }
+            Ok((workspace, BTreeMap::new()))
+        }
+    }
+}
+
+fn merge(
+    workspace: &mut WorkspaceStore,
+    imported: &WorkspaceStore,
+) -> Result<BTreeMap<String, String>> {
+    let mut remapped = BTreeMap::new();
+    // Depth-first order inserts parents before children and siblings in order.
+    for mut entry in imported.entries() {
+        let original_id = entry.item.id.clone();
+        let id = unused_id(workspace, &original_id);
+        if id != original_id {
+            remapped.insert(original_id, id.clone());
+        }
+
+        entry.item.id = id;
+        match entry.item.parent_id.take() {
+            Some(parent_id) => {
+                entry.item.parent_id = Some(remapped.get(&parent_id).cloned().unwrap_or(parent_id));
+            }
+            None => entry.item.order = i32::MAX,
+        }
+        // Popup tabs sit under their opener, so an opener is always remapped before them.
+        if let Some(opener_id) = entry.tab.as_mut().and_then(|tab| tab.opener_id.as_mut())
+            && let Some(remapped_opener) = remapped.get(opener_id.as_str())
+        {
+            *opener_id = remapped_opener.clone();
+        }
+        workspace
+            .insert(entry)
+            .context("Failed to merge bundle item into the workspace")?;
+    }
+    Ok(remapped)
+}
+
+fn unused_id(workspace: &WorkspaceStore, id: &str) -> String {
+    if workspace.item(id).is_none() {
+        return String::from(id);
+    }
+    (2..)
+        .map(|suffix| format!("{id}-{suffix}"))
+        .find(|candidate| workspace.item(candidate).is_none())
+        .expect("an unused id suffix exists")
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>> {
+    let entry = archive
+        .by_name(name)
+        .with_context(|| format!("Bundle is missing '{name}'"))?;
+    if entry.size() > MAX_ENTRY_BYTES {
+        return Err(anyhow!(
+            "Bundle entry '{name}' is {} bytes, over the {MAX_ENTRY_BYTES} byte limit",
+            entry.size()
+        ));
+    }
+
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_ENTRY_BYTES)
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to read bundle entry '{name}'"))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulse_core::{GroupRecord, ItemKind, TabRecord, WorkspaceEntry, WorkspaceItem};
+
+    fn entry(id: &str, parent_id: Option<&str>, kind: ItemKind) -> WorkspaceEntry {
+        WorkspaceEntry {
+            item: WorkspaceItem {
+                id: String::from(id),
+                kind: kind.clone(),
+                parent_id: parent_id.map(String::from),
+                title: String::from(id),
+                order: i32::MAX,
+            },
+            tab: (kind == ItemKind::BrowserTab).then(|| TabRecord {
+                url: format!("https://example.com/{id}"),
+                opener_id: None,
+                pinned: false,
+                history: None,
+            }),
+            group: (kind == ItemKind::Group).then(GroupRecord::default),
+            file: None,
+        }
+    }
+
+    fn store(entries: Vec<WorkspaceEntry>, settings: &[(&str, &str)]) -> WorkspaceStore {
+        let mut store = WorkspaceStore::default();
+        for entry in entries {
+            store.insert(entry).expect("insert");
+        }
+        for (key, value) in settings {
+            store.set_setting(key, value);
+        }
+        store
+    }
+
+    #[test]
+    fn merge_remaps_colliding_ids_and_their_children() {
+        let current = store(
+            vec![
+                entry("group-a", None, ItemKind::Group),
+                entry("tab-1", Some("group-a"), ItemKind::BrowserTab),
+            ],
+            &[("ui.selected_item_id", "tab-1")],
+        );
+        let mut popup = entry("tab-3", Some("tab-1"), ItemKind::BrowserTab);
+        popup.tab.as_mut().unwrap().opener_id = Some(String::from("tab-1"));
+        let imported = store(
+            vec![
+                entry("group-a", None, ItemKind::Group),
+                entry("tab-1", Some("group-a"), ItemKind::BrowserTab),
+                entry("tab-2", Some("group-a"), ItemKind::BrowserTab),
+                popup,
+            ],
+            &[("ui.selected_item_id", "tab-2")],
+        );
+
+        let (merged, remapped) = apply(current, imported, ImportMode::Merge).expect("merge");
+        merged.validate().expect("valid");
+        assert_eq!(
+            remapped.get("group-a").map(String::as_str),
+            Some("group-a-2")
+        );
+        assert_eq!(remapped.get("tab-1").map(String::as_str), Some("tab-1-2"));
+        assert!(!remapped.contains_key("tab-2"));
+
+        let roots = merged.children(None);
+        assert_eq!(
+            roots
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["group-a", "group-a-2"]
+        );
+        let imported_children = merged
+            .children(Some("group-a-2"))
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(imported_children, ["tab-1-2", "tab-2"]);
+        assert_eq!(
+            merged
+                .item("tab-3")
+                .and_then(|item| item.parent_id.as_deref()),
+            Some("tab-1-2")
+        );
+        assert_eq!(
+            merged.tab("tab-3").and_then(|tab| tab.opener_id.as_deref()),
+            Some("tab-1-2")
+        );
+        assert_eq!(merged.setting("ui.selected_item_id"), Some("tab-1"));
+    }
+
+    #[test]
+    fn replace_keeps_host_settings() {
+        let current = store(
+            vec![entry("tab-1", None, ItemKind::BrowserTab)],
+            &[("host.theme", "dark"), ("ui.address", "current")],
+        );
+        let imported = store(
+            vec![entry("tab-9", None, ItemKind::BrowserTab)],
+            &[
+                ("host.theme", "light"),
+                ("host.other", "x"),
+                ("ui.address", "bundle"),
+            ],
+        );
+
+        let (replaced, remapped) = apply(current, imported, ImportMode::Replace).expect("replace");
+        assert!(remapped.is_empty());
+        assert!(replaced.item("tab-1").is_none());
+        assert!(replaced.item("tab-9").is_some());
+        assert_eq!(replaced.setting("host.theme"), Some("dark"));
+        assert_eq!(replaced.setting("host.other"), None);
+        assert_eq!(replaced.setting("ui.address"), Some("bundle"));
+    }
+
+    #[test]
+    fn round_trips_and_rejects_tampered_state() {
+        let dir = std::env::temp_dir().join(format!("pulse-bundle-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("workspace.pulse");
+        let workspace = store(vec![entry("tab-1", None, ItemKind::BrowserTab)], &[]);
+        let envelope = serde_json::to_vec(&serde_json::json!({
+            "schema_version": CURRENT_SCHEMA_VERSION,
+            "updated_at_unix_ms": 1,
+            "checksum": migrations::workspace_checksum(&workspace).expect("checksum"),
+            "workspace": workspace,
+        }))
+        .expect("envelope");
+
+        let manifest = write(&path, &envelope, &workspace, 1).expect("write");
+        assert_eq!(manifest.item_count, 1);
+        let bundle = read(&path).expect("read");
+        assert_eq!(bundle.workspace, workspace);
+
+        let tampered = dir.join("tampered.pulse");
+        let mut archive = ZipWriter::new(File::create(&tampered).expect("create"));
+        archive
+            .start_file(MANIFEST_ENTRY, SimpleFileOptions::default())
+            .expect("manifest");
+        archive
+            .write_all(&serde_json::to_vec(&manifest).expect("manifest json"))
+            .expect("write manifest");
+        archive
+            .start_file(STATE_ENTRY, SimpleFileOptions::default())
+            .expect("state");
+        archive
+            .write_all(&envelope[..envelope.len() - 1])
+            .expect("write state");
+        archive.finish().expect("finish");
+
+        let error = read(&tampered).err().expect("tampered bundle");
+        assert!(format!("{error:#}").contains("checksum mismatch"));
+    }
+}
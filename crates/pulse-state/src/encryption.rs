@@ -0,0 +1,412 @@
+use anyhow::{Context, Result, anyhow};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Path of the key file for `FileKeyProvider`. Unset means state is stored in plaintext.
+pub const KEY_FILE_ENV: &str = "PULSE_STATE_KEY_FILE";
+const SEALED_FORMAT_VERSION: u32 = 1;
+const SEALED_ALGORITHM: &str = "chacha20-poly1305";
+const KEY_LEN: usize = 32;
+const KEY_ID_LEN: usize = 8;
+
+/// Errors about key availability, as opposed to damaged files. Persistence must not
+/// quarantine or overwrite state because of these: the data is fine, the key is not.
+#[derive(Debug, Error)]
+pub enum StateKeyError {
+    #[error(
+        "state is encrypted with key '{key_id}', but no state key provider is configured (set {KEY_FILE_ENV})"
+    )]
+    NotConfigured { key_id: String },
+    #[error("state key '{key_id}' is not available from {provider}; is this the right key file?")]
+    UnknownKey { key_id: String, provider: String },
+    #[error("state key provider {provider} is unavailable: {reason}")]
+    ProviderUnavailable { provider: String, reason: String },
+}
+
+pub struct StateKey {
+    pub id: String,
+    material: [u8; KEY_LEN],
+}
+
+/// Source of state encryption keys. Providers keep retired keys so files sealed before a
+/// rotation (backups, snapshots) still open.
+pub trait KeyProvider: Send + Sync {
+    /// Names the provider in error messages, e.g. `key file '/path/state-key.json'`.
+    fn describe(&self) -> String;
+    /// The key new writes are sealed with.
+    fn active_key(&self) -> Result<StateKey>;
+    /// Any key the provider knows, active or retired.
+    fn key(&self, key_id: &str) -> Result<Option<StateKey>>;
+    /// Generates a new active key, keeping the previous ones. Returns the new key id.
+    fn rotate(&self) -> Result<String>;
+}
+
+/// The provider selected by the environment, if encryption is enabled.
+pub fn configured_provider() -> Option<Box<dyn KeyProvider>> {
+    let path = std::env::var_os(KEY_FILE_ENV).filter(|value| !value.is_empty())?;
+    Some(Box::new(FileKeyProvider::new(PathBuf::from(path))))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedState {
+    pulse_encrypted: u32,
+    algorithm: String,
+    key_id: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn is_sealed(raw: &Value) -> bool {
+    raw.get("pulse_encrypted").is_some()
+}
+
+/// Encrypts `plaintext` with the provider's active key into the sealed JSON file format.
+pub fn seal(plaintext: &[u8], provider: &dyn KeyProvider) -> Result<Vec<u8>> {
+    let key = provider.active_key()?;
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("Failed to generate a state encryption nonce"))?;
+
+    let mut in_out = plaintext.to_vec();
+    aead_key(&key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(associated_data(&key.id).as_bytes()),
+            &mut in_out,
+        )
+        .map_err(|_| anyhow!("Failed to encrypt workspace state"))?;
+
+    let sealed = SealedState {
+        pulse_encrypted: SEALED_FORMAT_VERSION,
+        algorithm: String::from(SEALED_ALGORITHM),
+        key_id: key.id,
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(in_out),
+    };
+    let mut serialized =
+        serde_json::to_vec_pretty(&sealed).context("Failed to encode sealed workspace state")?;
+    serialized.push(b'\n');
+    Ok(serialized)
+}
+
+/// Decrypts a sealed file. Returns the plaintext and the id of the key that sealed it.
+pub fn open(raw: Value, provider: Option<&dyn KeyProvider>) -> Result<(Vec<u8>, String)> {
+    let sealed: SealedState =
+        serde_json::from_value(raw).context("Encrypted state header is malformed")?;
+    if sealed.pulse_encrypted != SEALED_FORMAT_VERSION || sealed.algorithm != SEALED_ALGORITHM {
+        return Err(anyhow!(
+            "Unsupported encrypted state format {} ({})",
+            sealed.pulse_encrypted,
+            sealed.algorithm
+        ));
+    }
+
+    let Some(provider) = provider else {
+        return Err(StateKeyError::NotConfigured {
+            key_id: sealed.key_id,
+        }
+        .into());
+    };
+    let Some(key) = provider.key(&sealed.key_id)? else {
+        return Err(StateKeyError::UnknownKey {
+            key_id: sealed.key_id,
+            provider: provider.describe(),
+        }
+        .into());
+    };
+
+    let nonce = BASE64
+        .decode(&sealed.nonce)
+        .context("Encrypted state nonce is not base64")?;
+    let mut in_out = BASE64
+        .decode(&sealed.ciphertext)
+        .context("Encrypted state ciphertext is not base64")?;
+    let nonce = Nonce::try_assume_unique_for_key(&nonce)
+        .map_err(|_| anyhow!("Encrypted state nonce has the wrong length"))?;
+
+    // With the right key id, a failed tag check means the file was altered.
+    let plaintext = aead_key(&key)?
+        .open_in_place(
+            nonce,
+            Aad::from(associated_data(&key.id).as_bytes()),
+            &mut in_out,
+        )
+        .map_err(|_| {
+            anyhow!(
+                "Encrypted state failed authentication with key '{}'",
+                key.id
+            )
+        })?;
+    Ok((plaintext.to_vec(), sealed.key_id))
+}
+
+/// Whether any error in `error`'s chain is a `StateKeyError`.
+pub fn is_key_error(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.downcast_ref::<StateKeyError>().is_some())
+}
+
+fn aead_key(key: &StateKey) -> Result<LessSafeKey> {
+    let unbound = UnboundKey::new(&CHACHA20_POLY1305, &key.material)
+        .map_err(|_| anyhow!("State key '{}' has invalid key material", key.id))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+// Binds the header to the ciphertext so the key id or format cannot be swapped.
+fn associated_data(key_id: &str) -> String {
+    format!("pulse-state:{SEALED_FORMAT_VERSION}:{SEALED_ALGORITHM}:{key_id}")
+}
+
+/// Keys stored as JSON in a single file, for tests and headless Linux without a keyring.
+pub struct FileKeyProvider {
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    active_key_id: String,
+    keys: Vec<KeyFileEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFileEntry {
+    id: String,
+    created_at_unix_ms: u64,
+    material: String,
+}
+
+impl FileKeyProvider {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn unavailable(&self, reason: impl Into<String>) -> StateKeyError {
+        StateKeyError::ProviderUnavailable {
+            provider: self.describe(),
+            reason: reason.into(),
+        }
+    }
+
+    fn read_key_file(&self) -> Result<KeyFile> {
+        if !self.path.exists() {
+            return Err(self
+                .unavailable(
+                    "the key file does not exist; run Pulse with --rotate-state-key to create it",
+                )
+                .into());
+        }
+
+        let text = fs::read_to_string(&self.path)
+            .map_err(|err| self.unavailable(format!("failed to read the key file: {err}")))?;
+        serde_json::from_str(&text).map_err(|err| {
+            self.unavailable(format!("the key file is malformed: {err}"))
+                .into()
+        })
+    }
+
+    fn entry_key(&self, entry: &KeyFileEntry) -> Result<StateKey> {
+        let material = BASE64
+            .decode(&entry.material)
+            .ok()
+            .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                self.unavailable(format!(
+                    "key '{}' is not {KEY_LEN} bytes of base64",
+                    entry.id
+                ))
+            })?;
+        Ok(StateKey {
+            id: entry.id.clone(),
+            material,
+        })
+    }
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn describe(&self) -> String {
+        format!("key file '{}'", self.path.display())
+    }
+
+    fn active_key(&self) -> Result<StateKey> {
+        let key_file = self.read_key_file()?;
+        let Some(entry) = key_file
+            .keys
+            .iter()
+            .find(|entry| entry.id == key_file.active_key_id)
+        else {
+            return Err(self
+                .unavailable(format!(
+                    "active key '{}' is not in the key file",
+                    key_file.active_key_id
+                ))
+                .into());
+        };
+        self.entry_key(entry)
+    }
+
+    fn key(&self, key_id: &str) -> Result<Option<StateKey>> {
+        let key_file = self.read_key_file()?;
+        key_file
+            .keys
+            .iter()
+            .find(|entry| entry.id == key_id)
+            .map(|entry| self.entry_key(entry))
+            .transpose()
+    }
+
+    fn rotate(&self) -> Result<String> {
+        let mut key_file = if self.path.exists() {
+            self.read_key_file()?
+        } else {
+            KeyFile {
+                active_key_id: String::new(),
+                keys: Vec::new(),
+            }
+        };
+
+        let rng = SystemRandom::new();
+        let mut material = [0u8; KEY_LEN];
+        let mut key_id = [0u8; KEY_ID_LEN];
+        rng.fill(&mut material)
+            .and_then(|_| rng.fill(&mut key_id))
+            .map_err(|_| anyhow!("Failed to generate a state key"))?;
+        let key_id = key_id
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+
+        key_file.keys.push(KeyFileEntry {
+            id: key_id.clone(),
+            created_at_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or(0),
+            material: BASE64.encode(material),
+        });
+        key_file.active_key_id = key_id.clone();
+        write_key_file(&self.path, &key_file)?;
+        Ok(key_id)
+    }
+}
+
+fn write_key_file(path: &Path, key_file: &KeyFile) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!("Failed to create key file directory '{}'", parent.display())
+        })?;
+    }
+
+    let temp = path.with_extension("tmp");
+    let _ = fs::remove_file(&temp);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(&temp)
+        .with_context(|| format!("Failed to create key file '{}'", temp.display()))?;
+    file.write_all(&serde_json::to_vec_pretty(key_file)?)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to write key file '{}'", temp.display()))?;
+    fs::rename(&temp, path)
+        .with_context(|| format!("Failed to commit key file '{}'", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_provider(name: &str) -> FileKeyProvider {
+        let dir =
+            std::env::temp_dir().join(format!("pulse-encryption-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        FileKeyProvider::new(dir.join("state-key.json"))
+    }
+
+    fn sealed_value(bytes: &[u8]) -> Value {
+        serde_json::from_slice(bytes).expect("sealed state is JSON")
+    }
+
+    #[test]
+    fn round_trips_through_the_active_key() {
+        let provider = temp_provider("round-trip");
+        let key_id = provider.rotate().expect("create key");
+
+        let sealed = seal(b"{\"schema_version\":3}", &provider).expect("seal");
+        let sealed = sealed_value(&sealed);
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.to_string().contains("schema_version"));
+
+        let (plaintext, sealed_with) = open(sealed, Some(&provider)).expect("open");
+        assert_eq!(plaintext, b"{\"schema_version\":3}");
+        assert_eq!(sealed_with, key_id);
+    }
+
+    #[test]
+    fn rotation_keeps_retired_keys_for_older_files() {
+        let provider = temp_provider("rotation");
+        let first = provider.rotate().expect("create key");
+        let sealed = sealed_value(&seal(b"old", &provider).expect("seal"));
+
+        let second = provider.rotate().expect("rotate");
+        assert_ne!(first, second);
+        assert_eq!(provider.active_key().expect("active").id, second);
+
+        let (plaintext, sealed_with) = open(sealed, Some(&provider)).expect("open old file");
+        assert_eq!(plaintext, b"old");
+        assert_eq!(sealed_with, first);
+    }
+
+    #[test]
+    fn missing_or_wrong_keys_are_key_errors() {
+        let provider = temp_provider("wrong-key");
+        provider.rotate().expect("create key");
+        let sealed = sealed_value(&seal(b"secret", &provider).expect("seal"));
+
+        let error = open(sealed.clone(), None).expect_err("no provider");
+        assert!(is_key_error(&error));
+
+        let other = temp_provider("other-key");
+        other.rotate().expect("create other key");
+        let error = open(sealed.clone(), Some(&other)).expect_err("wrong key file");
+        assert!(is_key_error(&error));
+
+        let missing = temp_provider("missing-key-file");
+        let error = open(sealed, Some(&missing)).expect_err("missing key file");
+        assert!(is_key_error(&error));
+        assert!(format!("{error:#}").contains("does not exist"));
+    }
+
+    #[test]
+    fn tampering_is_not_a_key_error() {
+        let provider = temp_provider("tamper");
+        provider.rotate().expect("create key");
+        let mut sealed = sealed_value(&seal(b"secret", &provider).expect("seal"));
+
+        let mut ciphertext = BASE64
+            .decode(sealed["ciphertext"].as_str().expect("ciphertext"))
+            .expect("base64");
+        ciphertext[0] ^= 1;
+        sealed["ciphertext"] = Value::from(BASE64.encode(ciphertext));
+
+        let error = open(sealed, Some(&provider)).expect_err("tampered");
+        assert!(!is_key_error(&error));
+        assert!(format!("{error:#}").contains("authentication"));
+    }
+}
@@ -0,0 +1,339 @@
+//! Content-addressed favicon cache of the current profile.
+//!
+//! Each icon is stored once, as `<profile>/favicons/<sha256>.png`. `index.json` maps pages,
+//! and their hosts as a fallback, to the icon they showed last, so tabs that were not loaded
+//! this session still have one. Pages are keyed by the SHA-256 of their URL, so the index
+//! does not keep a readable list of visited pages.
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use crate::persistence::{self, unix_time_ms};
+
+const INDEX_FILE_NAME: &str = "index.json";
+const INDEX_TEMP_FILE_NAME: &str = "index.tmp.json";
+const ICON_EXTENSION: &str = "png";
+const INDEX_VERSION: u32 = 2;
+/// Version 1 keyed pages by their plain URL.
+const HASHED_PAGES_VERSION: u32 = 2;
+/// Pages, and separately hosts, the index remembers; the least recently seen go first.
+const MAX_INDEXED_ENTRIES: usize = 2_000;
+/// Icons larger than this are not cached.
+pub const MAX_ICON_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct FaviconIndex {
+    version: u32,
+    /// Keyed by `page_key`.
+    #[serde(default)]
+    pages: BTreeMap<String, IndexedIcon>,
+    #[serde(default)]
+    hosts: BTreeMap<String, IndexedIcon>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct IndexedIcon {
+    hash: String,
+    seen_at_unix_ms: u64,
+}
+
+struct CachedIndex {
+    dir: PathBuf,
+    index: FaviconIndex,
+}
+
+/// Stores `png` as the icon of `page_url` and returns its hash.
+pub fn store(page_url: &str, png: &[u8]) -> Result<String> {
+    if png.len() > MAX_ICON_BYTES {
+        return Err(anyhow!(
+            "Favicon of '{page_url}' is {} bytes; the cache takes at most {MAX_ICON_BYTES}",
+            png.len()
+        ));
+    }
+    with_index(|dir, index| {
+        store_in(
+            dir,
+            index,
+            page_url,
+            png,
+            unix_time_ms(),
+            MAX_INDEXED_ENTRIES,
+        )
+    })
+}
+
+/// Hash of the icon last seen on `page_url`, or on another page of its host.
+pub fn lookup(page_url: &str) -> Result<Option<String>> {
+    with_index(|_, index| Ok((lookup_in(index, page_url), false)))
+}
+
+/// The PNG bytes of icon `hash`, if the cache has it.
+pub fn read(hash: &str) -> Result<Option<Vec<u8>>> {
+    if !is_icon_hash(hash) {
+        return Ok(None);
+    }
+    let _guard = cache();
+    let path = icon_path(&persistence::favicons_dir(), hash);
+    match fs::read(&path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read '{}'", path.display())),
+    }
+}
+
+/// Runs `update` on the cached index; it returns whether the index changed and must be saved.
+fn with_index<T>(update: impl FnOnce(&Path, &mut FaviconIndex) -> Result<(T, bool)>) -> Result<T> {
+    let mut cached = cache();
+    let dir = persistence::favicons_dir();
+    if cached.as_ref().is_none_or(|cached| cached.dir != dir) {
+        *cached = Some(CachedIndex {
+            index: load_index(&dir)?,
+            dir: dir.clone(),
+        });
+    }
+    let Some(CachedIndex { index, .. }) = cached.as_mut() else {
+        unreachable!("favicon index cache was filled above");
+    };
+
+    let outcome = update(&dir, index).and_then(|(value, changed)| {
+        if changed {
+            save_index(&dir, index)?;
+        }
+        Ok(value)
+    });
+    if outcome.is_err() {
+        *cached = None;
+    }
+    outcome
+}
+
+fn store_in(
+    dir: &Path,
+    index: &mut FaviconIndex,
+    page_url: &str,
+    png: &[u8],
+    now_unix_ms: u64,
+    max_entries: usize,
+) -> Result<(String, bool)> {
+    let hash = sha256_hex(png);
+    let path = icon_path(dir, &hash);
+    if !path.exists() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        // Write under a temporary name so a torn write never sits at a valid hash.
+        let temp = dir.join(format!("{hash}.tmp"));
+        fs::write(&temp, png).with_context(|| format!("Failed to write '{}'", temp.display()))?;
+        fs::rename(&temp, &path)
+            .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    }
+
+    let icon = IndexedIcon {
+        hash: hash.clone(),
+        seen_at_unix_ms: now_unix_ms,
+    };
+    index.pages.insert(page_key(page_url), icon.clone());
+    if let Some(host) = page_host(page_url) {
+        index.hosts.insert(host, icon);
+    }
+    prune(dir, index, max_entries);
+    Ok((hash, true))
+}
+
+fn lookup_in(index: &FaviconIndex, page_url: &str) -> Option<String> {
+    index
+        .pages
+        .get(&page_key(page_url))
+        .or_else(|| index.hosts.get(&page_host(page_url)?))
+        .map(|icon| icon.hash.clone())
+}
+
+/// Forgets the least recently seen entries beyond `max_entries` and deletes icons nothing
+/// refers to any more.
+fn prune(dir: &Path, index: &mut FaviconIndex, max_entries: usize) {
+    for entries in [&mut index.pages, &mut index.hosts] {
+        while entries.len() > max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, icon)| icon.seen_at_unix_ms)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+
+    let referenced = index
+        .pages
+        .values()
+        .chain(index.hosts.values())
+        .map(|icon| icon.hash.as_str())
+        .collect::<BTreeSet<_>>();
+    let Ok(files) = fs::read_dir(dir) else {
+        return;
+    };
+    for file in files.flatten() {
+        let path = file.path();
+        let is_unreferenced_icon = path.extension().is_some_and(|ext| ext == ICON_EXTENSION)
+            && path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|hash| is_icon_hash(hash) && !referenced.contains(hash));
+        if is_unreferenced_icon && let Err(err) = fs::remove_file(&path) {
+            eprintln!(
+                "Pulse favicon warning: could not remove '{}': {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+fn load_index(dir: &Path) -> Result<FaviconIndex> {
+    let path = dir.join(INDEX_FILE_NAME);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(FaviconIndex {
+                version: INDEX_VERSION,
+                ..FaviconIndex::default()
+            });
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read '{}'", path.display()));
+        }
+    };
+
+    // The cache can always be rebuilt, so an unreadable index starts over instead of failing.
+    match serde_json::from_slice::<FaviconIndex>(&bytes) {
+        Ok(mut index) if index.version <= INDEX_VERSION => {
+            if index.version < HASHED_PAGES_VERSION {
+                // Drop the plain page URLs; hosts still find an icon until pages are seen again.
+                index.pages.clear();
+            }
+            Ok(index)
+        }
+        _ => {
+            eprintln!(
+                "Pulse favicon warning: ignoring unreadable index '{}'",
+                path.display()
+            );
+            Ok(FaviconIndex {
+                version: INDEX_VERSION,
+                ..FaviconIndex::default()
+            })
+        }
+    }
+}
+
+fn save_index(dir: &Path, index: &FaviconIndex) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let path = dir.join(INDEX_FILE_NAME);
+    let temp = dir.join(INDEX_TEMP_FILE_NAME);
+    let index = FaviconIndex {
+        version: INDEX_VERSION,
+        ..index.clone()
+    };
+    fs::write(&temp, serde_json::to_vec(&index)?)
+        .with_context(|| format!("Failed to write '{}'", temp.display()))?;
+    fs::rename(&temp, &path).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(())
+}
+
+fn icon_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(format!("{hash}.{ICON_EXTENSION}"))
+}
+
+/// Only hashes this cache produced name files, so a request cannot reach other paths.
+fn is_icon_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+/// Hash of `page_url` without its fragment, so pages differing only there share an icon.
+fn page_key(page_url: &str) -> String {
+    let page = page_url.split_once('#').map_or(page_url, |(page, _)| page);
+    sha256_hex(page.as_bytes())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// `scheme://host[:port]` of `page_url`, without user info.
+fn page_host(page_url: &str) -> Option<String> {
+    let (scheme, rest) = page_url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    (!host.is_empty()).then(|| {
+        format!(
+            "{}://{}",
+            scheme.to_ascii_lowercase(),
+            host.to_ascii_lowercase()
+        )
+    })
+}
+
+fn cache() -> MutexGuard<'static, Option<CachedIndex>> {
+    static CACHE: OnceLock<Mutex<Option<CachedIndex>>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icons_are_stored_once_and_found_by_page_or_host() {
+        let dir = std::env::temp_dir().join(format!("pulse-favicons-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut index = load_index(&dir).unwrap();
+
+        let (docs, _) = store_in(
+            &dir,
+            &mut index,
+            "https://a.test/docs#intro",
+            b"docs",
+            10,
+            2,
+        )
+        .unwrap();
+        let (same, _) = store_in(&dir, &mut index, "https://b.test/", b"docs", 20, 2).unwrap();
+        assert_eq!(docs, same);
+        assert_eq!(lookup_in(&index, "https://a.test/docs"), Some(docs.clone()));
+        assert_eq!(
+            lookup_in(&index, "https://user@A.test/other"),
+            Some(docs.clone())
+        );
+        assert_eq!(lookup_in(&index, "https://c.test/"), None);
+
+        save_index(&dir, &index).unwrap();
+        assert_eq!(load_index(&dir).unwrap(), index);
+        let saved = fs::read_to_string(dir.join(INDEX_FILE_NAME)).unwrap();
+        assert!(!saved.contains("/docs"));
+
+        // A third page pushes out the oldest one; its icon is still used by b.test.
+        let (home, _) = store_in(&dir, &mut index, "https://c.test/", b"home", 30, 2).unwrap();
+        assert_eq!(lookup_in(&index, "https://a.test/docs"), None);
+        assert!(icon_path(&dir, &docs).exists());
+
+        store_in(&dir, &mut index, "https://d.test/", b"other", 40, 2).unwrap();
+        assert!(!icon_path(&dir, &docs).exists());
+        assert!(icon_path(&dir, &home).exists());
+        assert!(!is_icon_hash("../index"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
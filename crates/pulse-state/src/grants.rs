@@ -0,0 +1,492 @@
+//! Capability grants of the current profile and their append-only audit log, both kept
+//! next to the workspace state.
+
+use anyhow::{Context, Result, anyhow};
+use pulse_core::{AuditEntry, AuditEvent, Capability, Grant, GrantStore};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use crate::persistence::{self, unix_time_ms};
+
+const GRANTS_FILE_NAME: &str = "capability-grants.json";
+const GRANTS_TEMP_FILE_NAME: &str = "capability-grants.tmp.json";
+const AUDIT_FILE_NAME: &str = "capability-audit.jsonl";
+/// The audit log before its last rotation; the one before that is dropped.
+const ROTATED_AUDIT_FILE_NAME: &str = "capability-audit.1.jsonl";
+/// Size past which the audit log is rotated before the next append.
+const AUDIT_ROTATE_BYTES: u64 = 4 * 1024 * 1024;
+/// Bytes read at a time, from the end, when looking for the newest audit entries.
+const AUDIT_TAIL_CHUNK_BYTES: u64 = 64 * 1024;
+const GRANTS_FILE_VERSION: u32 = 1;
+/// Entries `capabilityAudit` returns, oldest first.
+const AUDIT_TAIL_LEN: usize = 500;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct GrantsFile {
+    version: u32,
+    /// Holders that already received their default grants. Revoking a default must stick,
+    /// so defaults are only ever granted once per holder.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    seeded_holders: Vec<String>,
+    #[serde(flatten)]
+    store: GrantStore,
+}
+
+#[derive(Debug, Deserialize)]
+struct RevokeRequest {
+    holder: String,
+    capability: Capability,
+}
+
+struct CachedGrants {
+    dir: PathBuf,
+    file: GrantsFile,
+}
+
+pub fn list() -> Result<Vec<Grant>> {
+    with_store(|file, _| Ok((file.store.grants().to_vec(), Vec::new())))
+}
+
+/// Grants `capabilities` to `holder` unless it was seeded before. Returns whether it was.
+pub fn seed_defaults(holder: &str, capabilities: &[Capability]) -> Result<bool> {
+    with_store(|file, now| {
+        if file.seeded_holders.iter().any(|seeded| seeded == holder) {
+            return Ok((false, Vec::new()));
+        }
+        let mut entries = Vec::with_capacity(capabilities.len());
+        for capability in capabilities {
+            entries.push(file.store.grant(holder, capability.clone(), now, None)?);
+        }
+        file.seeded_holders.push(holder.to_string());
+        Ok((true, entries))
+    })
+}
+
+/// Grants `capability` to `holder` until `expires_at_unix_ms` (or until revoked).
+pub fn grant(
+    holder: &str,
+    capability: Capability,
+    expires_at_unix_ms: Option<u64>,
+) -> Result<Grant> {
+    with_store(|file, now| {
+        let entry = file
+            .store
+            .grant(holder, capability.clone(), now, expires_at_unix_ms)?;
+        let grant = file
+            .store
+            .held_by(holder)
+            .find(|grant| grant.capability == capability)
+            .cloned()
+            .ok_or_else(|| anyhow!("Grant of '{capability}' to '{holder}' was not stored"))?;
+        Ok((grant, vec![entry]))
+    })
+}
+
+pub fn revoke(holder: &str, capability: &Capability) -> Result<Grant> {
+    with_store(|file, now| {
+        let (grant, entry) = file.store.revoke(holder, capability, now)?;
+        Ok((grant, vec![entry]))
+    })
+}
+
+/// Decides `request` for `holder` and records the decision. An error means the decision
+/// could not be recorded, and callers must treat it as a denial.
+pub fn check(holder: &str, request: &Capability) -> Result<bool> {
+    with_store(|file, now| {
+        let entry = file.store.check(holder, request, now);
+        let allowed = matches!(entry.event, AuditEvent::Allowed { .. });
+        Ok((allowed, vec![entry]))
+    })
+}
+
+/// Decides `request` for `holder` for callers that must not wait on the disk, such as the
+/// CEF UI thread. It sees the grants as the last other call in this process loaded or left
+/// them, and denies everything before any call has. The decision's audit entry is queued
+/// for `flush_queued`; an allowed check is only queued when `audit_allowed`.
+pub fn check_queued(holder: &str, request: &Capability, audit_allowed: bool) -> Result<bool> {
+    let entry = {
+        let published = published();
+        let store = published
+            .as_ref()
+            .ok_or_else(|| anyhow!("Capability grants of the profile are not loaded yet"))?;
+        store.check(holder, request, unix_time_ms())
+    };
+    let allowed = matches!(entry.event, AuditEvent::Allowed { .. });
+    if !allowed || audit_allowed {
+        queued().push(entry);
+    }
+    Ok(allowed)
+}
+
+/// Writes what `check_queued` queued, and the expiries since the last write.
+pub fn flush_queued() -> Result<()> {
+    with_store(|_, _| Ok(((), Vec::new())))
+}
+
+/// The newest audit entries, oldest first.
+pub fn audit_tail() -> Result<Vec<AuditEntry>> {
+    flush_queued()?;
+    read_audit(&persistence::state_dir()?, AUDIT_TAIL_LEN)
+}
+
+/// JSON-encoded list of `pulse_core::Grant`.
+pub fn list_grants_json() -> Result<String> {
+    serde_json::to_string(&list()?).context("Failed to serialize capability grants")
+}
+
+/// Revokes `{ "holder", "capability" }` and returns the removed grant as JSON.
+pub fn revoke_grant_json(serialized_request: &str) -> Result<String> {
+    let request: RevokeRequest = serde_json::from_str(serialized_request)
+        .context("Revoke request must be { \"holder\", \"capability\" }")?;
+    let grant = revoke(&request.holder, &request.capability)?;
+    serde_json::to_string(&grant).context("Failed to serialize revoked grant")
+}
+
+/// JSON-encoded list of the newest `pulse_core::AuditEntry` lines.
+pub fn capability_audit_json() -> Result<String> {
+    serde_json::to_string(&audit_tail()?).context("Failed to serialize capability audit log")
+}
+
+/// Runs `update` on the cached grants after dropping expired ones. Every entry it returns
+/// is appended to the audit log, and the file is saved when any of them changed a grant.
+/// The grants it leaves are published for `check_queued`.
+fn with_store<T>(
+    update: impl FnOnce(&mut GrantsFile, u64) -> Result<(T, Vec<AuditEntry>)>,
+) -> Result<T> {
+    let mut cached = cache();
+    let dir = persistence::state_dir()?;
+    let outcome = cached_for(&mut cached, &dir).and_then(|cached| {
+        let file = &mut cached.file;
+        // Queued checks happened first, so they go to the log first. Taken only now, so
+        // the lock `check_queued` pushes under is never held across a write.
+        let checked = std::mem::take(&mut *queued());
+        append_audit(&dir, &checked)?;
+        let now = unix_time_ms();
+        let expired = file.store.remove_expired(now);
+        if !expired.is_empty() {
+            record(&dir, file, &expired)?;
+        }
+        let (value, entries) = update(file, now)?;
+        record(&dir, file, &entries)?;
+        *published() = Some(file.store.clone());
+        Ok(value)
+    });
+    if outcome.is_err() {
+        // The next call reloads whatever actually reached the disk.
+        *cached = None;
+    }
+    outcome
+}
+
+/// The cache, loaded for `dir` unless it already is.
+fn cached_for<'a>(
+    cached: &'a mut Option<CachedGrants>,
+    dir: &Path,
+) -> Result<&'a mut CachedGrants> {
+    if cached.as_ref().is_none_or(|cached| cached.dir != dir) {
+        *cached = Some(CachedGrants {
+            file: load(dir)?,
+            dir: dir.to_path_buf(),
+        });
+    }
+    Ok(cached.as_mut().expect("grant cache was filled above"))
+}
+
+fn record(dir: &Path, file: &GrantsFile, entries: &[AuditEntry]) -> Result<()> {
+    let changed = entries
+        .iter()
+        .any(|entry| !matches!(entry.event, AuditEvent::Allowed { .. } | AuditEvent::Denied));
+    if changed {
+        save(dir, file)?;
+    }
+    append_audit(dir, entries)
+}
+
+fn load(dir: &Path) -> Result<GrantsFile> {
+    let path = dir.join(GRANTS_FILE_NAME);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(GrantsFile {
+                version: GRANTS_FILE_VERSION,
+                ..GrantsFile::default()
+            });
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read '{}'", path.display()));
+        }
+    };
+
+    let file: GrantsFile = serde_json::from_slice(&bytes)
+        .with_context(|| format!("'{}' is not a valid grants file", path.display()))?;
+    if file.version > GRANTS_FILE_VERSION {
+        return Err(anyhow!(
+            "Unsupported grants file version={} in '{}'. This build supports up to {GRANTS_FILE_VERSION}",
+            file.version,
+            path.display()
+        ));
+    }
+    Ok(file)
+}
+
+fn save(dir: &Path, file: &GrantsFile) -> Result<()> {
+    let path = dir.join(GRANTS_FILE_NAME);
+    let temp = dir.join(GRANTS_TEMP_FILE_NAME);
+    let file = GrantsFile {
+        version: GRANTS_FILE_VERSION,
+        ..file.clone()
+    };
+    fs::write(&temp, serde_json::to_vec_pretty(&file)?)
+        .with_context(|| format!("Failed to write '{}'", temp.display()))?;
+    fs::rename(&temp, &path).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(())
+}
+
+fn append_audit(dir: &Path, entries: &[AuditEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let path = dir.join(AUDIT_FILE_NAME);
+    let mut lines = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut lines, entry)?;
+        lines.push(b'\n');
+    }
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() >= AUDIT_ROTATE_BYTES) {
+        let rotated = dir.join(ROTATED_AUDIT_FILE_NAME);
+        fs::rename(&path, &rotated).with_context(|| {
+            format!(
+                "Failed to rotate audit log '{}' to '{}'",
+                path.display(),
+                rotated.display()
+            )
+        })?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| {
+            // Start on a fresh line after a torn write, so only that line is lost.
+            let mut last = [b'\n'];
+            if file.metadata()?.len() > 0 {
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+            }
+            if last[0] != b'\n' {
+                lines.insert(0, b'\n');
+            }
+            file.write_all(&lines)
+        })
+        .with_context(|| format!("Failed to append to audit log '{}'", path.display()))
+}
+
+/// The last `limit` entries, continuing into the rotated log when the current one holds
+/// fewer. Lines that do not parse, such as one torn by a crash, are skipped.
+fn read_audit(dir: &Path, limit: usize) -> Result<Vec<AuditEntry>> {
+    let mut entries = read_audit_tail(&dir.join(AUDIT_FILE_NAME), limit)?;
+    if entries.len() < limit {
+        let mut older = read_audit_tail(&dir.join(ROTATED_AUDIT_FILE_NAME), limit - entries.len())?;
+        older.append(&mut entries);
+        entries = older;
+    }
+    Ok(entries)
+}
+
+/// The last `limit` entries of one log, read from its end only as far as they go.
+fn read_audit_tail(path: &Path, limit: usize) -> Result<Vec<AuditEntry>> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read '{}'", path.display()));
+        }
+    };
+
+    let read = |file: &mut fs::File| -> std::io::Result<(Vec<u8>, u64)> {
+        let mut start = file.metadata()?.len();
+        let mut tail = Vec::new();
+        // One line more than needed, since the first line read may be cut.
+        while start > 0 && tail.iter().filter(|byte| **byte == b'\n').count() <= limit {
+            let chunk_len = AUDIT_TAIL_CHUNK_BYTES.min(start);
+            start -= chunk_len;
+            let mut chunk = vec![0; chunk_len as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut chunk)?;
+            chunk.append(&mut tail);
+            tail = chunk;
+        }
+        Ok((tail, start))
+    };
+    let (tail, start) =
+        read(&mut file).with_context(|| format!("Failed to read '{}'", path.display()))?;
+
+    let tail = String::from_utf8_lossy(&tail);
+    let entries = tail
+        .lines()
+        .skip(usize::from(start > 0))
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .collect::<Vec<_>>();
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+/// Held while the grants file and audit log are written.
+fn cache() -> MutexGuard<'static, Option<CachedGrants>> {
+    static CACHE: OnceLock<Mutex<Option<CachedGrants>>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Copy of the cached grants that `check_queued` reads; never held across I/O.
+fn published() -> MutexGuard<'static, Option<GrantStore>> {
+    static PUBLISHED: OnceLock<Mutex<Option<GrantStore>>> = OnceLock::new();
+    PUBLISHED
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Entries of `check_queued` decisions not written yet; never held across I/O.
+fn queued() -> MutexGuard<'static, Vec<AuditEntry>> {
+    static QUEUED: OnceLock<Mutex<Vec<AuditEntry>>> = OnceLock::new();
+    QUEUED
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grants_and_audit_entries_survive_a_reload() {
+        let dir = std::env::temp_dir().join(format!("pulse-grants-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let capability: Capability = "terminal.exec:*".parse().unwrap();
+        let mut file = load(&dir).unwrap();
+        let granted = file
+            .store
+            .grant("ui", capability.clone(), 10, None)
+            .unwrap();
+        let allowed = file
+            .store
+            .check("ui", &"terminal.exec:pwsh".parse().unwrap(), 20);
+        record(&dir, &file, &[granted, allowed]).unwrap();
+
+        assert_eq!(load(&dir).unwrap(), file);
+        let (_, revoked) = file.store.revoke("ui", &capability, 30).unwrap();
+        record(&dir, &file, &[revoked]).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(AUDIT_FILE_NAME))
+            .and_then(|mut audit| audit.write_all(b"{\"at_unix_ms\":4"))
+            .unwrap();
+
+        let denied = file
+            .store
+            .check("ui", &"terminal.exec:pwsh".parse().unwrap(), 40);
+        record(&dir, &file, &[denied]).unwrap();
+
+        assert!(load(&dir).unwrap().store.grants().is_empty());
+        let events = read_audit(&dir, 3)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                AuditEvent::Allowed {
+                    grant: capability.clone()
+                },
+                AuditEvent::Revoked,
+                AuditEvent::Denied
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_full_audit_log_is_rotated_and_read_from_both_files() {
+        let dir = std::env::temp_dir().join(format!("pulse-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut store = GrantStore::default();
+        let capability: Capability = "terminal.exec:*".parse().unwrap();
+        let granted = store.grant("ui", capability.clone(), 10, None).unwrap();
+        append_audit(&dir, &[granted]).unwrap();
+        let padding = vec![b'x'; AUDIT_ROTATE_BYTES as usize];
+        fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(AUDIT_FILE_NAME))
+            .and_then(|mut audit| audit.write_all(&padding))
+            .unwrap();
+
+        let (_, revoked) = store.revoke("ui", &capability, 20).unwrap();
+        append_audit(&dir, &[revoked]).unwrap();
+        assert!(dir.join(ROTATED_AUDIT_FILE_NAME).exists());
+        assert!(fs::metadata(dir.join(AUDIT_FILE_NAME)).unwrap().len() < AUDIT_TAIL_CHUNK_BYTES);
+
+        let events = |limit| {
+            read_audit(&dir, limit)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.event)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(events(1), [AuditEvent::Revoked]);
+        assert_eq!(
+            events(5),
+            [
+                AuditEvent::Granted {
+                    expires_at_unix_ms: None
+                },
+                AuditEvent::Revoked
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn queued_checks_use_the_published_grants_and_reach_the_log_on_flush() {
+        let root = std::env::temp_dir().join(format!("pulse-queued-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        persistence::set_profile_root(root.clone());
+
+        let layout: Capability = "ui.layout".parse().unwrap();
+        let terminal: Capability = "terminal.exec:pwsh".parse().unwrap();
+        seed_defaults("ui", std::slice::from_ref(&layout)).unwrap();
+        assert!(check_queued("ui", &layout, false).unwrap());
+        assert!(!check_queued("ui", &terminal, false).unwrap());
+        assert_eq!(queued().len(), 1);
+
+        let events = audit_tail()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                AuditEvent::Granted {
+                    expires_at_unix_ms: None
+                },
+                AuditEvent::Denied
+            ]
+        );
+        assert!(queued().is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
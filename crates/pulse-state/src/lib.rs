@@ -0,0 +1,45 @@
+//! Workspace state storage shared by the Pulse shell and the offline `pulse-state` CLI.
+//! Nothing here depends on CEF.
+
+use anyhow::{Context, Result, anyhow};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::Path;
+
+pub mod bundle;
+pub mod encryption;
+pub mod favicons;
+pub mod grants;
+pub mod migrations;
+pub mod paths;
+pub mod persistence;
+pub mod profiles;
+pub mod snapshots;
+pub mod ui_session;
+
+/// Lock file held by the running Pulse browser process for its profile root.
+pub const PROFILE_LOCK_FILE_NAME: &str = "pulse.lock";
+
+/// Takes the profile lock without waiting, so offline writes cannot race a running Pulse.
+pub fn lock_profile(root: &Path) -> Result<File> {
+    fs::create_dir_all(root)
+        .with_context(|| format!("Failed to create profile root '{}'", root.display()))?;
+    let lock_path = root.join(PROFILE_LOCK_FILE_NAME);
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file '{}'", lock_path.display()))?;
+
+    match lock_file.try_lock() {
+        Ok(()) => Ok(lock_file),
+        Err(TryLockError::WouldBlock) => Err(anyhow!(
+            "Pulse is running on profile '{}'; close it or work on a copy of the profile",
+            root.display()
+        )),
+        Err(TryLockError::Error(err)) => {
+            Err(err).with_context(|| format!("Failed to lock '{}'", lock_path.display()))
+        }
+    }
+}
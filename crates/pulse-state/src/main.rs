@@ -0,0 +1,472 @@
+//! `pulse-state`: inspects, validates and repairs a Pulse profile's workspace state
+//! without starting CEF. Pick a named profile with `--profile`, or point it at a copied
+//! profile with `--profile-dir`.
+
+use anyhow::{Result, anyhow};
+use pulse_state::bundle::ImportMode;
+use pulse_state::paths::{self, PulseDirs};
+use pulse_state::persistence::{self, InspectedState, StateSource};
+use pulse_state::{encryption, lock_profile};
+use pulse_state::{grants, profiles};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: pulse-state [--profile <name> | --profile-dir <dir>] <command> [args]
+
+Commands:
+  dump [source]           Print the stored envelope (decrypted, before migrations)
+  validate                Check the primary, backup and every snapshot
+  diff                    Compare the primary and backup workspaces
+  migrate [--dry-run]     Upgrade the primary to the current schema, with normal recovery
+  snapshots               List snapshots, newest first
+  restore <source>        Make the backup, a snapshot or a file the current state
+  export <file> [source]  Write a plaintext envelope in the current schema
+  import <file>           Validate an exported envelope and make it the current state
+  export-bundle <file>    Package the current workspace as a .pulse bundle
+  import-bundle <file> [--merge | --replace]
+                          Merge a .pulse bundle into the workspace (default) or replace it
+  grants                  List capability grants
+  grants grant <holder> <capability> [--days <n>]
+                          Grant a capability such as fs.read:/home/ada, optionally expiring
+  grants revoke <holder> <capability>
+  grants audit            Print the newest capability audit log entries
+
+Profile commands (always on the profile registry in the Pulse data directory):
+  profiles                List profiles; * marks the selected one
+  profiles create <name>  Register a new, empty profile
+  profiles rename <profile> <new-name>
+  profiles delete <profile>
+                          Remove a profile and all of its data (not the default profile)
+  profiles switch <profile>
+                          Launch <profile> when Pulse starts without --profile
+
+Sources: primary (default), backup, snapshot:<id>, or a path to an envelope file.
+The profile defaults to the selected one. Encrypted profiles need
+PULSE_STATE_KEY_FILE pointing at their key file.
+
+validate and diff exit with 1 when they find a problem or a difference.";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("pulse-state error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Whether `command` changes the profile or the profile registry.
+fn writes(command: &str, rest: &[&str]) -> bool {
+    match (command, rest) {
+        ("migrate", ["--dry-run"]) | ("grants", ["audit"]) | ("profiles", []) => false,
+        ("migrate" | "restore" | "import" | "import-bundle" | "grants" | "profiles", _) => true,
+        _ => false,
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<ExitCode> {
+    let profile_dir = take_flag_value(&mut args, "--profile-dir")?;
+    let profile_name = take_flag_value(&mut args, "--profile")?;
+
+    if args.is_empty() {
+        println!("{USAGE}");
+        return Ok(ExitCode::FAILURE);
+    }
+    let command = args.remove(0);
+    let rest = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let dirs = PulseDirs::resolve();
+    // Only commands that write to the data directory move old layouts into place; reading
+    // leaves it to the shell, and `--profile-dir` does not use the data directory at all.
+    let uses_data_dir = profile_dir.is_none() || command == "profiles";
+    if uses_data_dir && writes(&command, &rest) {
+        for migrate in [paths::migrate_legacy_root, paths::migrate_default_profile] {
+            if let Some(migration) = migrate(&dirs)? {
+                eprintln!(
+                    "pulse-state: moved {} entries from '{}'",
+                    migration.moved.len(),
+                    migration.from.display()
+                );
+            }
+        }
+    }
+    if command == "profiles" {
+        return manage_profiles(&dirs, &rest);
+    }
+    let profile_root = match (profile_dir, profile_name) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!("Use either --profile or --profile-dir, not both"));
+        }
+        (Some(dir), None) => PathBuf::from(dir),
+        (None, name) => profiles::resolve(&dirs, name.as_deref())?.1.root,
+    };
+    persistence::set_profile_root(profile_root.clone());
+
+    match (command.as_str(), rest.as_slice()) {
+        ("dump", source) if source.len() <= 1 => dump(&parse_source(source.first().copied())?),
+        ("validate", []) => validate(),
+        ("diff", []) => diff(),
+        ("migrate", []) => {
+            let _lock = lock_profile(&profile_root)?;
+            migrate()
+        }
+        ("migrate", ["--dry-run"]) => migrate_dry_run(),
+        ("snapshots", []) => list_snapshots(),
+        ("restore", [source]) => {
+            let source = parse_source(Some(source))?;
+            let _lock = lock_profile(&profile_root)?;
+            restore(&source)
+        }
+        ("export", [file, source @ ..]) if source.len() <= 1 => {
+            export(Path::new(file), &parse_source(source.first().copied())?)
+        }
+        ("import", [file]) => {
+            let _lock = lock_profile(&profile_root)?;
+            restore(&StateSource::File(PathBuf::from(file)))
+        }
+        ("export-bundle", [file]) => export_bundle(Path::new(file)),
+        ("import-bundle", [file, mode @ ..]) if mode.len() <= 1 => {
+            let mode = match mode.first().copied() {
+                None | Some("--merge") => ImportMode::Merge,
+                Some("--replace") => ImportMode::Replace,
+                Some(other) => return Err(anyhow!("Unknown import mode '{other}'")),
+            };
+            let _lock = lock_profile(&profile_root)?;
+            import_bundle(Path::new(file), mode)
+        }
+        ("grants", ["audit"]) => manage_grants(&["audit"]),
+        ("grants", args) => {
+            // Listing also drops expired grants, so every form but `audit` writes.
+            let _lock = lock_profile(&profile_root)?;
+            manage_grants(args)
+        }
+        ("help" | "--help" | "-h", []) => {
+            println!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(anyhow!(
+            "Unknown command or arguments: '{}'\n\n{USAGE}",
+            std::iter::once(command.as_str())
+                .chain(rest.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )),
+    }
+}
+
+fn dump(source: &StateSource) -> Result<ExitCode> {
+    let inspected = inspect_existing(source)?;
+    eprintln!("{}", describe(&inspected));
+    println!("{}", serde_json::to_string_pretty(&inspected.envelope)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate() -> Result<ExitCode> {
+    let mut sources = vec![StateSource::Primary, StateSource::Backup];
+    sources.extend(
+        persistence::list_snapshots()?
+            .into_iter()
+            .map(|snapshot| StateSource::Snapshot(snapshot.id)),
+    );
+
+    let mut failures = 0;
+    for source in &sources {
+        match persistence::inspect_state(source) {
+            Ok(Some(inspected)) => println!("ok       {source}: {}", describe(&inspected)),
+            Ok(None) => println!("missing  {source}"),
+            Err(err) => {
+                failures += 1;
+                println!("FAILED   {source}: {err:#}");
+            }
+        }
+    }
+
+    if failures > 0 {
+        println!(
+            "{failures} of {} state files failed validation",
+            sources.len()
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff() -> Result<ExitCode> {
+    let primary = inspect_existing(&StateSource::Primary)?;
+    let backup = inspect_existing(&StateSource::Backup)?;
+    println!("primary: {}", describe(&primary));
+    println!("backup:  {}", describe(&backup));
+
+    let primary_tables = serde_json::to_value(&primary.workspace)?;
+    let backup_tables = serde_json::to_value(&backup.workspace)?;
+    let mut differences = 0;
+    for (table, primary_rows) in object_entries(&primary_tables) {
+        let backup_rows = backup_tables.get(table).unwrap_or(&Value::Null);
+        for (id, row) in object_entries(primary_rows) {
+            match backup_rows.get(id) {
+                None => println!("+ {table}.{id} (primary only): {row}"),
+                Some(backup_row) if backup_row != row => {
+                    println!("~ {table}.{id}\n    primary: {row}\n    backup:  {backup_row}");
+                }
+                Some(_) => continue,
+            }
+            differences += 1;
+        }
+        for (id, row) in object_entries(backup_rows) {
+            if primary_rows.get(id).is_none() {
+                println!("- {table}.{id} (backup only): {row}");
+                differences += 1;
+            }
+        }
+    }
+
+    if differences > 0 {
+        println!("{differences} row(s) differ");
+        return Ok(ExitCode::FAILURE);
+    }
+    println!("Workspaces are identical");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn migrate() -> Result<ExitCode> {
+    // A normal load migrates, re-seals and recovers exactly as a Pulse launch would.
+    persistence::load_workspace_store()?;
+    if let Some(report) = persistence::last_recovery_report_json()? {
+        let report: Value = serde_json::from_str(&report)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn migrate_dry_run() -> Result<ExitCode> {
+    let inspected = inspect_existing(&StateSource::Primary)?;
+    if inspected.pending_migrations.is_empty() {
+        println!("Primary state is already at the current schema");
+    }
+    for migration in &inspected.pending_migrations {
+        println!(
+            "v{} -> v{}: {}",
+            migration.from_version,
+            migration.from_version + 1,
+            migration.description
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn list_snapshots() -> Result<ExitCode> {
+    for snapshot in persistence::list_snapshots()? {
+        println!("{}  {} bytes", snapshot.id, snapshot.size_bytes);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn restore(source: &StateSource) -> Result<ExitCode> {
+    let workspace = persistence::restore_state(source)?;
+    println!(
+        "Restored {source} as the current state ({} items); the previous primary is now the backup",
+        workspace.entries().len()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn export(destination: &Path, source: &StateSource) -> Result<ExitCode> {
+    persistence::export_state(source, destination)?;
+    println!("Exported {source} to '{}'", destination.display());
+    if encryption::configured_provider().is_some() {
+        eprintln!("pulse-state warning: the export is not encrypted");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn export_bundle(destination: &Path) -> Result<ExitCode> {
+    let manifest = persistence::export_bundle(destination)?;
+    println!(
+        "Exported {} items (schema v{}) to '{}'",
+        manifest.item_count,
+        manifest.schema_version,
+        destination.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn import_bundle(source: &Path, mode: ImportMode) -> Result<ExitCode> {
+    let import = persistence::import_bundle(source, mode)?;
+    println!(
+        "Imported {} items from '{}' ({mode:?}); the previous primary is now the backup",
+        import.manifest.item_count,
+        source.display()
+    );
+    for (original, renamed) in &import.remapped_ids {
+        println!("  renamed {original} -> {renamed}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn manage_profiles(dirs: &PulseDirs, args: &[&str]) -> Result<ExitCode> {
+    match args {
+        [] => {
+            let registry = profiles::load(&dirs.data)?;
+            for profile in &registry.profiles {
+                let marker = if profile.id == registry.selected {
+                    '*'
+                } else {
+                    ' '
+                };
+                println!(
+                    "{marker} {:<24} {:<24} {}",
+                    profile.name,
+                    profile.id,
+                    dirs.profile(&profile.id).root.display()
+                );
+            }
+        }
+        ["create", name] => {
+            let profile = profiles::create(dirs, name)?;
+            println!(
+                "Created profile '{}' at '{}'; launch it with --profile {}",
+                profile.name,
+                dirs.profile(&profile.id).root.display(),
+                profile.id
+            );
+        }
+        ["rename", profile, new_name] => {
+            let profile = profiles::rename(dirs, profile, new_name)?;
+            println!("Renamed profile '{}' to '{}'", profile.id, profile.name);
+        }
+        ["delete", profile] => {
+            let profile = profiles::delete(dirs, profile)?;
+            println!("Deleted profile '{}' and its data", profile.name);
+        }
+        ["switch", profile] => {
+            let profile = profiles::switch(dirs, profile)?;
+            println!("Pulse now starts with profile '{}'", profile.name);
+        }
+        _ => {
+            return Err(anyhow!(
+                "Unknown profiles arguments: '{}'\n\n{USAGE}",
+                args.join(" ")
+            ));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn manage_grants(args: &[&str]) -> Result<ExitCode> {
+    match args {
+        [] => {
+            for grant in grants::list()? {
+                let expires = grant
+                    .expires_at_unix_ms
+                    .map(|expires_at| format!("expires_at_unix_ms {expires_at}"))
+                    .unwrap_or_else(|| String::from("no expiry"));
+                println!(
+                    "{:<24} {:<48} granted_at_unix_ms {}, {expires}",
+                    grant.holder,
+                    grant.capability.to_string(),
+                    grant.granted_at_unix_ms
+                );
+            }
+        }
+        ["grant", holder, capability, expiry @ ..] => {
+            let capability = capability.parse()?;
+            let expires_at_unix_ms = match expiry {
+                [] => None,
+                ["--days", days] => {
+                    let days = days
+                        .parse::<u64>()
+                        .map_err(|_| anyhow!("--days needs a whole number, got '{days}'"))?;
+                    let now = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)?
+                        .as_millis() as u64;
+                    let expires_at = days
+                        .checked_mul(24 * 60 * 60 * 1000)
+                        .and_then(|duration_ms| now.checked_add(duration_ms))
+                        .ok_or_else(|| anyhow!("--days {days} is too far in the future"))?;
+                    Some(expires_at)
+                }
+                _ => return Err(anyhow!("Unknown grant arguments: '{}'", expiry.join(" "))),
+            };
+            let grant = grants::grant(holder, capability, expires_at_unix_ms)?;
+            println!("Granted '{}' to '{}'", grant.capability, grant.holder);
+        }
+        ["revoke", holder, capability] => {
+            let grant = grants::revoke(holder, &capability.parse()?)?;
+            println!("Revoked '{}' from '{}'", grant.capability, grant.holder);
+        }
+        ["audit"] => {
+            for entry in grants::audit_tail()? {
+                println!("{}", serde_json::to_string(&entry)?);
+            }
+        }
+        _ => {
+            return Err(anyhow!(
+                "Unknown grants arguments: '{}'\n\n{USAGE}",
+                args.join(" ")
+            ));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn inspect_existing(source: &StateSource) -> Result<InspectedState> {
+    persistence::inspect_state(source)?.ok_or_else(|| anyhow!("{source} does not exist"))
+}
+
+fn describe(inspected: &InspectedState) -> String {
+    let encryption = match &inspected.sealed_with {
+        Some(key_id) => format!("sealed with key '{key_id}'"),
+        None => String::from("plaintext"),
+    };
+    let updated = inspected
+        .envelope
+        .get("updated_at_unix_ms")
+        .and_then(Value::as_u64)
+        .map(|updated| format!(", updated_at_unix_ms {updated}"))
+        .unwrap_or_default();
+    format!(
+        "'{}': schema v{}, {} pending migration(s), {encryption}, {} items{updated}",
+        inspected.path.display(),
+        inspected.source_version,
+        inspected.pending_migrations.len(),
+        inspected.workspace.entries().len()
+    )
+}
+
+fn parse_source(value: Option<&str>) -> Result<StateSource> {
+    Ok(match value {
+        None | Some("primary") => StateSource::Primary,
+        Some("backup") => StateSource::Backup,
+        Some(value) => match value.strip_prefix("snapshot:") {
+            Some("") => return Err(anyhow!("Missing snapshot id in '{value}'")),
+            Some(id) => StateSource::Snapshot(String::from(id)),
+            None => StateSource::File(PathBuf::from(value)),
+        },
+    })
+}
+
+fn object_entries(value: &Value) -> impl Iterator<Item = (&String, &Value)> {
+    value.as_object().into_iter().flatten()
+}
+
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>> {
+    let equals_prefix = format!("{flag}=");
+    let Some(index) = args
+        .iter()
+        .position(|arg| arg == flag || arg.starts_with(&equals_prefix))
+    else {
+        return Ok(None);
+    };
+
+    let arg = args.remove(index);
+    if let Some(value) = arg.strip_prefix(&equals_prefix) {
+        return Ok(Some(String::from(value)));
+    }
+    if index < args.len() {
+        return Ok(Some(args.remove(index)));
+    }
+    Err(anyhow!("{flag} needs a value"))
+}
@@ -0,0 +1,308 @@
+use anyhow::{Context, Result, anyhow};
+use pulse_core::WorkspaceStore;
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::ui_session::store_from_ui_session;
+
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+const CHECKSUM_PREFIX: &str = "sha256:";
+
+/// One `vN -> vN+1` upgrade step over the raw JSON envelope.
+pub struct Migration {
+    pub from_version: u32,
+    pub description: &'static str,
+    migrate: fn(Value) -> Result<Value>,
+}
+
+/// Ordered upgrade chain. Entry `i` must migrate from `v{i}`; the last entry lands on
+/// `CURRENT_SCHEMA_VERSION`. Append a step (and a fixture) whenever the envelope changes.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 0,
+        description: "wrap unversioned UI payload in the v1 envelope",
+        migrate: migrate_v0_to_v1,
+    },
+    Migration {
+        from_version: 1,
+        description: "convert UI-owned ui_state into host workspace tables",
+        migrate: migrate_v1_to_v2,
+    },
+    Migration {
+        from_version: 2,
+        description: "add sha256 checksum over the workspace tables",
+        migrate: migrate_v2_to_v3,
+    },
+];
+
+pub struct MigrationOutcome {
+    pub source_version: u32,
+    pub workspace: WorkspaceStore,
+    pub applied: Vec<&'static Migration>,
+}
+
+/// Reads `schema_version` from a raw envelope; unversioned payloads are `v0`.
+pub fn detect_schema_version(raw: &Value) -> Result<u32> {
+    let Some(schema_value) = raw.as_object().and_then(|raw| raw.get("schema_version")) else {
+        return Ok(0);
+    };
+
+    schema_value
+        .as_u64()
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or_else(|| {
+            anyhow!("Persisted state schema_version must be an integer, got '{schema_value}'")
+        })
+}
+
+/// Runs every pending migration on `raw` and decodes the result. Nothing touches disk, so
+/// this doubles as the dry-run validation path.
+pub fn migrate_to_current(raw: Value) -> Result<MigrationOutcome> {
+    let source_version = detect_schema_version(&raw)?;
+    if source_version > CURRENT_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "Unsupported persisted state schema_version={source_version}. This build supports up to {CURRENT_SCHEMA_VERSION}"
+        ));
+    }
+
+    let mut envelope = raw;
+    let mut applied = Vec::new();
+    for migration in pending_migrations(source_version) {
+        envelope = (migration.migrate)(envelope).with_context(|| {
+            format!(
+                "Migration v{} -> v{} ({}) failed",
+                migration.from_version,
+                migration.from_version + 1,
+                migration.description
+            )
+        })?;
+
+        let reached = detect_schema_version(&envelope)?;
+        if reached != migration.from_version + 1 {
+            return Err(anyhow!(
+                "Migration from v{} produced schema_version={reached}",
+                migration.from_version
+            ));
+        }
+        applied.push(migration);
+    }
+
+    let workspace = decode_current(&envelope)?;
+    Ok(MigrationOutcome {
+        source_version,
+        workspace,
+        applied,
+    })
+}
+
+pub fn pending_migrations(source_version: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(move |migration| migration.from_version >= source_version)
+}
+
+fn decode_current(envelope: &Value) -> Result<WorkspaceStore> {
+    let Some(workspace) = envelope.get("workspace") else {
+        return Err(anyhow!(
+            "Persisted state schema v{CURRENT_SCHEMA_VERSION} is missing required 'workspace' field"
+        ));
+    };
+    let workspace: WorkspaceStore =
+        serde_json::from_value(workspace.clone()).with_context(|| {
+            format!(
+                "Persisted state schema v{CURRENT_SCHEMA_VERSION} has a malformed 'workspace' field"
+            )
+        })?;
+
+    let Some(stored_checksum) = envelope.get("checksum").and_then(Value::as_str) else {
+        return Err(anyhow!(
+            "Persisted state schema v{CURRENT_SCHEMA_VERSION} is missing required 'checksum' field"
+        ));
+    };
+    let computed_checksum = workspace_checksum(&workspace)?;
+    if stored_checksum != computed_checksum {
+        return Err(anyhow!(
+            "Persisted workspace checksum mismatch: stored '{stored_checksum}', computed '{computed_checksum}'"
+        ));
+    }
+
+    workspace
+        .validate()
+        .context("Persisted workspace tables violate workspace invariants")?;
+    Ok(workspace)
+}
+
+/// `sha256:<hex>` over the compact serialization of the workspace tables. Tables are
+/// ordered maps, so the encoding (and the hash) is deterministic for equal stores.
+pub fn workspace_checksum(workspace: &WorkspaceStore) -> Result<String> {
+    let canonical =
+        serde_json::to_vec(workspace).context("Failed to encode workspace for checksum")?;
+    Ok(sha256_checksum(&canonical))
+}
+
+/// `sha256:<hex>` over `bytes`.
+pub fn sha256_checksum(bytes: &[u8]) -> String {
+    let hex = Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("{CHECKSUM_PREFIX}{hex}")
+}
+
+fn migrate_v0_to_v1(raw: Value) -> Result<Value> {
+    let ui_state = match raw {
+        Value::Object(mut raw_obj) if raw_obj.contains_key("schema_version") => raw_obj
+            .remove("state")
+            .or_else(|| raw_obj.remove("ui_state"))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Legacy persisted state schema v0 must contain either 'state' or 'ui_state'"
+                )
+            })?,
+        // Unversioned files hold the UI payload itself.
+        raw => raw,
+    };
+
+    Ok(json!({
+        "schema_version": 1,
+        "updated_at_unix_ms": 0,
+        "ui_state": ui_state,
+    }))
+}
+
+fn migrate_v1_to_v2(raw: Value) -> Result<Value> {
+    let mut raw_obj = into_object(raw)?;
+    let Some(ui_state) = raw_obj.remove("ui_state") else {
+        return Err(anyhow!(
+            "Persisted state schema v1 is missing required 'ui_state' field"
+        ));
+    };
+
+    let imported = store_from_ui_session(&ui_state)
+        .context("UI state payload could not be converted to workspace tables")?;
+    for reason in &imported.dropped {
+        eprintln!("Pulse state warning: migration skipped UI workspace {reason}");
+    }
+    let workspace = imported.store;
+    raw_obj.insert("schema_version".into(), json!(2));
+    raw_obj.insert("workspace".into(), serde_json::to_value(workspace)?);
+    Ok(Value::Object(raw_obj))
+}
+
+fn migrate_v2_to_v3(raw: Value) -> Result<Value> {
+    let mut raw_obj = into_object(raw)?;
+    let Some(workspace) = raw_obj.get("workspace") else {
+        return Err(anyhow!(
+            "Persisted state schema v2 is missing required 'workspace' field"
+        ));
+    };
+
+    let workspace: WorkspaceStore = serde_json::from_value(workspace.clone())
+        .context("Persisted state schema v2 has a malformed 'workspace' field")?;
+    raw_obj.insert("schema_version".into(), json!(3));
+    raw_obj.insert("checksum".into(), json!(workspace_checksum(&workspace)?));
+    Ok(Value::Object(raw_obj))
+}
+
+fn into_object(raw: Value) -> Result<Map<String, Value>> {
+    match raw {
+        Value::Object(raw_obj) => Ok(raw_obj),
+        _ => Err(anyhow!("Persisted state envelope must be a JSON object")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &[(&str, &str)] = &[
+        (
+            "v0-raw.json",
+            include_str!("../tests/fixtures/state/v0-raw.json"),
+        ),
+        (
+            "v0-envelope.json",
+            include_str!("../tests/fixtures/state/v0-envelope.json"),
+        ),
+        ("v1.json", include_str!("../tests/fixtures/state/v1.json")),
+        ("v2.json", include_str!("../tests/fixtures/state/v2.json")),
+        ("v3.json", include_str!("../tests/fixtures/state/v3.json")),
+    ];
+
+    fn current_fixture() -> Value {
+        fixture(&format!("v{CURRENT_SCHEMA_VERSION}.json"))
+    }
+
+    fn fixture(name: &str) -> Value {
+        let (_, text) = FIXTURES
+            .iter()
+            .find(|(fixture_name, _)| *fixture_name == name)
+            .expect("unknown fixture");
+        serde_json::from_str(text).expect("fixture is valid JSON")
+    }
+
+    #[test]
+    fn registry_is_a_contiguous_chain_to_current() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.from_version, index as u32);
+        }
+        assert_eq!(MIGRATIONS.len() as u32, CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn every_historical_fixture_migrates_to_the_golden_workspace() {
+        let golden = migrate_to_current(current_fixture()).expect("golden fixture decodes");
+        assert!(golden.applied.is_empty());
+
+        let mut seen_versions = Vec::new();
+        for (name, _) in FIXTURES {
+            let outcome = migrate_to_current(fixture(name))
+                .unwrap_or_else(|error| panic!("{name} failed to migrate: {error:#}"));
+            assert_eq!(outcome.workspace, golden.workspace, "{name}");
+            assert_eq!(
+                outcome.applied.len() as u32,
+                CURRENT_SCHEMA_VERSION - outcome.source_version,
+                "{name}"
+            );
+            seen_versions.push(outcome.source_version);
+        }
+
+        for version in 0..=CURRENT_SCHEMA_VERSION {
+            assert!(
+                seen_versions.contains(&version),
+                "missing fixture for schema v{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_schema_versions_from_newer_builds() {
+        let mut future = current_fixture();
+        future["schema_version"] = json!(CURRENT_SCHEMA_VERSION + 1);
+        assert!(migrate_to_current(future).is_err());
+    }
+
+    #[test]
+    fn rejects_current_envelopes_that_break_workspace_invariants() {
+        let mut broken = current_fixture();
+        broken["workspace"]["items"]["tab-edge"]["parent_id"] = json!("missing-group");
+        // Re-sign so the invariant check, not the checksum, is what rejects the file.
+        let workspace: WorkspaceStore =
+            serde_json::from_value(broken["workspace"].clone()).expect("tables still decode");
+        broken["checksum"] = json!(workspace_checksum(&workspace).expect("checksum"));
+        let Err(error) = migrate_to_current(broken) else {
+            panic!("broken tree must be rejected");
+        };
+        assert!(format!("{error:#}").contains("invariants"));
+    }
+
+    #[test]
+    fn rejects_current_envelopes_whose_checksum_does_not_match() {
+        let mut tampered = current_fixture();
+        tampered["workspace"]["tabs"]["tab-edge"]["url"] = json!("https://example.invalid");
+        let Err(error) = migrate_to_current(tampered) else {
+            panic!("tampered payload must be rejected");
+        };
+        assert!(format!("{error:#}").contains("checksum mismatch"));
+    }
+}
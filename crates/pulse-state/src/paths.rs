@@ -0,0 +1,433 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{PROFILE_LOCK_FILE_NAME, lock_profile, profiles};
+
+/// Overrides the `Pulse` directory name under every base directory.
+const APP_DIR_ENV: &str = "PULSE_CACHE_ROOT";
+const APP_DIR_NAME: &str = "Pulse";
+const PROFILES_DIR_NAME: &str = "profiles";
+const LOG_FILE_NAME: &str = "debug.log";
+const STATE_DIR_NAME: &str = "state";
+const EXPORTS_DIR_NAME: &str = "exports";
+const FAVICONS_DIR_NAME: &str = "favicons";
+
+/// Base directories for Pulse's files. Windows keeps all three in `%LOCALAPPDATA%\Pulse`;
+/// Linux follows the XDG base directory spec and macOS uses `~/Library`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PulseDirs {
+    /// Profile registry, workspace state and exports. Must survive reboots and cleaners.
+    pub data: PathBuf,
+    /// CEF root cache: HTTP cache, but also cookies and site data.
+    pub cache: PathBuf,
+    /// Logs.
+    pub state: PathBuf,
+}
+
+/// Where one profile's files live: `profiles/<id>` below each base directory. The default
+/// profile is no exception, so no profile's CEF root cache is nested in another's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfilePaths {
+    /// Workspace state, exports and the profile lock.
+    pub root: PathBuf,
+    /// CEF `root_cache_path`; the browser cache is `cache/default` below it.
+    pub cache_root: PathBuf,
+    pub log_file: PathBuf,
+}
+
+/// What `migrate_legacy_root` or `migrate_default_profile` moved, and out of where.
+#[derive(Debug)]
+pub struct LegacyMigration {
+    pub from: PathBuf,
+    pub moved: Vec<PathBuf>,
+    /// Entries left behind because the destination already existed.
+    pub skipped: Vec<PathBuf>,
+}
+
+impl PulseDirs {
+    pub fn resolve() -> Self {
+        Self::for_platform(std::env::consts::OS, |name| {
+            std::env::var_os(name).map(PathBuf::from)
+        })
+    }
+
+    /// Data, cache and logs all in `root`, as before platform paths were resolved.
+    pub fn single_root(root: PathBuf) -> Self {
+        Self {
+            data: root.clone(),
+            cache: root.clone(),
+            state: root,
+        }
+    }
+
+    fn for_platform(os: &str, var: impl Fn(&str) -> Option<PathBuf>) -> Self {
+        let app_dir = var(APP_DIR_ENV)
+            .filter(|name| !name.as_os_str().is_empty())
+            .unwrap_or_else(|| PathBuf::from(APP_DIR_NAME));
+        // Relative values are invalid for every variable read here, XDG ones included.
+        let absolute = |name: &str| var(name).filter(|path| path.is_absolute());
+        let home = absolute("HOME");
+        let base = |xdg_var: Option<&str>, home_relative: &str| {
+            xdg_var
+                .and_then(absolute)
+                .or_else(|| home.as_ref().map(|home| home.join(home_relative)))
+                .map(|base| base.join(&app_dir))
+                .unwrap_or_else(|| legacy_root_for(&app_dir))
+        };
+
+        match os {
+            "windows" => Self::single_root(
+                absolute("LOCALAPPDATA")
+                    .map(|base| base.join(&app_dir))
+                    .unwrap_or_else(|| legacy_root_for(&app_dir)),
+            ),
+            "macos" => Self {
+                data: base(None, "Library/Application Support"),
+                cache: base(None, "Library/Caches"),
+                state: base(None, "Library/Logs"),
+            },
+            _ => Self {
+                data: base(Some("XDG_DATA_HOME"), ".local/share"),
+                cache: base(Some("XDG_CACHE_HOME"), ".cache"),
+                state: base(Some("XDG_STATE_HOME"), ".local/state"),
+            },
+        }
+    }
+
+    pub fn profile(&self, id: &str) -> ProfilePaths {
+        let below = |base: &Path| base.join(PROFILES_DIR_NAME).join(id);
+        ProfilePaths {
+            root: below(&self.data),
+            cache_root: below(&self.cache),
+            log_file: below(&self.state).join(LOG_FILE_NAME),
+        }
+    }
+}
+
+/// Where every Pulse file lived before platform paths: `<temp dir>/Pulse`.
+pub fn legacy_root() -> PathBuf {
+    let app_dir = std::env::var_os(APP_DIR_ENV)
+        .filter(|name| !name.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(APP_DIR_NAME));
+    legacy_root_for(&app_dir)
+}
+
+fn legacy_root_for(app_dir: &Path) -> PathBuf {
+    std::env::temp_dir().join(app_dir)
+}
+
+/// Moves profiles from the legacy temp-dir root into `dirs`, once: only while `dirs.data`
+/// has no registry or state yet. Refuses while a build using the legacy root is running.
+pub fn migrate_legacy_root(dirs: &PulseDirs) -> Result<Option<LegacyMigration>> {
+    migrate_root(&legacy_root(), dirs)
+}
+
+/// Moves the default profile from the base directories themselves, where builds before
+/// `profiles/default` kept it, into `profiles/default`, once: only while that does not exist
+/// yet. Refuses while a build using the old layout is running.
+pub fn migrate_default_profile(dirs: &PulseDirs) -> Result<Option<LegacyMigration>> {
+    let to = dirs.profile(profiles::DEFAULT_PROFILE_ID);
+    let bases = [&dirs.data, &dirs.cache, &dirs.state];
+    let mut bases = bases
+        .into_iter()
+        .filter(|base| base.is_dir())
+        .collect::<Vec<_>>();
+    bases.dedup();
+    if to.root.exists() || bases.is_empty() {
+        return Ok(None);
+    }
+
+    let lock = if dirs.data.is_dir() {
+        Some(lock_profile(&dirs.data)?)
+    } else {
+        None
+    };
+    let mut migration = LegacyMigration {
+        from: dirs.data.clone(),
+        moved: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for base in bases {
+        for entry in read_dir(base)? {
+            if let Some(destination) = profile_entry_destination(&entry.file_name(), &to) {
+                move_entry(&entry.path(), &destination, &mut migration)?;
+            }
+        }
+    }
+    // Marks the move as done even when the default profile had nothing to move yet.
+    fs::create_dir_all(&to.root)
+        .with_context(|| format!("Failed to create '{}'", to.root.display()))?;
+    drop(lock);
+    let _ = fs::remove_file(dirs.data.join(PROFILE_LOCK_FILE_NAME));
+    Ok((!migration.moved.is_empty() || !migration.skipped.is_empty()).then_some(migration))
+}
+
+/// Where an entry of an old profile root goes in `to`; `None` for entries that stay.
+fn profile_entry_destination(name: &std::ffi::OsStr, to: &ProfilePaths) -> Option<PathBuf> {
+    match name.to_str() {
+        Some(PROFILE_LOCK_FILE_NAME | PROFILES_DIR_NAME | profiles::REGISTRY_FILE_NAME) => None,
+        // Single-instance endpoint files belong to the process that wrote them.
+        Some(name) if name.starts_with("pulse.instance") => None,
+        Some(STATE_DIR_NAME | EXPORTS_DIR_NAME | FAVICONS_DIR_NAME) => Some(to.root.join(name)),
+        Some(LOG_FILE_NAME) => Some(to.log_file.clone()),
+        // Everything else is CEF's: `cache/`, `Local State` and friends.
+        _ => Some(to.cache_root.join(name)),
+    }
+}
+
+fn migrate_root(legacy: &Path, dirs: &PulseDirs) -> Result<Option<LegacyMigration>> {
+    let already_populated = dirs.data.join(profiles::REGISTRY_FILE_NAME).exists()
+        || dirs.data.join(STATE_DIR_NAME).exists()
+        || dirs.data.join(PROFILES_DIR_NAME).exists();
+    if !legacy.is_dir() || legacy == dirs.data || already_populated {
+        return Ok(None);
+    }
+
+    let registry = profiles::load(legacy)?;
+    // The legacy root predates `profiles/default`: the default profile is the root itself.
+    let legacy_root_of = |id: &str| {
+        if id == profiles::DEFAULT_PROFILE_ID {
+            legacy.to_path_buf()
+        } else {
+            legacy.join(PROFILES_DIR_NAME).join(id)
+        }
+    };
+    let mut locks = Vec::new();
+    for profile in &registry.profiles {
+        let root = legacy_root_of(&profile.id);
+        if root.is_dir() {
+            locks.push(lock_profile(&root)?);
+        }
+    }
+
+    let mut migration = LegacyMigration {
+        from: legacy.to_path_buf(),
+        moved: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for profile in &registry.profiles {
+        let from = legacy_root_of(&profile.id);
+        let to = dirs.profile(&profile.id);
+        if !from.is_dir() {
+            continue;
+        }
+
+        for entry in read_dir(&from)? {
+            if let Some(destination) = profile_entry_destination(&entry.file_name(), &to) {
+                move_entry(&entry.path(), &destination, &mut migration)?;
+            }
+        }
+    }
+    move_entry(
+        &legacy.join(profiles::REGISTRY_FILE_NAME),
+        &dirs.data.join(profiles::REGISTRY_FILE_NAME),
+        &mut migration,
+    )?;
+
+    drop(locks);
+    if migration.skipped.is_empty() {
+        fs::remove_dir_all(legacy).with_context(|| {
+            format!(
+                "Failed to remove migrated legacy root '{}'",
+                legacy.display()
+            )
+        })?;
+    }
+    Ok(Some(migration))
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<fs::DirEntry>> {
+    fs::read_dir(dir)
+        .and_then(Iterator::collect)
+        .with_context(|| format!("Failed to list '{}'", dir.display()))
+}
+
+fn move_entry(source: &Path, destination: &Path, migration: &mut LegacyMigration) -> Result<()> {
+    if fs::symlink_metadata(source).is_err() {
+        return Ok(());
+    }
+    if fs::symlink_metadata(destination).is_ok() {
+        migration.skipped.push(source.to_path_buf());
+        return Ok(());
+    }
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+    }
+    // The temp dir is often a separate filesystem, where a rename cannot work.
+    if fs::rename(source, destination).is_err() {
+        copy_recursively(source, destination)?;
+        remove_path(source)?;
+    }
+    migration.moved.push(destination.to_path_buf());
+    Ok(())
+}
+
+fn copy_recursively(source: &Path, destination: &Path) -> Result<()> {
+    let copy_error = || {
+        format!(
+            "Failed to copy '{}' to '{}'",
+            source.display(),
+            destination.display()
+        )
+    };
+    let file_type = fs::symlink_metadata(source)
+        .with_context(copy_error)?
+        .file_type();
+    if file_type.is_dir() {
+        fs::create_dir_all(destination).with_context(copy_error)?;
+        for entry in read_dir(source)? {
+            copy_recursively(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else if file_type.is_file() {
+        fs::copy(source, destination).with_context(copy_error)?;
+    }
+    // Symlinks are Chromium's per-process singleton markers; they are not data.
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let removed = if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    removed.with_context(|| format!("Failed to remove '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pulse-paths-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn resolves_platform_locations_from_the_environment() {
+        let home = std::env::temp_dir().join("home");
+        let xdg_cache = std::env::temp_dir().join("xdg-cache");
+        let env = |name: &str| match name {
+            "HOME" => Some(home.clone()),
+            "XDG_CACHE_HOME" => Some(xdg_cache.clone()),
+            "XDG_DATA_HOME" => Some(PathBuf::from("relative/is/ignored")),
+            "LOCALAPPDATA" => Some(home.join("AppData/Local")),
+            _ => None,
+        };
+
+        let linux = PulseDirs::for_platform("linux", env);
+        assert_eq!(linux.data, home.join(".local/share/Pulse"));
+        assert_eq!(linux.cache, xdg_cache.join("Pulse"));
+        assert_eq!(linux.state, home.join(".local/state/Pulse"));
+
+        let macos = PulseDirs::for_platform("macos", env);
+        assert_eq!(macos.data, home.join("Library/Application Support/Pulse"));
+        assert_eq!(macos.cache, home.join("Library/Caches/Pulse"));
+
+        let windows = PulseDirs::for_platform("windows", env);
+        assert_eq!(
+            windows,
+            PulseDirs::single_root(home.join("AppData/Local/Pulse"))
+        );
+
+        let homeless = PulseDirs::for_platform("linux", |_| None);
+        assert_eq!(
+            homeless,
+            PulseDirs::single_root(legacy_root_for(Path::new("Pulse")))
+        );
+
+        let work = linux.profile("work");
+        assert_eq!(work.root, home.join(".local/share/Pulse/profiles/work"));
+        assert_eq!(
+            work.log_file,
+            home.join(".local/state/Pulse/profiles/work/debug.log")
+        );
+        let default = linux.profile(profiles::DEFAULT_PROFILE_ID);
+        assert_eq!(default.cache_root, xdg_cache.join("Pulse/profiles/default"));
+    }
+
+    #[test]
+    fn migrates_the_legacy_root_once_and_splits_it_by_kind() {
+        let scratch = scratch("migrate");
+        let legacy = scratch.join("legacy");
+        let dirs = PulseDirs {
+            data: scratch.join("data"),
+            cache: scratch.join("cache"),
+            state: scratch.join("state"),
+        };
+        for file in [
+            "state/workspace-state.json",
+            "cache/default/Cookies",
+            "Local State",
+            "debug.log",
+            PROFILE_LOCK_FILE_NAME,
+        ] {
+            let path = legacy.join(file);
+            fs::create_dir_all(path.parent().expect("has parent")).expect("mkdir");
+            fs::write(&path, file).expect("write");
+        }
+
+        let migration = migrate_root(&legacy, &dirs)
+            .expect("migrates")
+            .expect("legacy root was found");
+        assert!(migration.skipped.is_empty());
+        assert!(!legacy.exists());
+        let default = dirs.profile(profiles::DEFAULT_PROFILE_ID);
+        assert!(default.root.join("state/workspace-state.json").is_file());
+        assert!(default.cache_root.join("cache/default/Cookies").is_file());
+        assert!(default.cache_root.join("Local State").is_file());
+        assert!(default.log_file.is_file());
+        assert!(!default.root.join(PROFILE_LOCK_FILE_NAME).exists());
+        assert!(migrate_default_profile(&dirs).expect("no-op").is_none());
+
+        fs::create_dir_all(legacy.join("state")).expect("mkdir");
+        assert!(migrate_root(&legacy, &dirs).expect("no-op").is_none());
+
+        fs::remove_dir_all(&scratch).expect("cleanup");
+    }
+
+    #[test]
+    fn moves_the_default_profile_out_of_the_base_directories() {
+        let scratch = scratch("default");
+        let dirs = PulseDirs {
+            data: scratch.join("data"),
+            cache: scratch.join("cache"),
+            state: scratch.join("state"),
+        };
+        let work = dirs.profile("work");
+        for file in [
+            dirs.data.join("state/workspace-state.json"),
+            dirs.data.join(profiles::REGISTRY_FILE_NAME),
+            dirs.cache.join("cache/default/Cookies"),
+            dirs.state.join(LOG_FILE_NAME),
+            work.root.join("state/workspace-state.json"),
+            work.cache_root.join("Local State"),
+        ] {
+            fs::create_dir_all(file.parent().expect("has parent")).expect("mkdir");
+            fs::write(&file, "x").expect("write");
+        }
+
+        let migration = migrate_default_profile(&dirs)
+            .expect("migrates")
+            .expect("default profile was moved");
+        assert!(migration.skipped.is_empty());
+        let default = dirs.profile(profiles::DEFAULT_PROFILE_ID);
+        assert!(default.root.join("state/workspace-state.json").is_file());
+        assert!(default.cache_root.join("cache/default/Cookies").is_file());
+        assert!(default.log_file.is_file());
+        assert!(dirs.data.join(profiles::REGISTRY_FILE_NAME).is_file());
+        assert!(!dirs.data.join(STATE_DIR_NAME).exists());
+        assert!(!dirs.data.join(PROFILE_LOCK_FILE_NAME).exists());
+        // Named profiles stay where they were.
+        assert!(work.root.join("state/workspace-state.json").is_file());
+        assert!(work.cache_root.join("Local State").is_file());
+        assert!(migrate_default_profile(&dirs).expect("no-op").is_none());
+
+        fs::remove_dir_all(&scratch).expect("cleanup");
+    }
+}
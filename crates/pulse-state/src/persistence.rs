@@ -0,0 +1,983 @@
+use anyhow::{Context, Result, anyhow};
+use pulse_core::{WorkspaceCommand, WorkspaceResponse, WorkspaceStore};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bundle::{self, BundleManifest, ImportMode};
+use crate::encryption;
+use crate::migrations::{self, CURRENT_SCHEMA_VERSION, Migration};
+use crate::paths::PulseDirs;
+use crate::snapshots::{self, RetentionPolicy, SnapshotInfo};
+use crate::ui_session::{store_from_ui_session, ui_session_from_store};
+
+const STATE_DIR_NAME: &str = "state";
+const PRIMARY_FILE_NAME: &str = "workspace-state.json";
+const PRIMARY_FILE_STEM: &str = "workspace-state";
+const PRIMARY_FILE_EXTENSION: &str = "json";
+const BACKUP_FILE_NAME: &str = "workspace-state.backup.json";
+const TEMP_FILE_NAME: &str = "workspace-state.tmp.json";
+const QUARANTINE_DIR_NAME: &str = "quarantine";
+const SNAPSHOTS_DIR_NAME: &str = "snapshots";
+const EXPORTS_DIR_NAME: &str = "exports";
+const FAVICONS_DIR_NAME: &str = "favicons";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedStateV3 {
+    schema_version: u32,
+    updated_at_unix_ms: u64,
+    checksum: String,
+    workspace: WorkspaceStore,
+}
+
+struct DecodedState {
+    workspace: WorkspaceStore,
+    source_version: u32,
+    /// Plaintext while a key is configured, or sealed with a key that is no longer active.
+    stale_encryption: bool,
+}
+
+impl DecodedState {
+    fn needs_migration(&self) -> bool {
+        self.source_version < CURRENT_SCHEMA_VERSION
+    }
+
+    fn needs_rewrite(&self) -> bool {
+        self.needs_migration() || self.stale_encryption
+    }
+}
+
+/// A state file decoded without writing anything, for offline inspection.
+pub struct InspectedState {
+    pub path: PathBuf,
+    /// The envelope as stored, after decryption and before any migration.
+    pub envelope: Value,
+    pub source_version: u32,
+    /// Id of the key the file was sealed with; `None` for plaintext files.
+    pub sealed_with: Option<String>,
+    pub pending_migrations: Vec<&'static Migration>,
+    pub workspace: WorkspaceStore,
+}
+
+/// Which state file an offline operation reads.
+#[derive(Debug, Clone)]
+pub enum StateSource {
+    Primary,
+    Backup,
+    Snapshot(String),
+    /// Any envelope on disk, e.g. an exported file being imported.
+    File(PathBuf),
+}
+
+impl std::fmt::Display for StateSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Primary => write!(f, "primary state"),
+            Self::Backup => write!(f, "backup state"),
+            Self::Snapshot(id) => write!(f, "snapshot '{id}'"),
+            Self::File(path) => write!(f, "state file '{}'", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportBundleRequest {
+    /// Defaults to `<profile root>/exports/workspace-<unix_ms>.pulse`.
+    #[serde(default)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportBundleResponse {
+    path: PathBuf,
+    manifest: BundleManifest,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportBundleRequest {
+    path: PathBuf,
+    mode: ImportMode,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportBundleResponse {
+    manifest: BundleManifest,
+    remapped_ids: BTreeMap<String, String>,
+    /// The workspace after the import, as a UI session payload (same shape as `loadState`).
+    session: Value,
+}
+
+pub struct BundleImport {
+    pub manifest: BundleManifest,
+    pub remapped_ids: BTreeMap<String, String>,
+    pub workspace: WorkspaceStore,
+}
+
+#[derive(Clone)]
+struct StatePaths {
+    dir: PathBuf,
+    primary: PathBuf,
+    backup: PathBuf,
+    temp: PathBuf,
+    quarantine: PathBuf,
+    snapshots: PathBuf,
+    exports: PathBuf,
+    favicons: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecoverySource {
+    Primary,
+    Backup,
+    Snapshot,
+    None,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedFile {
+    pub original_path: PathBuf,
+    pub quarantine_path: PathBuf,
+    pub reason: String,
+}
+
+/// What the last load read from disk, for the UI to surface after a recovery.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryReport {
+    pub source: RecoverySource,
+    pub source_path: Option<PathBuf>,
+    pub migrated_from_version: Option<u32>,
+    pub quarantined: Vec<QuarantinedFile>,
+    pub loaded_at_unix_ms: u64,
+}
+
+impl RecoveryReport {
+    fn new() -> Self {
+        Self {
+            source: RecoverySource::None,
+            source_path: None,
+            migrated_from_version: None,
+            quarantined: Vec::new(),
+            loaded_at_unix_ms: unix_time_ms(),
+        }
+    }
+
+    fn is_recovery(&self) -> bool {
+        matches!(
+            self.source,
+            RecoverySource::Backup | RecoverySource::Snapshot
+        ) || !self.quarantined.is_empty()
+    }
+
+    fn loaded_from(&mut self, source: RecoverySource, path: &Path, decoded: &DecodedState) {
+        self.source = source;
+        self.source_path = Some(path.to_path_buf());
+        if decoded.needs_migration() {
+            self.migrated_from_version = Some(decoded.source_version);
+        }
+    }
+}
+
+pub fn load_ui_state_json() -> Result<Option<String>> {
+    let Some(workspace) = load_workspace_store()? else {
+        return Ok(None);
+    };
+
+    let serialized = serde_json::to_string(&ui_session_from_store(&workspace))
+        .context("Failed to serialize persisted UI state for renderer bridge")?;
+    Ok(Some(serialized))
+}
+
+pub fn save_ui_state_json(serialized_state: &str) -> Result<()> {
+    let parsed: Value =
+        serde_json::from_str(serialized_state).context("UI state payload is not valid JSON")?;
+    let imported = store_from_ui_session(&parsed)?;
+    for reason in &imported.dropped {
+        eprintln!("Pulse state warning: skipped UI workspace {reason}");
+    }
+    let workspace = imported.store;
+
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let workspace = carry_over_host_settings(&paths, workspace)?;
+    save_workspace_store_inner(&paths, &workspace)
+}
+
+/// Runs one typed CRUD request against the persisted workspace tables.
+pub fn apply_workspace_command_json(serialized_command: &str) -> Result<String> {
+    let command: WorkspaceCommand = serde_json::from_str(serialized_command)
+        .context("Workspace command payload is not a valid command")?;
+    let response = apply_workspace_command(command)?;
+    serde_json::to_string(&response).context("Failed to serialize workspace command response")
+}
+
+pub fn apply_workspace_command(command: WorkspaceCommand) -> Result<WorkspaceResponse> {
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let mut workspace = load_workspace_store_inner(&paths)?.unwrap_or_default();
+
+    let is_mutation = command.is_mutation();
+    let response = workspace.apply(command)?;
+    if is_mutation {
+        save_workspace_store_inner(&paths, &workspace)?;
+    }
+
+    Ok(response)
+}
+
+pub fn load_workspace_store() -> Result<Option<WorkspaceStore>> {
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let mut report = RecoveryReport::new();
+    let loaded = load_with_recovery(&paths, &mut report);
+    record_recovery_report(report);
+    loaded
+}
+
+/// JSON-encoded list of `snapshots::SnapshotInfo`, newest first.
+pub fn list_snapshots_json() -> Result<String> {
+    serde_json::to_string(&list_snapshots()?).context("Failed to serialize snapshot list")
+}
+
+pub fn list_snapshots() -> Result<Vec<SnapshotInfo>> {
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    snapshots::list(&paths.snapshots)
+}
+
+/// Makes snapshot `id` the current workspace and returns it as a UI session payload.
+/// The replaced state is itself snapshotted by the save, so a restore can be undone.
+pub fn restore_snapshot(id: &str) -> Result<String> {
+    let workspace = restore_state(&StateSource::Snapshot(String::from(id)))?;
+    serde_json::to_string(&ui_session_from_store(&workspace))
+        .context("Failed to serialize restored UI state for renderer bridge")
+}
+
+/// Validates `source` and saves it as the current workspace. The previous primary becomes
+/// the backup, so restoring the backup swaps the two.
+pub fn restore_state(source: &StateSource) -> Result<WorkspaceStore> {
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let path = paths.source_path(source)?;
+    let Some(decoded) = read_state_file(&path)? else {
+        return Err(anyhow!("{source} does not exist"));
+    };
+
+    save_workspace_store_inner(&paths, &decoded.workspace)?;
+    Ok(decoded.workspace)
+}
+
+/// Decodes `source` through decryption, migrations and validation without writing
+/// anything, so it is safe on a damaged profile. `Ok(None)` means the file is missing.
+pub fn inspect_state(source: &StateSource) -> Result<Option<InspectedState>> {
+    let _guard = io_lock();
+    let path = state_paths().source_path(source)?;
+    decode_state_file(&path)
+}
+
+/// Writes `source` to `destination` as a plaintext envelope in the current schema.
+pub fn export_state(source: &StateSource, destination: &Path) -> Result<()> {
+    let Some(inspected) = inspect_state(source)? else {
+        return Err(anyhow!("{source} does not exist"));
+    };
+
+    fs::write(destination, envelope_bytes(&inspected.workspace)?)
+        .with_context(|| format!("Failed to write export '{}'", destination.display()))
+}
+
+/// Bridge form of `export_bundle`: takes `{ "path"? }`, returns `{ path, manifest }`.
+pub fn export_bundle_json(serialized_request: &str) -> Result<String> {
+    let request: ExportBundleRequest = serde_json::from_str(serialized_request)
+        .context("Bundle export payload is not a valid request")?;
+    let path = match request.path {
+        Some(path) => path,
+        None => state_paths().exports.join(format!(
+            "workspace-{}.{}",
+            unix_time_ms(),
+            bundle::BUNDLE_EXTENSION
+        )),
+    };
+
+    let manifest = export_bundle(&path)?;
+    serde_json::to_string(&ExportBundleResponse { path, manifest })
+        .context("Failed to serialize bundle export response")
+}
+
+/// Packages the current workspace as a `.pulse` bundle at `destination`.
+pub fn export_bundle(destination: &Path) -> Result<BundleManifest> {
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let workspace = load_workspace_store_inner(&paths)?.unwrap_or_default();
+    bundle::write(
+        destination,
+        &envelope_bytes(&workspace)?,
+        &workspace,
+        unix_time_ms(),
+    )
+}
+
+/// Bridge form of `import_bundle`: takes `{ path, mode }`, returns the manifest, the
+/// renamed ids and the resulting UI session.
+pub fn import_bundle_json(serialized_request: &str) -> Result<String> {
+    let request: ImportBundleRequest = serde_json::from_str(serialized_request)
+        .context("Bundle import payload is not a valid request")?;
+    let import = import_bundle(&request.path, request.mode)?;
+    serde_json::to_string(&ImportBundleResponse {
+        session: ui_session_from_store(&import.workspace),
+        manifest: import.manifest,
+        remapped_ids: import.remapped_ids,
+    })
+    .context("Failed to serialize bundle import response")
+}
+
+/// Validates the bundle at `source` and merges it into, or replaces, the current workspace.
+/// Like any save, the replaced state stays available as the backup and a snapshot.
+pub fn import_bundle(source: &Path, mode: ImportMode) -> Result<BundleImport> {
+    let bundle = bundle::read(source)?;
+
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let current = load_workspace_store_inner(&paths)?.unwrap_or_default();
+    let (workspace, remapped_ids) = bundle::apply(current, bundle.workspace, mode)?;
+    save_workspace_store_inner(&paths, &workspace)?;
+    Ok(BundleImport {
+        manifest: bundle.manifest,
+        remapped_ids,
+        workspace,
+    })
+}
+
+/// Where `exportBundle` writes when the request names no path.
+pub fn exports_dir() -> PathBuf {
+    state_paths().exports
+}
+
+/// Profile root used instead of the default profile's, e.g. a named profile or a copy.
+pub fn set_profile_root(root: PathBuf) {
+    *profile_root_slot()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(root);
+}
+
+/// Makes a new state key active and re-seals the current workspace with it. Older keys
+/// stay in the provider, so backups and snapshots sealed before the rotation still open.
+pub fn rotate_state_key() -> Result<String> {
+    let Some(provider) = encryption::configured_provider() else {
+        return Err(anyhow!(
+            "No state key provider is configured; set {} to a key file path",
+            encryption::KEY_FILE_ENV
+        ));
+    };
+
+    let _guard = io_lock();
+    let paths = prepare_state_dir()?;
+    let key_id = provider.rotate()?;
+    // The primary is now sealed with a retired key (or plaintext), so loading rewrites it.
+    load_workspace_store_inner(&paths)?;
+    Ok(key_id)
+}
+
+/// JSON-encoded `RecoveryReport` from the most recent load in this process, if any.
+pub fn last_recovery_report_json() -> Result<Option<String>> {
+    let report = last_recovery_report_slot()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let Some(report) = report else {
+        return Ok(None);
+    };
+
+    let serialized =
+        serde_json::to_string(&report).context("Failed to serialize state recovery report")?;
+    Ok(Some(serialized))
+}
+
+/// Favicon cache of the current profile, next to its state directory. Not created here;
+/// `favicons` creates it when it stores the first icon.
+pub(crate) fn favicons_dir() -> PathBuf {
+    state_paths().favicons
+}
+
+/// State directory of the current profile, created if missing.
+pub(crate) fn state_dir() -> Result<PathBuf> {
+    Ok(prepare_state_dir()?.dir)
+}
+
+fn prepare_state_dir() -> Result<StatePaths> {
+    let paths = state_paths();
+    fs::create_dir_all(&paths.dir).with_context(|| {
+        format!(
+            "Failed to create Pulse state directory '{}'",
+            paths.dir.display()
+        )
+    })?;
+    Ok(paths)
+}
+
+// Loads done on behalf of saves and workspace commands only replace the last report when
+// they had to recover, so a routine save does not hide how the session was restored.
+fn load_workspace_store_inner(paths: &StatePaths) -> Result<Option<WorkspaceStore>> {
+    let mut report = RecoveryReport::new();
+    let loaded = load_with_recovery(paths, &mut report);
+    if report.is_recovery() {
+        record_recovery_report(report);
+    }
+    loaded
+}
+
+fn record_recovery_report(report: RecoveryReport) {
+    *last_recovery_report_slot()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(report);
+}
+
+fn load_with_recovery(
+    paths: &StatePaths,
+    report: &mut RecoveryReport,
+) -> Result<Option<WorkspaceStore>> {
+    match read_state_file(&paths.primary) {
+        Ok(Some(decoded)) => {
+            report.loaded_from(RecoverySource::Primary, &paths.primary, &decoded);
+            if decoded.needs_migration() {
+                preserve_pre_migration_copy(paths, &paths.primary, decoded.source_version)?;
+            }
+            if decoded.needs_rewrite() {
+                save_workspace_store_inner(paths, &decoded.workspace)?;
+            }
+            return Ok(Some(decoded.workspace));
+        }
+        Ok(None) => {}
+        // The file is intact but unreadable with the current keys; keep it where it is.
+        Err(primary_err) if encryption::is_key_error(&primary_err) => return Err(primary_err),
+        Err(primary_err) => {
+            eprintln!(
+                "Pulse state warning: failed to read primary state '{}': {primary_err:#}",
+                paths.primary.display()
+            );
+            quarantine_file(paths, &paths.primary, &primary_err, report);
+        }
+    }
+
+    match read_state_file(&paths.backup) {
+        Ok(Some(decoded)) => {
+            report.loaded_from(RecoverySource::Backup, &paths.backup, &decoded);
+            if decoded.needs_migration() {
+                preserve_pre_migration_copy(paths, &paths.backup, decoded.source_version)?;
+            }
+            if let Err(restore_err) = restore_primary_copy(paths, &decoded.workspace) {
+                eprintln!(
+                    "Pulse state warning: failed to restore primary from backup '{}': {restore_err}",
+                    paths.backup.display()
+                );
+            }
+            return Ok(Some(decoded.workspace));
+        }
+        Ok(None) => {}
+        Err(backup_err) if encryption::is_key_error(&backup_err) => return Err(backup_err),
+        Err(backup_err) => {
+            eprintln!(
+                "Pulse state warning: failed to read backup state '{}': {backup_err:#}",
+                paths.backup.display()
+            );
+            quarantine_file(paths, &paths.backup, &backup_err, report);
+        }
+    }
+
+    // Only a damaged state falls back to snapshots; a profile without state starts empty.
+    if report.quarantined.is_empty() {
+        return Ok(None);
+    }
+    recover_from_snapshot(paths, report)
+}
+
+/// The newest snapshot that reads, restored as primary. Snapshots that fail are skipped
+/// and left in place.
+fn recover_from_snapshot(
+    paths: &StatePaths,
+    report: &mut RecoveryReport,
+) -> Result<Option<WorkspaceStore>> {
+    for snapshot in snapshots::list(&paths.snapshots)? {
+        let path = snapshots::resolve(&paths.snapshots, &snapshot.id)?;
+        match read_state_file(&path) {
+            Ok(Some(decoded)) => {
+                report.loaded_from(RecoverySource::Snapshot, &path, &decoded);
+                if let Err(restore_err) = restore_primary_copy(paths, &decoded.workspace) {
+                    eprintln!(
+                        "Pulse state warning: failed to restore primary from snapshot '{}': {restore_err}",
+                        path.display()
+                    );
+                }
+                return Ok(Some(decoded.workspace));
+            }
+            Ok(None) => {}
+            Err(snapshot_err) if encryption::is_key_error(&snapshot_err) => {
+                return Err(snapshot_err);
+            }
+            Err(snapshot_err) => eprintln!(
+                "Pulse state warning: skipping snapshot '{}': {snapshot_err:#}",
+                path.display()
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// Moves a file that failed to decode or verify into `state/quarantine/` so that a
+/// later restore or save cannot overwrite the evidence.
+fn quarantine_file(
+    paths: &StatePaths,
+    path: &Path,
+    reason: &anyhow::Error,
+    report: &mut RecoveryReport,
+) {
+    if !path.exists() {
+        return;
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from(PRIMARY_FILE_NAME));
+    let quarantine_path = paths
+        .quarantine
+        .join(format!("{}-{file_name}", unix_time_ms()));
+
+    let moved =
+        fs::create_dir_all(&paths.quarantine).and_then(|_| fs::rename(path, &quarantine_path));
+    if let Err(move_err) = moved {
+        eprintln!(
+            "Pulse state warning: failed to quarantine '{}' to '{}': {move_err}",
+            path.display(),
+            quarantine_path.display()
+        );
+        return;
+    }
+
+    eprintln!(
+        "Pulse state warning: quarantined '{}' as '{}'",
+        path.display(),
+        quarantine_path.display()
+    );
+    report.quarantined.push(QuarantinedFile {
+        original_path: path.to_path_buf(),
+        quarantine_path,
+        reason: format!("{reason:#}"),
+    });
+}
+
+/// Keeps host-owned settings (anything outside the `ui.` namespace) across UI snapshot saves.
+/// A load failure aborts the save rather than overwrite state the host could not read.
+fn carry_over_host_settings(
+    paths: &StatePaths,
+    mut workspace: WorkspaceStore,
+) -> Result<WorkspaceStore> {
+    let Some(previous) = load_workspace_store_inner(paths)? else {
+        return Ok(workspace);
+    };
+
+    for (key, value) in previous.settings() {
+        if !key.starts_with("ui.") && workspace.setting(key).is_none() {
+            workspace.set_setting(key, value);
+        }
+    }
+    Ok(workspace)
+}
+
+fn encode_envelope(workspace: &WorkspaceStore) -> Result<Vec<u8>> {
+    let serialized = envelope_bytes(workspace)?;
+
+    // With a key provider configured, nothing is written in plaintext.
+    match encryption::configured_provider() {
+        Some(provider) => encryption::seal(&serialized, provider.as_ref()),
+        None => Ok(serialized),
+    }
+}
+
+fn envelope_bytes(workspace: &WorkspaceStore) -> Result<Vec<u8>> {
+    let envelope = PersistedStateV3 {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        updated_at_unix_ms: unix_time_ms(),
+        checksum: migrations::workspace_checksum(workspace)?,
+        workspace: workspace.clone(),
+    };
+    let mut serialized = serde_json::to_vec_pretty(&envelope)
+        .context("Failed to encode workspace state envelope for persistence")?;
+    serialized.push(b'\n');
+    Ok(serialized)
+}
+
+fn save_workspace_store_inner(paths: &StatePaths, workspace: &WorkspaceStore) -> Result<()> {
+    let serialized = encode_envelope(workspace)?;
+    write_temp_file(&paths.temp, &serialized)?;
+
+    if paths.backup.exists() {
+        let _ = fs::remove_file(&paths.backup);
+    }
+
+    if paths.primary.exists() {
+        fs::rename(&paths.primary, &paths.backup).with_context(|| {
+            format!(
+                "Failed to rotate primary state '{}' to backup '{}'",
+                paths.primary.display(),
+                paths.backup.display()
+            )
+        })?;
+    }
+
+    fs::rename(&paths.temp, &paths.primary).with_context(|| {
+        format!(
+            "Failed to commit temp state '{}' to '{}'",
+            paths.temp.display(),
+            paths.primary.display()
+        )
+    })?;
+
+    if let Err(snapshot_err) = snapshots::record(
+        &paths.snapshots,
+        &serialized,
+        unix_time_ms(),
+        RetentionPolicy::default(),
+    ) {
+        eprintln!("Pulse state warning: failed to record snapshot: {snapshot_err:#}");
+    }
+
+    Ok(())
+}
+
+fn restore_primary_copy(paths: &StatePaths, workspace: &WorkspaceStore) -> Result<()> {
+    let serialized =
+        encode_envelope(workspace).context("Failed to encode backup recovery state")?;
+
+    write_temp_file(&paths.temp, &serialized)?;
+    if paths.primary.exists() {
+        let _ = fs::remove_file(&paths.primary);
+    }
+
+    fs::rename(&paths.temp, &paths.primary).with_context(|| {
+        format!(
+            "Failed to restore primary state file '{}' from backup",
+            paths.primary.display()
+        )
+    })?;
+
+    Ok(())
+}
+
+fn write_temp_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    if path.exists() {
+        let _ = fs::remove_file(path);
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("Failed to open temporary state file '{}'", path.display()))?;
+    file.write_all(bytes)
+        .with_context(|| format!("Failed to write temporary state file '{}'", path.display()))?;
+    file.flush()
+        .with_context(|| format!("Failed to flush temporary state file '{}'", path.display()))?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync temporary state file '{}'", path.display()))?;
+    Ok(())
+}
+
+fn read_state_file(path: &Path) -> Result<Option<DecodedState>> {
+    let active_key_id = encryption::configured_provider()
+        .map(|provider| provider.active_key().map(|key| key.id))
+        .transpose()?;
+    let Some(inspected) = decode_state_file(path)? else {
+        return Ok(None);
+    };
+
+    for migration in &inspected.pending_migrations {
+        eprintln!(
+            "Pulse state: migrated '{}' v{} -> v{} ({})",
+            path.display(),
+            migration.from_version,
+            migration.from_version + 1,
+            migration.description
+        );
+    }
+    Ok(Some(DecodedState {
+        stale_encryption: inspected.sealed_with != active_key_id,
+        workspace: inspected.workspace,
+        source_version: inspected.source_version,
+    }))
+}
+
+fn decode_state_file(path: &Path) -> Result<Option<InspectedState>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read state file '{}'", path.display()))?;
+    let parsed: Value = serde_json::from_slice(&bytes)
+        .with_context(|| format!("State file '{}' is not valid JSON", path.display()))?;
+
+    let (envelope, sealed_with) = if encryption::is_sealed(&parsed) {
+        let provider = encryption::configured_provider();
+        let (plaintext, key_id) = encryption::open(parsed, provider.as_deref())
+            .with_context(|| format!("State file '{}' could not be decrypted", path.display()))?;
+        let envelope = serde_json::from_slice(&plaintext).with_context(|| {
+            format!(
+                "Decrypted state file '{}' is not valid JSON",
+                path.display()
+            )
+        })?;
+        (envelope, Some(key_id))
+    } else {
+        (parsed, None)
+    };
+
+    let outcome = migrations::migrate_to_current(envelope.clone())
+        .with_context(|| format!("State file '{}' could not be migrated", path.display()))?;
+    Ok(Some(InspectedState {
+        path: path.to_path_buf(),
+        envelope,
+        source_version: outcome.source_version,
+        sealed_with,
+        pending_migrations: outcome.applied,
+        workspace: outcome.workspace,
+    }))
+}
+
+/// Keeps the untouched pre-migration file as `workspace-state.v{N}.json` before it is
+/// rewritten in the current schema. An existing copy for the same version is kept.
+fn preserve_pre_migration_copy(paths: &StatePaths, source: &Path, version: u32) -> Result<()> {
+    let versioned = paths.versioned_backup(version);
+    if versioned.exists() {
+        return Ok(());
+    }
+
+    fs::copy(source, &versioned).with_context(|| {
+        format!(
+            "Failed to keep pre-migration copy of '{}' at '{}'",
+            source.display(),
+            versioned.display()
+        )
+    })?;
+    Ok(())
+}
+
+impl StatePaths {
+    fn new(root: &Path) -> Self {
+        let dir = root.join(STATE_DIR_NAME);
+        Self {
+            primary: dir.join(PRIMARY_FILE_NAME),
+            backup: dir.join(BACKUP_FILE_NAME),
+            temp: dir.join(TEMP_FILE_NAME),
+            quarantine: dir.join(QUARANTINE_DIR_NAME),
+            snapshots: dir.join(SNAPSHOTS_DIR_NAME),
+            exports: root.join(EXPORTS_DIR_NAME),
+            favicons: root.join(FAVICONS_DIR_NAME),
+            dir,
+        }
+    }
+
+    fn versioned_backup(&self, version: u32) -> PathBuf {
+        self.dir.join(format!(
+            "{PRIMARY_FILE_STEM}.v{version}.{PRIMARY_FILE_EXTENSION}"
+        ))
+    }
+
+    fn source_path(&self, source: &StateSource) -> Result<PathBuf> {
+        match source {
+            StateSource::Primary => Ok(self.primary.clone()),
+            StateSource::Backup => Ok(self.backup.clone()),
+            StateSource::Snapshot(id) => snapshots::resolve(&self.snapshots, id),
+            StateSource::File(path) => Ok(path.clone()),
+        }
+    }
+}
+
+fn state_paths() -> StatePaths {
+    let root = profile_root_slot()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+        .unwrap_or_else(|| PulseDirs::resolve().data);
+    StatePaths::new(&root)
+}
+
+fn profile_root_slot() -> &'static Mutex<Option<PathBuf>> {
+    static PROFILE_ROOT: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
+    PROFILE_ROOT.get_or_init(|| Mutex::new(None))
+}
+
+fn last_recovery_report_slot() -> &'static Mutex<Option<RecoveryReport>> {
+    static LAST_RECOVERY_REPORT: OnceLock<Mutex<Option<RecoveryReport>>> = OnceLock::new();
+    LAST_RECOVERY_REPORT.get_or_init(|| Mutex::new(None))
+}
+
+fn io_lock() -> MutexGuard<'static, ()> {
+    static IO_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    IO_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub(crate) fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::{FileKeyProvider, KeyProvider};
+
+    fn temp_paths(name: &str) -> StatePaths {
+        let root =
+            std::env::temp_dir().join(format!("pulse-persistence-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let paths = StatePaths::new(&root);
+        fs::create_dir_all(&paths.dir).unwrap();
+        paths
+    }
+
+    fn workspace(theme: &str) -> WorkspaceStore {
+        let mut workspace = WorkspaceStore::default();
+        workspace.set_setting("ui.theme", theme);
+        workspace
+    }
+
+    fn load(paths: &StatePaths) -> (Option<WorkspaceStore>, RecoveryReport) {
+        let mut report = RecoveryReport::new();
+        let loaded = load_with_recovery(paths, &mut report).unwrap();
+        (loaded, report)
+    }
+
+    #[test]
+    fn a_primary_failing_its_checksum_is_quarantined_and_restored_from_backup() {
+        let paths = temp_paths("checksum");
+        save_workspace_store_inner(&paths, &workspace("light")).unwrap();
+        save_workspace_store_inner(&paths, &workspace("dark")).unwrap();
+
+        let mut envelope: Value =
+            serde_json::from_slice(&fs::read(&paths.primary).unwrap()).unwrap();
+        envelope["checksum"] = Value::from("0".repeat(64));
+        fs::write(&paths.primary, serde_json::to_vec(&envelope).unwrap()).unwrap();
+
+        let (loaded, report) = load(&paths);
+        assert_eq!(loaded, Some(workspace("light")));
+        assert!(report.is_recovery());
+        assert_eq!(report.source, RecoverySource::Backup);
+        assert_eq!(report.source_path.as_deref(), Some(paths.backup.as_path()));
+        assert_eq!(report.quarantined.len(), 1);
+        let quarantined = &report.quarantined[0];
+        assert_eq!(quarantined.original_path, paths.primary);
+        assert!(quarantined.reason.contains("checksum mismatch"));
+        assert!(quarantined.quarantine_path.starts_with(&paths.quarantine));
+        assert_eq!(
+            fs::read(&quarantined.quarantine_path).unwrap(),
+            serde_json::to_vec(&envelope).unwrap()
+        );
+
+        let reported = serde_json::to_value(&report).unwrap();
+        assert_eq!(reported["source"], "backup");
+        assert_eq!(
+            reported["quarantined"][0]["original_path"],
+            json_path(&paths.primary)
+        );
+
+        // The restored primary loads cleanly on the next start.
+        let (loaded, report) = load(&paths);
+        assert_eq!(loaded, Some(workspace("light")));
+        assert_eq!(report.source, RecoverySource::Primary);
+        assert!(!report.is_recovery());
+        let _ = fs::remove_dir_all(paths.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn the_newest_readable_snapshot_is_used_when_primary_and_backup_fail() {
+        let paths = temp_paths("snapshot");
+        let policy = RetentionPolicy::default();
+        snapshots::record(
+            &paths.snapshots,
+            &envelope_bytes(&workspace("old")).unwrap(),
+            1_000,
+            policy,
+        )
+        .unwrap();
+        snapshots::record(
+            &paths.snapshots,
+            &envelope_bytes(&workspace("kept")).unwrap(),
+            2_000,
+            policy,
+        )
+        .unwrap();
+        snapshots::record(&paths.snapshots, b"{\"schema_version\":", 3_000, policy).unwrap();
+        fs::write(&paths.primary, b"{ torn").unwrap();
+        fs::write(&paths.backup, b"").unwrap();
+
+        let (loaded, report) = load(&paths);
+        assert_eq!(loaded, Some(workspace("kept")));
+        assert_eq!(report.source, RecoverySource::Snapshot);
+        assert_eq!(
+            report.source_path,
+            Some(snapshots::resolve(&paths.snapshots, "2000").unwrap())
+        );
+        let originals = report
+            .quarantined
+            .iter()
+            .map(|file| file.original_path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(originals, [paths.primary.clone(), paths.backup.clone()]);
+        assert_eq!(snapshots::list(&paths.snapshots).unwrap().len(), 3);
+        assert_eq!(load(&paths).0, Some(workspace("kept")));
+        let _ = fs::remove_dir_all(paths.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn a_profile_without_state_starts_empty_even_with_snapshots() {
+        let paths = temp_paths("empty");
+        snapshots::record(
+            &paths.snapshots,
+            &envelope_bytes(&workspace("old")).unwrap(),
+            1_000,
+            RetentionPolicy::default(),
+        )
+        .unwrap();
+
+        let (loaded, report) = load(&paths);
+        assert_eq!(loaded, None);
+        assert_eq!(report.source, RecoverySource::None);
+        assert!(!report.is_recovery());
+        let _ = fs::remove_dir_all(paths.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn key_errors_fail_the_load_without_quarantining() {
+        let paths = temp_paths("key-error");
+        let provider = FileKeyProvider::new(paths.dir.join("state-key.json"));
+        provider.rotate().unwrap();
+        let sealed =
+            encryption::seal(&envelope_bytes(&workspace("secret")).unwrap(), &provider).unwrap();
+        fs::write(&paths.primary, &sealed).unwrap();
+        fs::write(&paths.backup, &sealed).unwrap();
+
+        // No provider is configured, so the sealed files cannot be opened.
+        let mut report = RecoveryReport::new();
+        let error = load_with_recovery(&paths, &mut report).unwrap_err();
+        assert!(encryption::is_key_error(&error));
+        assert!(report.quarantined.is_empty());
+        assert!(!paths.quarantine.exists());
+        assert_eq!(fs::read(&paths.primary).unwrap(), sealed);
+        assert_eq!(fs::read(&paths.backup).unwrap(), sealed);
+        let _ = fs::remove_dir_all(paths.dir.parent().unwrap());
+    }
+
+    fn json_path(path: &Path) -> Value {
+        serde_json::to_value(path).unwrap()
+    }
+}
@@ -0,0 +1,433 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::paths::{ProfilePaths, PulseDirs};
+use crate::persistence::{self, unix_time_ms};
+
+pub const REGISTRY_FILE_NAME: &str = "profiles.json";
+const REGISTRY_TEMP_FILE_NAME: &str = "profiles.tmp.json";
+const REGISTRY_VERSION: u32 = 1;
+/// Always registered and never deleted. Data from before `profiles/default` existed is moved
+/// there by `paths::migrate_default_profile`.
+pub const DEFAULT_PROFILE_ID: &str = "default";
+const DEFAULT_PROFILE_NAME: &str = "Default";
+const MAX_NAME_CHARS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Stable directory name under `profiles/` in each base directory; renames keep it.
+    pub id: String,
+    pub name: String,
+    pub created_at_unix_ms: u64,
+}
+
+/// `profiles.json` in the data directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileRegistry {
+    pub version: u32,
+    /// Profile launched when no `--profile` is given.
+    pub selected: String,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for ProfileRegistry {
+    fn default() -> Self {
+        Self {
+            version: REGISTRY_VERSION,
+            selected: String::from(DEFAULT_PROFILE_ID),
+            profiles: vec![Profile {
+                id: String::from(DEFAULT_PROFILE_ID),
+                name: String::from(DEFAULT_PROFILE_NAME),
+                created_at_unix_ms: 0,
+            }],
+        }
+    }
+}
+
+impl ProfileRegistry {
+    /// Looks a profile up by id, or by name ignoring case.
+    pub fn find(&self, query: &str) -> Option<&Profile> {
+        let query = query.trim();
+        self.profiles
+            .iter()
+            .find(|profile| profile.id == query)
+            .or_else(|| {
+                self.profiles
+                    .iter()
+                    .find(|profile| profile.name.eq_ignore_ascii_case(query))
+            })
+    }
+
+    fn get(&self, query: &str) -> Result<&Profile> {
+        self.find(query).ok_or_else(|| {
+            anyhow!(
+                "Unknown profile '{query}'; known profiles: {}",
+                self.profiles
+                    .iter()
+                    .map(|profile| profile.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+    }
+
+    fn check_name_available(&self, name: &str, except_id: Option<&str>) -> Result<()> {
+        let taken = self.profiles.iter().any(|profile| {
+            Some(profile.id.as_str()) != except_id
+                && (profile.name.eq_ignore_ascii_case(name) || profile.id == name)
+        });
+        if taken {
+            return Err(anyhow!("A profile named '{name}' already exists"));
+        }
+        Ok(())
+    }
+
+    fn unused_id(&self, name: &str) -> String {
+        let mut base = String::new();
+        for character in name.chars() {
+            if character.is_ascii_alphanumeric() {
+                base.push(character.to_ascii_lowercase());
+            } else if !base.is_empty() && !base.ends_with('-') {
+                base.push('-');
+            }
+        }
+        let base = match base.trim_end_matches('-') {
+            "" => "profile",
+            trimmed => trimmed,
+        };
+
+        let is_free = |id: &str| self.profiles.iter().all(|profile| profile.id != id);
+        if is_free(base) {
+            return String::from(base);
+        }
+        (2..)
+            .map(|suffix| format!("{base}-{suffix}"))
+            .find(|id| is_free(id))
+            .expect("an unused suffix exists")
+    }
+}
+
+/// Registered profiles as shown to the UI.
+#[derive(Debug, Clone, Serialize)]
+struct ProfileList<'a> {
+    current: &'a str,
+    selected: &'a str,
+    profiles: &'a [Profile],
+}
+
+struct OpenedProfile {
+    data: PathBuf,
+    id: String,
+}
+
+/// Reads the registry in `data`; a missing file is the registry holding only the default
+/// profile.
+pub fn load(data: &Path) -> Result<ProfileRegistry> {
+    let path = data.join(REGISTRY_FILE_NAME);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(ProfileRegistry::default());
+        }
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read profile registry '{}'", path.display()));
+        }
+    };
+
+    let registry: ProfileRegistry = serde_json::from_str(&text)
+        .with_context(|| format!("Profile registry '{}' is malformed", path.display()))?;
+    if registry.version > REGISTRY_VERSION {
+        return Err(anyhow!(
+            "Unsupported profile registry version={}. This build supports up to {REGISTRY_VERSION}",
+            registry.version
+        ));
+    }
+    if let Some(profile) = registry
+        .profiles
+        .iter()
+        .find(|profile| !is_valid_id(&profile.id))
+    {
+        return Err(anyhow!(
+            "Profile registry '{}' has an invalid profile id '{}'",
+            path.display(),
+            profile.id
+        ));
+    }
+    if registry.find(DEFAULT_PROFILE_ID).is_none() || registry.find(&registry.selected).is_none() {
+        return Err(anyhow!(
+            "Profile registry '{}' must list the default and the selected profile",
+            path.display()
+        ));
+    }
+    Ok(registry)
+}
+
+fn save(data: &Path, registry: &ProfileRegistry) -> Result<()> {
+    fs::create_dir_all(data)
+        .with_context(|| format!("Failed to create data directory '{}'", data.display()))?;
+    let path = data.join(REGISTRY_FILE_NAME);
+    let temp = data.join(REGISTRY_TEMP_FILE_NAME);
+    fs::write(&temp, serde_json::to_vec_pretty(registry)?)
+        .with_context(|| format!("Failed to write '{}'", temp.display()))?;
+    fs::rename(&temp, &path).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(())
+}
+
+/// The profile named by `requested`, or the selected profile, and where it lives.
+pub fn resolve(dirs: &PulseDirs, requested: Option<&str>) -> Result<(Profile, ProfilePaths)> {
+    let registry = load(&dirs.data)?;
+    let profile = registry
+        .get(requested.unwrap_or(&registry.selected))?
+        .clone();
+    let paths = dirs.profile(&profile.id);
+    Ok((profile, paths))
+}
+
+/// Resolves the profile for this process and points persistence at its root. Call once,
+/// before the first load.
+pub fn open(dirs: &PulseDirs, requested: Option<&str>) -> Result<(Profile, ProfilePaths)> {
+    let (profile, paths) = resolve(dirs, requested)?;
+    persistence::set_profile_root(paths.root.clone());
+    let _ = opened_profile_slot().set(OpenedProfile {
+        data: dirs.data.clone(),
+        id: profile.id.clone(),
+    });
+    Ok((profile, paths))
+}
+
+/// JSON `{current, selected, profiles}` for the UI, where `current` is the profile passed
+/// to `open`.
+pub fn list_profiles_json() -> Result<String> {
+    let Some(opened) = opened_profile_slot().get() else {
+        return Err(anyhow!("No profile has been opened in this process"));
+    };
+
+    let registry = load(&opened.data)?;
+    serde_json::to_string(&ProfileList {
+        current: &opened.id,
+        selected: &registry.selected,
+        profiles: &registry.profiles,
+    })
+    .context("Failed to encode profile list")
+}
+
+pub fn create(dirs: &PulseDirs, name: &str) -> Result<Profile> {
+    let name = validated_name(name)?;
+    let mut registry = load(&dirs.data)?;
+    registry.check_name_available(name, None)?;
+
+    let profile = Profile {
+        id: registry.unused_id(name),
+        name: String::from(name),
+        created_at_unix_ms: unix_time_ms(),
+    };
+    let root = dirs.profile(&profile.id).root;
+    fs::create_dir_all(&root)
+        .with_context(|| format!("Failed to create profile root '{}'", root.display()))?;
+    registry.profiles.push(profile.clone());
+    save(&dirs.data, &registry)?;
+    Ok(profile)
+}
+
+/// Changes the display name only; the id and the directory stay put.
+pub fn rename(dirs: &PulseDirs, query: &str, new_name: &str) -> Result<Profile> {
+    let new_name = validated_name(new_name)?;
+    let mut registry = load(&dirs.data)?;
+    let id = registry.get(query)?.id.clone();
+    registry.check_name_available(new_name, Some(&id))?;
+
+    let profile = registry
+        .profiles
+        .iter_mut()
+        .find(|profile| profile.id == id)
+        .expect("profile was just found");
+    profile.name = String::from(new_name);
+    let renamed = profile.clone();
+    save(&dirs.data, &registry)?;
+    Ok(renamed)
+}
+
+/// Unregisters the profile and removes its data, cache and logs. Refuses the default
+/// profile and any profile a running Pulse holds. Deleting the selected profile selects the
+/// default one.
+pub fn delete(dirs: &PulseDirs, query: &str) -> Result<Profile> {
+    let mut registry = load(&dirs.data)?;
+    let profile = registry.get(query)?.clone();
+    if profile.id == DEFAULT_PROFILE_ID {
+        return Err(anyhow!("The default profile cannot be deleted"));
+    }
+
+    let paths = dirs.profile(&profile.id);
+    // Held until the data is gone, so a Pulse starting meanwhile cannot open the profile.
+    let lock = crate::lock_profile(&paths.root)?;
+
+    registry
+        .profiles
+        .retain(|registered| registered.id != profile.id);
+    if registry.selected == profile.id {
+        registry.selected = String::from(DEFAULT_PROFILE_ID);
+    }
+    save(&dirs.data, &registry)?;
+
+    let log_dir = paths.log_file.parent().map(Path::to_path_buf);
+    // On Windows all three are the same directory, which goes last.
+    for dir in [Some(paths.cache_root), log_dir]
+        .into_iter()
+        .flatten()
+        .filter(|dir| *dir != paths.root)
+    {
+        remove_dir(&dir)?;
+    }
+    let mut lock = Some(lock);
+    if cfg!(target_os = "windows") {
+        // Windows cannot remove a file while it is open, so the lock file is released
+        // only after everything else in the root is gone.
+        remove_entries_except(&paths.root, crate::PROFILE_LOCK_FILE_NAME)?;
+        lock = None;
+    }
+    remove_dir(&paths.root)?;
+    drop(lock);
+    Ok(profile)
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("Failed to remove '{}'", dir.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn remove_entries_except(dir: &Path, kept: &str) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to list '{}'", dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to list '{}'", dir.display()))?;
+        if entry.file_name() == kept {
+            continue;
+        }
+        let path = entry.path();
+        let removed = if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.with_context(|| format!("Failed to remove '{}'", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Makes `query` the profile launched when no `--profile` is given.
+pub fn switch(dirs: &PulseDirs, query: &str) -> Result<Profile> {
+    let mut registry = load(&dirs.data)?;
+    let profile = registry.get(query)?.clone();
+    registry.selected = profile.id.clone();
+    save(&dirs.data, &registry)?;
+    Ok(profile)
+}
+
+fn validated_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("Profile names cannot be empty"));
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(anyhow!(
+            "Profile names are limited to {MAX_NAME_CHARS} characters"
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(anyhow!("Profile names cannot contain control characters"));
+    }
+    Ok(name)
+}
+
+/// Ids name directories, so they stay lowercase ASCII words joined by `-`.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('-')
+        && id.chars().all(|character| {
+            character.is_ascii_lowercase() || character.is_ascii_digit() || character == '-'
+        })
+}
+
+fn opened_profile_slot() -> &'static OnceLock<OpenedProfile> {
+    static OPENED_PROFILE: OnceLock<OpenedProfile> = OnceLock::new();
+    &OPENED_PROFILE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(name: &str) -> PulseDirs {
+        let dir =
+            std::env::temp_dir().join(format!("pulse-profiles-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        PulseDirs {
+            data: dir.join("data"),
+            cache: dir.join("cache"),
+            state: dir.join("state"),
+        }
+    }
+
+    #[test]
+    fn missing_registry_resolves_to_the_default_profile() {
+        let dirs = dirs("default");
+        let (profile, paths) = resolve(&dirs, None).expect("default resolves");
+        assert_eq!(profile.id, DEFAULT_PROFILE_ID);
+        assert_eq!(
+            paths.root,
+            dirs.data.join("profiles").join(DEFAULT_PROFILE_ID)
+        );
+        assert_eq!(
+            paths.cache_root,
+            dirs.cache.join("profiles").join(DEFAULT_PROFILE_ID)
+        );
+        assert!(resolve(&dirs, Some("work")).is_err());
+    }
+
+    #[test]
+    fn create_rename_switch_and_delete_round_trip_through_the_registry() {
+        let dirs = dirs("lifecycle");
+        let work = create(&dirs, "Work Stuff").expect("create");
+        assert_eq!(work.id, "work-stuff");
+        assert!(dirs.profile(&work.id).root.is_dir());
+        assert!(create(&dirs, "work stuff").is_err(), "names are unique");
+        assert_eq!(
+            create(&dirs, "Work/Stuff").expect("create").id,
+            "work-stuff-2"
+        );
+
+        let renamed = rename(&dirs, "Work Stuff", "Research").expect("rename");
+        assert_eq!(renamed.id, "work-stuff");
+        switch(&dirs, "research").expect("switch");
+        let (selected, selected_paths) = resolve(&dirs, None).expect("resolve");
+        assert_eq!(selected.name, "Research");
+        assert_eq!(
+            selected_paths.root,
+            dirs.data.join("profiles").join("work-stuff")
+        );
+
+        fs::create_dir_all(&selected_paths.cache_root).expect("mkdir");
+        assert!(delete(&dirs, DEFAULT_PROFILE_ID).is_err());
+        let running = crate::lock_profile(&selected_paths.root).expect("lock");
+        assert!(delete(&dirs, "research").is_err(), "a running profile stays");
+        assert!(selected_paths.root.is_dir());
+        drop(running);
+        delete(&dirs, "research").expect("delete");
+        assert!(!selected_paths.root.exists());
+        assert!(!selected_paths.cache_root.exists());
+        let registry = load(&dirs.data).expect("load");
+        assert_eq!(registry.selected, DEFAULT_PROFILE_ID);
+        assert!(registry.find("research").is_none());
+
+        let root = dirs.data.parent().expect("scratch root").to_path_buf();
+        fs::remove_dir_all(root).expect("cleanup");
+    }
+}
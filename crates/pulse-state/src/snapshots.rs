@@ -0,0 +1,226 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_EXTENSION: &str = "json";
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// How many snapshots survive pruning: the newest `recent` saves, plus the newest save in
+/// each of the last `hourly` hours and `daily` days that have one. The newest snapshot
+/// always survives.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub recent: usize,
+    pub hourly: usize,
+    pub daily: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            recent: 10,
+            hourly: 24,
+            daily: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub created_at_unix_ms: u64,
+    pub size_bytes: u64,
+}
+
+/// Writes `bytes` as snapshot `<unix_ms>.json` and prunes the directory to `policy`.
+pub fn record(
+    dir: &Path,
+    bytes: &[u8],
+    created_at_unix_ms: u64,
+    policy: RetentionPolicy,
+) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create snapshot directory '{}'", dir.display()))?;
+
+    let path = snapshot_path(dir, created_at_unix_ms);
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, bytes)
+        .with_context(|| format!("Failed to write snapshot '{}'", temp.display()))?;
+    fs::rename(&temp, &path)
+        .with_context(|| format!("Failed to commit snapshot '{}'", path.display()))?;
+
+    prune(dir, policy)
+}
+
+/// Snapshots in `dir`, newest first.
+pub fn list(dir: &Path) -> Result<Vec<SnapshotInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Failed to list snapshot directory '{}'", dir.display()))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(created_at_unix_ms) = snapshot_timestamp(&path) else {
+            continue;
+        };
+        snapshots.push(SnapshotInfo {
+            id: created_at_unix_ms.to_string(),
+            created_at_unix_ms,
+            size_bytes: entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+        });
+    }
+
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at_unix_ms));
+    Ok(snapshots)
+}
+
+/// Resolves a snapshot id from `list` to its file, rejecting anything that is not one.
+pub fn resolve(dir: &Path, id: &str) -> Result<PathBuf> {
+    let created_at_unix_ms = id
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Invalid snapshot id '{id}'"))?;
+    let path = snapshot_path(dir, created_at_unix_ms);
+    if !path.exists() {
+        return Err(anyhow!("Snapshot '{id}' does not exist"));
+    }
+    Ok(path)
+}
+
+fn prune(dir: &Path, policy: RetentionPolicy) -> Result<()> {
+    let snapshots = list(dir)?;
+    let timestamps = snapshots
+        .iter()
+        .map(|snapshot| snapshot.created_at_unix_ms)
+        .collect::<Vec<_>>();
+    let retained = retained_timestamps(&timestamps, policy);
+
+    for snapshot in snapshots {
+        if retained.contains(&snapshot.created_at_unix_ms) {
+            continue;
+        }
+        let path = snapshot_path(dir, snapshot.created_at_unix_ms);
+        if let Err(err) = fs::remove_file(&path) {
+            eprintln!(
+                "Pulse state warning: failed to prune snapshot '{}': {err}",
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// `timestamps` must be sorted newest first.
+fn retained_timestamps(timestamps: &[u64], policy: RetentionPolicy) -> BTreeSet<u64> {
+    let mut retained = timestamps
+        .iter()
+        .take(policy.recent.max(1))
+        .copied()
+        .collect::<BTreeSet<_>>();
+    retained.extend(newest_per_bucket(timestamps, HOUR_MS, policy.hourly));
+    retained.extend(newest_per_bucket(timestamps, DAY_MS, policy.daily));
+    retained
+}
+
+fn newest_per_bucket(timestamps: &[u64], bucket_ms: u64, buckets: usize) -> Vec<u64> {
+    let mut seen = BTreeSet::new();
+    let mut keepers = Vec::new();
+    for &timestamp in timestamps {
+        if seen.len() >= buckets {
+            break;
+        }
+        // Newest-first order means the first hit in a bucket is its newest snapshot.
+        if seen.insert(timestamp / bucket_ms) {
+            keepers.push(timestamp);
+        }
+    }
+    keepers
+}
+
+fn snapshot_path(dir: &Path, created_at_unix_ms: u64) -> PathBuf {
+    dir.join(format!("{created_at_unix_ms}.{SNAPSHOT_EXTENSION}"))
+}
+
+fn snapshot_timestamp(path: &Path) -> Option<u64> {
+    if path.extension()?.to_str()? != SNAPSHOT_EXTENSION {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    #[test]
+    fn buckets_split_on_hour_and_day_boundaries() {
+        let timestamps = [2 * HOUR_MS, 2 * HOUR_MS - 1, HOUR_MS, HOUR_MS - 1];
+        assert_eq!(
+            newest_per_bucket(&timestamps, HOUR_MS, 10),
+            [2 * HOUR_MS, 2 * HOUR_MS - 1, HOUR_MS - 1]
+        );
+        assert_eq!(
+            newest_per_bucket(&timestamps, HOUR_MS, 2),
+            [2 * HOUR_MS, 2 * HOUR_MS - 1]
+        );
+        assert_eq!(
+            newest_per_bucket(&[DAY_MS, DAY_MS - 1, 0], DAY_MS, 10),
+            [DAY_MS, DAY_MS - 1]
+        );
+    }
+
+    #[test]
+    fn tiers_overlap_instead_of_adding_up() {
+        let midnight = 100 * DAY_MS;
+        let now = midnight + 12 * HOUR_MS + 30 * MINUTE_MS;
+        let recent = (0..10).map(|minute| now - minute * MINUTE_MS);
+        // Every hour of today before noon and all of yesterday, then one save a day.
+        let hourly = (1..=36).map(|hour| midnight + 12 * HOUR_MS - hour * HOUR_MS + 5 * MINUTE_MS);
+        let daily = (2..=20).map(|day| midnight - day * DAY_MS + 7 * HOUR_MS);
+        let timestamps = recent.chain(hourly).chain(daily).collect::<Vec<_>>();
+        assert!(timestamps.is_sorted_by(|a, b| a > b));
+
+        let retained = retained_timestamps(&timestamps, RetentionPolicy::default());
+        // The current hour is already covered by the recent tier, so the hourly tier reaches
+        // 23 hours further back, and today and yesterday already count as two of the days.
+        let oldest_hour = midnight - 11 * HOUR_MS + 5 * MINUTE_MS;
+        let oldest_day = midnight - 13 * DAY_MS + 7 * HOUR_MS;
+        assert!(retained.contains(&oldest_hour));
+        assert!(!retained.contains(&(oldest_hour - HOUR_MS)));
+        assert!(retained.contains(&oldest_day));
+        assert!(!retained.contains(&(oldest_day - DAY_MS)));
+        assert_eq!(retained.len(), 10 + 23 + 12);
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_snapshot() {
+        let dir = std::env::temp_dir().join(format!("pulse-snapshots-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let keep_nothing = RetentionPolicy {
+            recent: 0,
+            hourly: 0,
+            daily: 0,
+        };
+
+        for created_at in [HOUR_MS, 2 * HOUR_MS, 2 * HOUR_MS + 1] {
+            record(&dir, b"{}", created_at, keep_nothing).unwrap();
+            let ids = list(&dir)
+                .unwrap()
+                .into_iter()
+                .map(|snapshot| snapshot.created_at_unix_ms)
+                .collect::<Vec<_>>();
+            assert_eq!(ids, [created_at]);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{Context, Result, anyhow};
+use pulse_core::{
+    FileRecord, GroupRecord, ItemKind, TabHistory, TabRecord, WorkspaceEntry, WorkspaceItem,
+    WorkspaceStore,
+};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeSet;
+
+// Mirrors `CURRENT_SESSION_VERSION` in `apps/ui/src/lib/session-state.ts`.
+const UI_SESSION_VERSION: u64 = 1;
+
+pub const SETTING_SELECTED_ITEM_ID: &str = "ui.selected_item_id";
+pub const SETTING_ADDRESS: &str = "ui.address";
+
+/// Host-owned workspace tables built from a UI session, and why items were left out.
+pub struct UiSessionImport {
+    pub store: WorkspaceStore,
+    /// One line per skipped item.
+    pub dropped: Vec<String>,
+}
+
+/// Converts the UI session payload (`{ version, items, selectedItemId, address }`) into
+/// host-owned workspace tables. Sibling orders are renumbered so the result always
+/// satisfies the `(parent_id, order)` invariants.
+///
+/// Like `parseWorkspaceItems` in the UI, an item that cannot be stored is skipped rather
+/// than failing the whole session: malformed items, repeated ids after the first, and items
+/// whose parent is missing or cannot hold them, with everything below them.
+pub fn store_from_ui_session(session: &Value) -> Result<UiSessionImport> {
+    let Some(session) = session.as_object() else {
+        return Err(anyhow!("UI session payload must be a JSON object"));
+    };
+
+    let version = session.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > UI_SESSION_VERSION {
+        return Err(anyhow!(
+            "Unsupported UI session version={version}. This build supports up to {UI_SESSION_VERSION}"
+        ));
+    }
+
+    let Some(raw_items) = session.get("items").and_then(Value::as_array) else {
+        return Err(anyhow!("UI session payload is missing the 'items' array"));
+    };
+
+    let mut dropped = Vec::new();
+    let mut seen_ids = BTreeSet::new();
+    // `(order, index, entry)`; ties in the UI's order keep payload order.
+    let mut entries = Vec::with_capacity(raw_items.len());
+    for (index, raw_item) in raw_items.iter().enumerate() {
+        match entry_from_ui_item(raw_item) {
+            Ok(entry) if !seen_ids.insert(entry.item.id.clone()) => {
+                dropped.push(format!(
+                    "item at index {index} repeats id '{}'",
+                    entry.item.id
+                ));
+            }
+            Ok(entry) => entries.push((entry.item.order, index, entry)),
+            Err(err) => dropped.push(format!("item at index {index}: {err:#}")),
+        }
+    }
+
+    let mut store = WorkspaceStore::default();
+    // Parents must exist before their children are inserted.
+    let mut ranked = Vec::with_capacity(entries.len());
+    let mut pending = entries;
+    loop {
+        let before = pending.len();
+        let mut deferred = Vec::new();
+        for (order, index, mut entry) in pending {
+            let parent_ready = entry
+                .item
+                .parent_id
+                .as_deref()
+                .is_none_or(|parent_id| store.item(parent_id).is_some());
+            if !parent_ready {
+                deferred.push((order, index, entry));
+                continue;
+            }
+            let id = entry.item.id.clone();
+            // Append; renumbering below restores the UI's sibling order.
+            entry.item.order = i32::MAX;
+            match store.insert(entry) {
+                Ok(()) => ranked.push((order, index, id)),
+                Err(err) => dropped.push(format!("item at index {index}: {err}")),
+            }
+        }
+
+        if deferred.is_empty() || deferred.len() == before {
+            // Whatever is left waits on a parent that never arrived, or on itself.
+            for (_, index, entry) in deferred {
+                dropped.push(format!(
+                    "item at index {index} references missing parent '{}'",
+                    entry.item.parent_id.unwrap_or_default()
+                ));
+            }
+            break;
+        }
+        pending = deferred;
+    }
+
+    ranked.sort();
+    for (_, _, id) in ranked {
+        let parent_id = store.item(&id).and_then(|item| item.parent_id.clone());
+        store.move_item(&id, parent_id.as_deref(), i32::MAX)?;
+    }
+
+    if let Some(selected) = session.get("selectedItemId").and_then(Value::as_str) {
+        store.set_setting(SETTING_SELECTED_ITEM_ID, selected);
+    }
+    if let Some(address) = session.get("address").and_then(Value::as_str) {
+        store.set_setting(SETTING_ADDRESS, address);
+    }
+
+    store.validate()?;
+    Ok(UiSessionImport { store, dropped })
+}
+
+/// Renders workspace tables back into the UI session payload shape.
+pub fn ui_session_from_store(store: &WorkspaceStore) -> Value {
+    let items = store
+        .entries()
+        .into_iter()
+        .filter_map(ui_item_from_entry)
+        .collect::<Vec<_>>();
+
+    json!({
+        "version": UI_SESSION_VERSION,
+        "items": items,
+        "selectedItemId": store.setting(SETTING_SELECTED_ITEM_ID),
+        "address": store.setting(SETTING_ADDRESS),
+    })
+}
+
+fn entry_from_ui_item(raw: &Value) -> Result<WorkspaceEntry> {
+    let Some(raw) = raw.as_object() else {
+        return Err(anyhow!("workspace item must be a JSON object"));
+    };
+
+    let id = required_string(raw, "id")?;
+    let title = required_string(raw, "title")?;
+    let kind: ItemKind = serde_json::from_value(raw.get("kind").cloned().unwrap_or(Value::Null))
+        .context("workspace item has an unknown 'kind'")?;
+    let parent_id = raw
+        .get("parentId")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let order = raw
+        .get("order")
+        .and_then(Value::as_f64)
+        .ok_or_else(|| anyhow!("workspace item '{id}' is missing a numeric 'order'"))?
+        as i32;
+
+    let mut entry = WorkspaceEntry {
+        item: WorkspaceItem {
+            id: id.clone(),
+            kind: kind.clone(),
+            parent_id,
+            title,
+            order,
+        },
+        tab: None,
+        group: None,
+        file: None,
+    };
+
+    match kind {
+        ItemKind::BrowserTab => {
+            entry.tab = Some(TabRecord {
+                url: required_string(raw, "url")?,
+                opener_id: raw
+                    .get("openerId")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                pinned: raw.get("pinned").and_then(Value::as_bool).unwrap_or(false),
+                // A malformed list only costs the tab its back/forward history.
+                history: raw
+                    .get("history")
+                    .and_then(|history| serde_json::from_value::<TabHistory>(history.clone()).ok())
+                    .and_then(TabHistory::normalized),
+            });
+        }
+        ItemKind::FileRef => {
+            entry.file = Some(FileRecord {
+                path: required_string(raw, "filePath")?,
+            });
+        }
+        ItemKind::Group => {
+            entry.group = Some(GroupRecord {
+                collapsed: raw
+                    .get("collapsed")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+            });
+        }
+        ItemKind::CapsuleView => {}
+    }
+
+    Ok(entry)
+}
+
+fn ui_item_from_entry(entry: WorkspaceEntry) -> Option<Value> {
+    let WorkspaceEntry {
+        item,
+        tab,
+        group,
+        file,
+    } = entry;
+
+    let mut value = Map::new();
+    value.insert("id".into(), json!(item.id));
+    value.insert("kind".into(), serde_json::to_value(&item.kind).ok()?);
+    value.insert("parentId".into(), json!(item.parent_id));
+    value.insert("order".into(), json!(item.order));
+    value.insert("title".into(), json!(item.title));
+
+    match item.kind {
+        ItemKind::BrowserTab => {
+            let tab = tab?;
+            value.insert("url".into(), json!(tab.url));
+            if let Some(opener_id) = tab.opener_id {
+                value.insert("openerId".into(), json!(opener_id));
+            }
+            if tab.pinned {
+                value.insert("pinned".into(), json!(true));
+            }
+            if let Some(history) = tab.history {
+                value.insert("history".into(), serde_json::to_value(history).ok()?);
+            }
+        }
+        ItemKind::FileRef => {
+            value.insert("filePath".into(), json!(file?.path));
+        }
+        ItemKind::Group => {
+            value.insert(
+                "collapsed".into(),
+                json!(group.unwrap_or_default().collapsed),
+            );
+        }
+        // The UI has no renderer for capsule views yet.
+        ItemKind::CapsuleView => return None,
+    }
+
+    Some(Value::Object(value))
+}
+
+fn required_string(raw: &Map<String, Value>, key: &str) -> Result<String> {
+    raw.get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("workspace item is missing required string '{key}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_that_cannot_be_stored_are_skipped_and_reported() {
+        let session = json!({
+            "version": 1,
+            "items": [
+                { "id": "g", "kind": "group", "title": "G", "parentId": null, "order": 0 },
+                { "id": "b", "kind": "browser-tab", "title": "B", "parentId": "g", "order": 1, "url": "https://b.test/" },
+                { "id": "a", "kind": "browser-tab", "title": "A", "parentId": "g", "order": 0, "url": "https://a.test/" },
+                { "id": "a", "kind": "group", "title": "Again", "parentId": null, "order": 1 },
+                { "id": "no-url", "kind": "browser-tab", "title": "?", "parentId": null, "order": 2 },
+                { "id": "lost", "kind": "browser-tab", "title": "L", "parentId": "gone", "order": 0, "url": "https://l.test/" },
+                { "id": "below-lost", "kind": "browser-tab", "title": "BL", "parentId": "lost", "order": 0, "url": "https://bl.test/" },
+                { "id": "f", "kind": "file-ref", "title": "F", "parentId": "a", "order": 0, "filePath": "/tmp/f" },
+                "not an item"
+            ],
+            "selectedItemId": "a",
+        });
+
+        let imported = store_from_ui_session(&session).unwrap();
+        let ids = imported
+            .store
+            .entries()
+            .into_iter()
+            .map(|entry| (entry.item.id, entry.item.order))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [("g".into(), 0), ("a".into(), 0), ("b".into(), 1)] as [(String, i32); 3]
+        );
+        assert_eq!(imported.dropped.len(), 6, "{:?}", imported.dropped);
+        assert!(imported.dropped[0].contains("repeats id 'a'"));
+        assert_eq!(imported.store.setting(SETTING_SELECTED_ITEM_ID), Some("a"));
+    }
+}